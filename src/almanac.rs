@@ -1,8 +1,45 @@
 use crate::{
-    constants::{P2_11, P2_19, P2_20, P2_21, P2_23, P2_38, SC2RAD},
-    util::{getbitu, getbitu2},
+    constants::{
+        EARTH_MU_GPS, P2_11, P2_19, P2_20, P2_21, P2_23, P2_24, P2_27, P2_30, P2_35, P2_38, P2_50,
+        P2_51, SC2RAD, SECS_PER_WEEK,
+    },
+    ephemeris::{Ephemeris, lnav_field},
+    util::{getbits, getbits2, getbitu, setbitu, setbitu2},
 };
 
+const ALM_I0: f64 = 0.3; // reference inclination, semi-circles
+pub const ALM_TOA_UNIT: u32 = 4096;
+
+// page 25 of subframe 4: A-S and configuration of each SV, health of SVs 25..32
+const ARRAY_SVCONF_IDX: [usize; 32] = [
+    68, 72, 76, 80, 90, 94, 98, 102, 106, 110, 120, 124, 128, 132, 136, 140, 150, 154, 158, 162,
+    166, 170, 180, 184, 188, 192, 196, 200, 210, 214, 218, 222,
+];
+const ARRAY_SVH_IDX_SF4: [usize; 8] = [228, 240, 246, 252, 258, 270, 276, 282];
+// page 25 of subframe 5: health of SVs 1..24
+const ARRAY_SVH_IDX_SF5: [usize; 24] = [
+    90, 96, 102, 108, 120, 126, 132, 138, 150, 156, 162, 168, 180, 186, 192, 198, 210, 216, 222,
+    228, 240, 246, 252, 258,
+];
+
+// SV id of the pages of subframe 4 (IS-GPS-200, table 20-V): 57 and 62 are
+// reserved/spare pages
+const SF4_PAGE_SVID: [u32; 25] = [
+    57, 25, 26, 27, 28, 57, 29, 30, 31, 32, 57, 62, 52, 53, 54, 57, 55, 56, 62, 62, 57, 62, 62, 62,
+    63,
+];
+
+// SV id carried by page 1..25 of subframe 4 or 5
+pub fn lnav_page_svid(subframe_id: u32, page: u32) -> u32 {
+    assert!((1..=25).contains(&page));
+    match subframe_id {
+        4 => SF4_PAGE_SVID[page as usize - 1],
+        5 if page == 25 => 51,
+        5 => page,
+        _ => 0,
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Almanac {
     pub sat: u32,    /* satellite number */
@@ -12,6 +49,7 @@ pub struct Almanac {
     /* SV orbit parameters */
     pub a: f64,
     pub e: f64,
+    pub i0: f64,
     pub omg0: f64,
     pub omg: f64,
     pub m0: f64,
@@ -23,21 +61,186 @@ pub struct Almanac {
 }
 
 impl Almanac {
+    // the almanac of the SV of eph, its orbit and clock propagated to toas
+    // of week (toas: a multiple of 4096 s)
+    pub fn from_ephemeris(eph: &Ephemeris, week: u32, toas: u32) -> Self {
+        let toa = week as f64 * SECS_PER_WEEK as f64 + toas as f64;
+        let dte = toa - (eph.week as f64 * SECS_PER_WEEK as f64 + eph.toe as f64);
+        let dtc = toa - (eph.week as f64 * SECS_PER_WEEK as f64 + eph.toc as f64);
+        let n = (EARTH_MU_GPS / eph.a.powi(3)).sqrt() + eph.deln;
+        Self {
+            sat: eph.sv.prn as u32,
+            svh: eph.svh,
+            svconf: 0,
+            a: eph.a,
+            e: eph.ecc,
+            i0: eph.i0 + eph.i_dot * dte,
+            omg0: eph.omg0 + eph.omg_dot * dte,
+            omg: eph.omg,
+            m0: eph.m0 + n * dte,
+            omg_dot: eph.omg_dot,
+            week,
+            toas,
+            f0: eph.f0 + eph.f1 * dtc,
+            f1: eph.f1,
+        }
+    }
+
     pub fn nav_decode_alm(&mut self, buf: &[u8], svid: u32) {
         assert!(svid > 0 && svid <= 32);
         self.sat = svid;
         self.e = getbitu(buf, 68, 16) as f64 * P2_21;
-        self.toas = getbitu(buf, 90, 8) * 4096;
-        let _delta_i = getbitu(buf, 98, 16) as f64 * P2_19 * SC2RAD;
+        self.toas = getbitu(buf, 90, 8) * ALM_TOA_UNIT;
+        let delta_i = getbits(buf, 98, 16) as f64 * P2_19;
+        self.i0 = (ALM_I0 + delta_i) * SC2RAD;
 
-        self.omg_dot = getbitu(buf, 120, 16) as f64 * P2_38 * SC2RAD;
+        self.omg_dot = getbits(buf, 120, 16) as f64 * P2_38 * SC2RAD;
         self.svh = getbitu(buf, 136, 8);
         let sqrt_a = getbitu(buf, 150, 24) as f64 * P2_11;
         self.a = sqrt_a * sqrt_a;
-        self.omg0 = getbitu(buf, 180, 24) as f64 * P2_23 * SC2RAD;
-        self.omg = getbitu(buf, 210, 24) as f64 * P2_23 * SC2RAD;
-        self.m0 = getbitu(buf, 240, 24) as f64 * P2_23 * SC2RAD;
-        self.f0 = getbitu2(buf, 270, 8, 289, 3) as f64 * P2_20;
-        self.f1 = getbitu(buf, 278, 11) as f64 * P2_38;
+        self.omg0 = getbits(buf, 180, 24) as f64 * P2_23 * SC2RAD;
+        self.omg = getbits(buf, 210, 24) as f64 * P2_23 * SC2RAD;
+        self.m0 = getbits(buf, 240, 24) as f64 * P2_23 * SC2RAD;
+        self.f0 = getbits2(buf, 270, 8, 289, 3) as f64 * P2_20;
+        self.f1 = getbits(buf, 278, 11) as f64 * P2_38;
+    }
+
+    // almanac page of subframe 4 or 5, the SV id being the satellite number
+    pub fn nav_encode_alm(&self, buf: &mut [u8]) {
+        assert!(self.sat > 0 && self.sat <= 32);
+        setbitu(buf, 60, 2, 1);
+        setbitu(buf, 62, 6, self.sat);
+        setbitu(buf, 68, 16, lnav_field(self.e, P2_21));
+        setbitu(buf, 90, 8, self.toas / ALM_TOA_UNIT);
+        setbitu(buf, 98, 16, lnav_field(self.i0 / SC2RAD - ALM_I0, P2_19));
+        setbitu(buf, 120, 16, lnav_field(self.omg_dot, P2_38 * SC2RAD));
+        setbitu(buf, 136, 8, self.svh);
+        setbitu(buf, 150, 24, lnav_field(self.a.sqrt(), P2_11));
+        setbitu(buf, 180, 24, lnav_field(self.omg0, P2_23 * SC2RAD));
+        setbitu(buf, 210, 24, lnav_field(self.omg, P2_23 * SC2RAD));
+        setbitu(buf, 240, 24, lnav_field(self.m0, P2_23 * SC2RAD));
+        setbitu2(buf, 270, 8, 289, 3, lnav_field(self.f0, P2_20));
+        setbitu(buf, 278, 11, lnav_field(self.f1, P2_38));
+    }
+}
+
+// page 25 of subframe 4 (SV id 63)
+pub fn nav_decode_svconf_health(buf: &[u8], alm_array: &mut [Almanac]) {
+    for (alm, &pos) in alm_array.iter_mut().zip(ARRAY_SVCONF_IDX.iter()) {
+        alm.svconf = getbitu(buf, pos, 4);
+    }
+    for (alm, &pos) in alm_array[24..].iter_mut().zip(ARRAY_SVH_IDX_SF4.iter()) {
+        alm.svh = getbitu(buf, pos, 6);
+    }
+}
+
+pub fn nav_encode_svconf_health(buf: &mut [u8], alm_array: &[Almanac]) {
+    setbitu(buf, 60, 2, 1);
+    setbitu(buf, 62, 6, 63);
+    for (alm, &pos) in alm_array.iter().zip(ARRAY_SVCONF_IDX.iter()) {
+        setbitu(buf, pos, 4, alm.svconf);
+    }
+    for (alm, &pos) in alm_array.iter().skip(24).zip(ARRAY_SVH_IDX_SF4.iter()) {
+        setbitu(buf, pos, 6, alm.svh);
+    }
+}
+
+// page 25 of subframe 5 (SV id 51): toa, week and health of SVs 1..24. The
+// week is sent modulo 256, decoded in 2048..2303.
+pub fn nav_decode_alm_health(buf: &[u8], alm_array: &mut [Almanac]) {
+    let toas = getbitu(buf, 68, 8) * ALM_TOA_UNIT;
+    let week = getbitu(buf, 76, 8) + 2048;
+    for (alm, &pos) in alm_array.iter_mut().zip(ARRAY_SVH_IDX_SF5.iter()) {
+        alm.svh = getbitu(buf, pos, 6);
+    }
+    for alm in alm_array.iter_mut() {
+        alm.week = week;
+        alm.toas = toas;
+    }
+}
+
+pub fn nav_encode_alm_health(buf: &mut [u8], alm_array: &[Almanac], toas: u32, week: u32) {
+    setbitu(buf, 60, 2, 1);
+    setbitu(buf, 62, 6, 51);
+    setbitu(buf, 68, 8, toas / ALM_TOA_UNIT);
+    setbitu(buf, 76, 8, week % 256);
+    for (alm, &pos) in alm_array.iter().zip(ARRAY_SVH_IDX_SF5.iter()) {
+        setbitu(buf, pos, 6, alm.svh);
+    }
+}
+
+// Klobuchar ionosphere parameters and GPS-UTC relationship, page 18 of
+// subframe 4 (SV id 56)
+#[derive(Default, Clone, Copy, Debug)]
+pub struct IonoUtc {
+    pub ion: [f64; 8], /* alpha0..3, beta0..3 */
+    pub utc: [f64; 8], /* A0, A1, tot, WNt, dt_LS, WN_LSF, DN, dt_LSF */
+}
+
+impl IonoUtc {
+    pub fn nav_decode_iono_utc(&mut self, buf: &[u8]) {
+        self.ion[0] = getbits(buf, 68, 8) as f64 * P2_30;
+        self.ion[1] = getbits(buf, 76, 8) as f64 * P2_27;
+        self.ion[2] = getbits(buf, 90, 8) as f64 * P2_24;
+        self.ion[3] = getbits(buf, 98, 8) as f64 * P2_24;
+        self.ion[4] = getbits(buf, 106, 8) as f64 * 2.0_f64.powi(11);
+        self.ion[5] = getbits(buf, 120, 8) as f64 * 2.0_f64.powi(14);
+        self.ion[6] = getbits(buf, 128, 8) as f64 * 2.0_f64.powi(16);
+        self.ion[7] = getbits(buf, 136, 8) as f64 * 2.0_f64.powi(16);
+
+        self.utc[0] = getbits2(buf, 180, 24, 210, 8) as f64 * P2_30;
+        self.utc[1] = getbits(buf, 150, 24) as f64 * P2_50;
+        self.utc[2] = getbitu(buf, 218, 8) as f64 * 2.0_f64.powi(12);
+        self.utc[3] = getbitu(buf, 226, 8) as f64;
+        self.utc[4] = getbits(buf, 240, 8) as f64;
+        self.utc[5] = getbitu(buf, 248, 8) as f64;
+        self.utc[6] = getbitu(buf, 256, 8) as f64;
+        self.utc[7] = getbits(buf, 270, 8) as f64;
+    }
+
+    // CNAV (message 30) and CNAV-2 (subframe 3 page 1) Klobuchar parameters
+    // starting at bit pos
+    pub fn nav_decode_cnav_iono(&mut self, buf: &[u8], pos: usize) {
+        const SCALE: [f64; 8] = [
+            P2_30, P2_27, P2_24, P2_24, 2048.0, 16384.0, 65536.0, 65536.0,
+        ];
+        for (i, scale) in SCALE.iter().enumerate() {
+            self.ion[i] = getbits(buf, pos + i * 8, 8) as f64 * scale;
+        }
+    }
+
+    // CNAV (message 33) and CNAV-2 (subframe 3 page 1) GPS-UTC parameters
+    // starting at bit pos. A2 is not kept.
+    pub fn nav_decode_cnav_utc(&mut self, buf: &[u8], pos: usize) {
+        self.utc[0] = getbits(buf, pos, 16) as f64 * P2_35;
+        self.utc[1] = getbits(buf, pos + 16, 13) as f64 * P2_51;
+        self.utc[4] = getbits(buf, pos + 36, 8) as f64;
+        self.utc[2] = getbitu(buf, pos + 44, 16) as f64 * 16.0;
+        self.utc[3] = getbitu(buf, pos + 60, 13) as f64;
+        self.utc[5] = getbitu(buf, pos + 73, 13) as f64;
+        self.utc[6] = getbitu(buf, pos + 86, 4) as f64;
+        self.utc[7] = getbits(buf, pos + 90, 8) as f64;
+    }
+
+    pub fn nav_encode_iono_utc(&self, buf: &mut [u8]) {
+        setbitu(buf, 60, 2, 1);
+        setbitu(buf, 62, 6, 56);
+        setbitu(buf, 68, 8, lnav_field(self.ion[0], P2_30));
+        setbitu(buf, 76, 8, lnav_field(self.ion[1], P2_27));
+        setbitu(buf, 90, 8, lnav_field(self.ion[2], P2_24));
+        setbitu(buf, 98, 8, lnav_field(self.ion[3], P2_24));
+        setbitu(buf, 106, 8, lnav_field(self.ion[4], 2.0_f64.powi(11)));
+        setbitu(buf, 120, 8, lnav_field(self.ion[5], 2.0_f64.powi(14)));
+        setbitu(buf, 128, 8, lnav_field(self.ion[6], 2.0_f64.powi(16)));
+        setbitu(buf, 136, 8, lnav_field(self.ion[7], 2.0_f64.powi(16)));
+
+        setbitu2(buf, 180, 24, 210, 8, lnav_field(self.utc[0], P2_30));
+        setbitu(buf, 150, 24, lnav_field(self.utc[1], P2_50));
+        setbitu(buf, 218, 8, lnav_field(self.utc[2], 2.0_f64.powi(12)));
+        setbitu(buf, 226, 8, self.utc[3] as u32 % 256);
+        setbitu(buf, 240, 8, lnav_field(self.utc[4], 1.0));
+        setbitu(buf, 248, 8, self.utc[5] as u32 % 256);
+        setbitu(buf, 256, 8, self.utc[6] as u32);
+        setbitu(buf, 270, 8, lnav_field(self.utc[7], 1.0));
     }
 }
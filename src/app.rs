@@ -7,12 +7,11 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::thread;
 
-use gnss_rs::constellation::Constellation;
-use gnss_rs::sv::SV;
-
 use crate::channel::State;
+use crate::network::RtlSdrTcpConfig;
 use crate::receiver::Receiver;
 use crate::recording::IQFileType;
+use crate::state::ChannelId;
 use crate::state::GnssState;
 
 const PI: f64 = std::f64::consts::PI;
@@ -20,11 +19,16 @@ const PI: f64 = std::f64::consts::PI;
 const WIDTH: usize = 800;
 const HEIGHT: usize = 600;
 
+const SIG_STR: [&str; 5] = ["L1CA", "L1C", "E1B", "B1I", "L1OF"];
+const SPEED_STR: [&str; 5] = ["max", "0.5x", "1x", "2x", "10x"];
+const SPEED: [f64; 5] = [0.0, 0.5, 1.0, 2.0, 10.0];
+
 pub struct GnssRcvApp {
     iq_file: String,
     iq_file_choice: usize,
     iq_type_choice: usize,
     sig_choice: usize,
+    speed_choice: usize,
     needs_stop: Arc<AtomicBool>,
     active: Arc<AtomicBool>,
     pub_state: Arc<Mutex<GnssState>>,
@@ -37,6 +41,7 @@ impl Default for GnssRcvApp {
             iq_file_choice: 0,
             iq_type_choice: 0,
             sig_choice: 0,
+            speed_choice: 0,
             active: Arc::new(AtomicBool::new(false)),
             needs_stop: Arc::new(AtomicBool::new(false)),
             pub_state: Arc::new(Mutex::new(GnssState::new())),
@@ -50,25 +55,39 @@ fn async_receive(
     file: PathBuf,
     iq_file_type: IQFileType,
     sig: &str,
+    speed: f64,
     pub_state: Arc<Mutex<GnssState>>,
 ) {
     log::info!("start_receiving");
 
     active.store(true, Ordering::SeqCst);
 
-    let mut receiver = Receiver::new(
-        false,
+    let res = Receiver::new(
+        "",
         "",
+        &RtlSdrTcpConfig::default(),
         &file,
         &iq_file_type,
+        &PathBuf::new(),
+        speed,
         2046000.0,
         0.0,
+        1,
+        0.0,
         0,
         sig,
         "",
         needs_stop.clone(),
         pub_state,
     );
+    let mut receiver = match res {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            log::warn!("failed to start the receiver: {e}");
+            active.store(false, Ordering::SeqCst);
+            return;
+        }
+    };
 
     log::info!("run_loop");
 
@@ -98,7 +117,8 @@ impl GnssRcvApp {
 
         self.pub_state = Arc::new(Mutex::new(GnssState::new()));
         let pub_state = self.pub_state.clone();
-        let sig = "L1CA";
+        let sig = SIG_STR[self.sig_choice];
+        let speed = SPEED[self.speed_choice];
         let ctx_clone = ctx.clone();
         let iq_file_type = if self.iq_file_choice == 0 {
             IQFileType::TypePairFloat32
@@ -123,6 +143,7 @@ impl GnssRcvApp {
                 iq_file.into(),
                 iq_file_type,
                 sig,
+                speed,
                 pub_state,
             );
             log::info!("thread_stop");
@@ -168,20 +189,31 @@ impl GnssRcvApp {
             });
     }
     fn update_sig_type(&mut self, ui: &mut egui::Ui) {
-        let sig_str = ["L1CA"];
-
         egui::ComboBox::from_label("signal")
             .width(30.0)
-            .selected_text(sig_str[self.sig_choice])
+            .selected_text(SIG_STR[self.sig_choice])
             .show_ui(ui, |ui| {
-                for (i, s) in sig_str.iter().enumerate() {
-                    let value = ui.selectable_value(&mut self.iq_type_choice, i, s.to_string());
+                for (i, s) in SIG_STR.iter().enumerate() {
+                    let value = ui.selectable_value(&mut self.sig_choice, i, s.to_string());
                     if value.clicked() {
                         self.sig_choice = i;
                     }
                 }
             });
     }
+    fn update_speed(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("speed")
+            .width(30.0)
+            .selected_text(SPEED_STR[self.speed_choice])
+            .show_ui(ui, |ui| {
+                for (i, s) in SPEED_STR.iter().enumerate() {
+                    let value = ui.selectable_value(&mut self.speed_choice, i, s.to_string());
+                    if value.clicked() {
+                        self.speed_choice = i;
+                    }
+                }
+            });
+    }
     fn update_start_stop(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         let button_text = if self.active.load(Ordering::SeqCst) {
             "stop"
@@ -242,6 +274,9 @@ impl GnssRcvApp {
                     ui.horizontal(|ui| {
                         self.update_sig_type(ui);
                     });
+                    ui.horizontal(|ui| {
+                        self.update_speed(ui);
+                    });
                     ui.end_row();
                     self.update_start_stop(ui, ctx);
                 });
@@ -349,15 +384,24 @@ impl GnssRcvApp {
                     ui.strong("ephemeris");
                 });
                 header.col(|ui| {
-                    ui.strong("other");
+                    ui.strong("signal");
                 });
             })
             .body(|mut body| {
-                for row_index in 1..=32 {
+                let mut id_vec: Vec<ChannelId> = self
+                    .pub_state
+                    .lock()
+                    .unwrap()
+                    .channels
+                    .keys()
+                    .cloned()
+                    .collect();
+                id_vec.sort();
+                for id in id_vec {
                     let row_height = 20.0;
-                    let sv = SV::new(Constellation::GPS, row_index);
                     let pub_state = self.pub_state.lock().unwrap();
-                    let channel = pub_state.channels.get(&sv);
+                    let channel = pub_state.channels.get(&id);
+                    let (sv, sig) = id;
 
                     if channel.is_none() {
                         continue;
@@ -393,7 +437,7 @@ impl GnssRcvApp {
                             ui.label(s.to_string());
                         });
                         row.col(|ui| {
-                            ui.label("".to_string());
+                            ui.label(sig.to_string());
                         });
                     });
                 }
@@ -13,6 +13,8 @@ use crate::navigation::Navigation;
 use crate::plots::plot_iq_scatter;
 use crate::plots::plot_time_graph;
 use crate::plots::plot_time_graph_with_sz;
+use crate::state::ChannelEvent;
+use crate::state::ChannelId;
 use crate::state::ChannelState;
 use crate::state::GnssState;
 use crate::util::calc_correlation;
@@ -32,10 +34,13 @@ const B_PLL: f64 = 10.0; // bandwidth of PLL filter Hz
 const B_DLL: f64 = 0.5; // bandwidth of DLL filter Hz
 
 const DOPPLER_SPREAD_HZ: f64 = 8000.0;
-const DOPPLER_SPREAD_BINS: usize = 50;
+const DOPPLER_SPREAD_BINS: usize = 50; // per msec of coherent integration
 const HISTORY_NUM: usize = 20000;
 const CN0_THRESHOLD_LOCKED: f64 = 35.0;
 const CN0_THRESHOLD_LOST: f64 = 29.0;
+const THRESHOLD_SEC_SYNC: f64 = 0.8;
+const NEUTRAL_CHIPS: f64 = 40.0; // offset of the noise (neutral) correlator
+const T_SEC_SYNC_PILOT: f64 = 1.0; // interval between overlay code searches
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum State {
@@ -46,7 +51,8 @@ pub enum State {
 
 #[derive(Default)]
 pub struct Tracking {
-    prn_code: Vec<Complex64>, // upsampled
+    prn_code: Vec<Complex64>,   // upsampled
+    prn_code_d: Vec<Complex64>, // upsampled data component, when tracking a pilot
     doppler_hz: f64,
     code_off_sec: f64,
     cn0: f64,
@@ -57,6 +63,10 @@ pub struct Tracking {
     sum_corr_l: f64,
     sum_corr_p: f64,
     sum_corr_n: f64,
+    sec_sync: usize, // start of secondary code in num_trk_samples, 0 if unknown
+    sec_pol: f64,    // polarity of the pilot once its overlay code is known
+    num_sec_sync_try: usize,
+    seg_off: usize, // long code period of the first tracked period
 }
 
 #[derive(Default)]
@@ -67,6 +77,7 @@ pub struct History {
     phi_error: Vec<f64>,
     doppler_hz: Vec<f64>,
     pub corr_p: Vec<Complex64>,
+    pub corr_d: Vec<Complex64>, // prompt of the data component
 }
 
 impl History {
@@ -83,6 +94,10 @@ impl History {
             self.corr_p.rotate_left(1);
             self.corr_p.pop();
         }
+        if self.corr_d.len() > HISTORY_NUM {
+            self.corr_d.rotate_left(1);
+            self.corr_d.pop();
+        }
         if self.code_phase_offset.len() > HISTORY_NUM {
             self.code_phase_offset.rotate_left(1);
             self.code_phase_offset.pop();
@@ -94,18 +109,27 @@ impl History {
 pub struct Acquisition {
     prn_code_fft: Vec<Complex64>,
     sum_p: Vec<Vec<f64>>,
+    doppler_bins: usize,
+    num_seg_try: usize, // periods searched for the start of a long code
 }
 
 pub struct Channel {
     pub pub_state: Arc<Mutex<GnssState>>,
     pub sv: SV,
+    pub sig: String,
+    pub id: ChannelId,
     fc: f64, // carrier frequency
     fs: f64, // sampling frequency
-    fi: f64, // intermediate frequency
+    fi: f64, // intermediate frequency, including the carrier offset
 
     code_sec: f64,   // code duration in sec
     code_len: usize, // prn code len: e.g. 1023
     code_sp: usize,  // samples per upsampled code: e.g. 2046 for L1CA
+    sp_chip: f64,    // samples per chip, not necessarily an integer
+    code_ms: usize,  // receiver periods per code: e.g. 4 for E1B
+    num_periods: usize,
+    sec_code: Option<Vec<i8>>, // secondary code, e.g. NH20 for B1I
+    long_code: Vec<i8>,        // code spanning several periods, e.g. L2CL
 
     fft_planner: FftPlanner<f64>,
     state: State,
@@ -136,6 +160,10 @@ impl Channel {
         self.trk.cn0
     }
 
+    pub fn get_code_off_sec(&self) -> f64 {
+        self.trk.code_off_sec
+    }
+
     pub fn is_state_tracking(&self) -> bool {
         self.state == State::Tracking
     }
@@ -154,7 +182,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .state
             .clone();
@@ -163,7 +191,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .state = state.clone();
 
@@ -182,7 +210,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .state
             .clone();
@@ -191,7 +219,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .phi = self.trk.phi;
 
@@ -206,7 +234,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .state
             .clone();
@@ -215,7 +243,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .code_idx = *self.hist.code_phase_offset.last().unwrap();
 
@@ -229,7 +257,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .state
             .clone();
@@ -238,7 +266,7 @@ impl Channel {
             .lock()
             .unwrap()
             .channels
-            .get_mut(&self.sv)
+            .get_mut(&self.id)
             .unwrap()
             .doppler_hz = self.trk.doppler_hz;
 
@@ -249,49 +277,85 @@ impl Channel {
     fn update_state_cn0(&mut self) {
         let need_update = {
             let mut st = self.pub_state.lock().unwrap();
-            st.channels.get_mut(&self.sv).unwrap().cn0 = self.trk.cn0;
-            st.channels.get(&self.sv).unwrap().state == State::Tracking
+            st.channels.get_mut(&self.id).unwrap().cn0 = self.trk.cn0;
+            st.channels.get(&self.id).unwrap().state == State::Tracking
         };
         if need_update {
             (self.pub_state.lock().unwrap().update_func.func)();
         }
     }
 
-    pub fn new(sig: &str, sv: SV, fs: f64, fi: f64, pub_state: Arc<Mutex<GnssState>>) -> Self {
-        let code_buf = Code::gen_code(sig, sv.prn).unwrap();
+    // fo is the carrier offset of this satellite relative to the signal
+    // frequency, e.g. k * 562.5 KHz for GLONASS frequency channel k.
+    pub fn new(
+        sig: &str,
+        sv: SV,
+        fs: f64,
+        fi: f64,
+        fo: f64,
+        pub_state: Arc<Mutex<GnssState>>,
+    ) -> Option<Self> {
+        let code_prn = Code::get_code_prn(sv);
+        let code_buf = Code::gen_code(sig, code_prn)?;
         let code_sec = Code::get_code_period(sig);
         let code_len = Code::get_code_len(sig);
-        let code_sp = (fs * code_sec) as usize;
+        let code_ms = (code_sec / 1e-3).round() as usize;
+        // a whole number of receiver periods: when fs is not a multiple of
+        // 1 KHz, code_sp samples last slightly less than a code period.
+        let code_sp = code_ms * (fs * 1e-3) as usize;
+        let sp_chip = fs * code_sec / code_len as f64;
+        let doppler_bins = DOPPLER_SPREAD_BINS * code_ms;
         let mut fft_planner = FftPlanner::new();
 
-        let prn_code: Vec<_> = code_buf
-            .iter()
-            .map(|&x| Complex64::new(x as f64, 0.0))
-            .flat_map(|x| [x, x])
-            .collect();
+        // the acquisition of a long code looks for its first period
+        let prn_code = Self::gen_replica(sig, &code_buf[..code_len], code_sp, sp_chip);
+        let long_code = if code_buf.len() > code_len {
+            code_buf
+        } else {
+            vec![]
+        };
+        let prn_code_d = match Code::get_data_signal(sig) {
+            Some(sig_d) => {
+                let code_d = Code::gen_code(sig_d, code_prn)?;
+                Self::gen_replica(sig_d, &code_d, code_sp, sp_chip)
+            }
+            None => vec![],
+        };
+        let sec_code = Code::get_secondary_code(sig, code_prn);
+        if !prn_code_d.is_empty() && sec_code.is_none() {
+            return None;
+        }
 
         let mut prn_code_fft = prn_code.clone();
 
         let fft_fw = fft_planner.plan_fft_forward(prn_code_fft.len());
         fft_fw.process(&mut prn_code_fft);
 
+        let id = (sv, sig.to_string());
         pub_state
             .lock()
             .unwrap()
             .channels
-            .insert(sv, ChannelState::default());
+            .insert(id.clone(), ChannelState::default());
 
-        Self {
+        Some(Self {
             pub_state: pub_state.clone(),
             sv,
+            sig: sig.to_string(),
+            id,
             fft_planner,
             ts_sec: 0.0,
-            fc: Code::get_code_freq(sig),
+            fc: Code::get_code_freq(sig) + fo,
             fs,
-            fi,
+            fi: fi + fo,
             code_sec,
             code_len,
             code_sp,
+            sp_chip,
+            code_ms,
+            num_periods: 0,
+            sec_code,
+            long_code,
 
             num_acq_samples: 0,
             num_idl_samples: 0,
@@ -302,17 +366,49 @@ impl Channel {
             hist: History::default(),
             trk: Tracking {
                 prn_code,
+                prn_code_d,
                 ..Default::default()
             },
             acq: Acquisition {
                 prn_code_fft,
-                sum_p: vec![vec![0.0; code_sp]; DOPPLER_SPREAD_BINS],
+                sum_p: vec![vec![0.0; code_sp]; doppler_bins],
+                doppler_bins,
+                num_seg_try: 0,
             },
+        })
+    }
+
+    // replica of one code period sampled at fs: each sample takes the chip
+    // at its code phase, so that sp_chip need not be an integer.
+    fn gen_replica(sig: &str, code: &[i8], code_sp: usize, sp_chip: f64) -> Vec<Complex64> {
+        let code_len = code.len();
+        (0..code_sp)
+            .map(|i| {
+                let chip = i as f64 / sp_chip;
+                let idx = usize::min(chip as usize, code_len - 1);
+                let phase = chip - idx as f64;
+                code[idx] as f64 * Code::get_subcarrier(sig, idx, phase, sp_chip)
+            })
+            .map(|x| Complex64::new(x, 0.0))
+            .collect()
+    }
+
+    fn push_event(&self, label: &str) {
+        let mut pub_state = self.pub_state.lock().unwrap();
+        if !pub_state.record_events {
+            return;
         }
+        pub_state.events.push(ChannelEvent {
+            ts_sec: self.ts_sec,
+            id: self.id.clone(),
+            label: label.to_string(),
+            cn0: self.trk.cn0,
+        });
     }
 
     fn idle_start(&mut self) {
         if self.state == State::Tracking {
+            self.push_event("LOST");
             log::warn!(
                 "{}: {} cn0={:.1} ts_sec={:.3}",
                 self.sv,
@@ -343,7 +439,7 @@ impl Channel {
     }
 
     fn acquisition_init(&mut self) {
-        self.acq.sum_p = vec![vec![0.0; self.code_sp]; DOPPLER_SPREAD_BINS];
+        self.acq.sum_p = vec![vec![0.0; self.code_sp]; self.acq.doppler_bins];
         self.num_acq_samples = 0;
         self.num_idl_samples = 0;
         self.num_trk_samples = 0;
@@ -351,6 +447,7 @@ impl Channel {
 
     fn acquisition_start(&mut self) {
         self.acquisition_init();
+        self.acq.num_seg_try = 0;
         self.set_state(State::Acquisition);
     }
 
@@ -364,6 +461,10 @@ impl Channel {
         self.trk.sum_corr_e = 0.0;
         self.trk.sum_corr_l = 0.0;
         self.trk.sum_corr_n = 0.0;
+        self.trk.sec_sync = 0;
+        self.trk.sec_pol = 1.0;
+        self.trk.num_sec_sync_try = 0;
+        self.trk.seg_off = 0;
         self.num_trk_samples = 0;
         self.num_acq_samples = 0;
         self.num_idl_samples = 0;
@@ -392,6 +493,7 @@ impl Channel {
         self.update_state_doppler_hz();
         self.trk.cn0 = cn0;
         self.update_state_cn0();
+        self.push_event("LOCK");
     }
 
     fn acquisition_integrate_correlation(
@@ -469,9 +571,9 @@ impl Channel {
     fn acquisition_process(&mut self, iq_vec: &[Complex64]) {
         // only take the last minute worth of data
         let iq_vec_slice = &iq_vec[self.code_sp..];
-        let step_hz = 2.0 * DOPPLER_SPREAD_HZ / DOPPLER_SPREAD_BINS as f64;
+        let step_hz = 2.0 * DOPPLER_SPREAD_HZ / self.acq.doppler_bins as f64;
 
-        for i in 0..DOPPLER_SPREAD_BINS {
+        for i in 0..self.acq.doppler_bins {
             let doppler_hz = -DOPPLER_SPREAD_HZ + i as f64 * step_hz;
             let c_non_coherent = self.acquisition_integrate_correlation(iq_vec_slice, doppler_hz);
             assert_eq!(c_non_coherent.len(), self.code_sp);
@@ -491,7 +593,7 @@ impl Channel {
             let mut p_peak = 0.0;
             let mut p_total = 0.0;
 
-            for i in 0..DOPPLER_SPREAD_BINS {
+            for i in 0..self.acq.doppler_bins {
                 let p_sum = self.acq.sum_p[i].iter().sum();
                 let (j_peak, v_peak) = get_max_with_idx(&self.acq.sum_p[i]);
 
@@ -505,12 +607,22 @@ impl Channel {
             }
 
             let doppler_hz = -DOPPLER_SPREAD_HZ + (idx as f64 + 0.5) * step_hz;
-            let code_off_sec = code_offset_idx as f64 / self.code_sp as f64 * self.code_sec;
-            let p_avg = p_total / self.acq.sum_p[idx].len() as f64 / DOPPLER_SPREAD_BINS as f64;
+            let code_off_sec = code_offset_idx as f64 / self.fs;
+            let p_avg = p_total / self.acq.sum_p[idx].len() as f64 / self.acq.doppler_bins as f64;
             let cn0 = 10.0 * ((p_peak - p_avg) / p_avg / self.code_sec).log10();
 
+            let num_seg = self.long_code.len() / self.code_len;
             if cn0 >= CN0_THRESHOLD_LOCKED {
+                let seg_off = if num_seg > 1 {
+                    self.acquisition_long_code_offset(iq_vec_slice, doppler_hz, code_offset_idx)
+                } else {
+                    0
+                };
                 self.tracking_start(doppler_hz, cn0, code_off_sec, code_offset_idx);
+                self.trk.seg_off = seg_off;
+            } else if self.acq.num_seg_try < num_seg {
+                // the first period of a long code comes by once per long code
+                self.acq.num_seg_try += 1;
             } else {
                 self.idle_start();
             }
@@ -518,13 +630,46 @@ impl Channel {
         }
     }
 
+    // The correlation peak of a long code is found in a period holding either
+    // the start of its first period, at the peak code offset, or the end of
+    // it. The stronger part of the correlation tells which one: returns the
+    // long code period of the next tracked period.
+    fn acquisition_long_code_offset(
+        &self,
+        iq_vec_slice: &[Complex64],
+        doppler_hz: f64,
+        code_offset_idx: usize,
+    ) -> usize {
+        let n = self.code_sp;
+        let code = &self.long_code[..self.code_len];
+        let replica = Self::gen_replica(&self.sig, code, n, self.sp_chip);
+        let mut iq_vec = iq_vec_slice.to_vec();
+        doppler_shift(&mut iq_vec, self.fi + doppler_hz, 0.0, self.fs);
+
+        let mut head = Complex64::default();
+        let mut tail = Complex64::default();
+        for (i, r) in replica.iter().enumerate() {
+            let c = iq_vec[(i + code_offset_idx) % n] * r;
+            if i + code_offset_idx < n {
+                head += c;
+            } else {
+                tail += c;
+            }
+        }
+        if head.norm() >= tail.norm() { 0 } else { 1 }
+    }
+
     fn tracking_compute_correlation(
         &mut self,
         iq_vec2: &[Complex64],
-    ) -> (Complex64, Complex64, Complex64, Complex64) {
+    ) -> (Complex64, Complex64, Complex64, Complex64, Complex64) {
         let n = self.code_sp as i32;
         let code_idx = *self.hist.code_phase_offset.last().unwrap() as i32;
-        assert!(-n < code_idx && code_idx < n);
+        // code_sp may be short of a code period by a fraction of a sample per
+        // receiver period: a code starting in that fraction is correlated
+        // from the end of the first window.
+        let code_idx = code_idx.min(n);
+        assert!(-n < code_idx && code_idx <= n);
 
         //       [-------][-------][---------]
         // t=n   [^(.......)      ]                code_idx=0
@@ -540,14 +685,20 @@ impl Channel {
         let hi_u = (lo + n) as usize;
         let mut signal = iq_vec2[lo_u..hi_u].to_vec();
 
-        doppler_shift(&mut signal, self.trk.doppler_hz, self.trk.phi, self.fs);
+        doppler_shift(
+            &mut signal,
+            self.fi + self.trk.doppler_hz,
+            self.trk.phi,
+            self.fs,
+        );
 
-        let pos = (SP_CORR * self.code_sec * self.fs / self.code_len as f64) as usize;
+        let pos = self.get_corr_spacing();
 
         let mut corr_prompt = Complex64::default();
         let mut corr_early = Complex64::default();
         let mut corr_late = Complex64::default();
         let mut corr_neutral = Complex64::default();
+        let mut corr_data = Complex64::default();
 
         // PROMPT
         for (j, sig_val) in signal.iter().enumerate() {
@@ -555,6 +706,14 @@ impl Channel {
         }
         corr_prompt /= signal.len() as f64;
 
+        // DATA PROMPT:
+        if !self.trk.prn_code_d.is_empty() {
+            for (j, sig_val) in signal.iter().enumerate() {
+                corr_data += sig_val * self.trk.prn_code_d[j];
+            }
+            corr_data /= signal.len() as f64;
+        }
+
         // EARLY:
         #[allow(clippy::needless_range_loop)]
         for j in 0..signal.len() - pos {
@@ -569,14 +728,20 @@ impl Channel {
         corr_late /= (signal.len() - pos) as f64;
 
         // NEUTRAL:
-        let pos_neutral: usize = 80;
+        let pos_neutral = (NEUTRAL_CHIPS * self.sp_chip) as usize;
         #[allow(clippy::needless_range_loop)]
         for j in 0..signal.len() - pos_neutral {
             corr_neutral += signal[j] * self.trk.prn_code[pos_neutral + j];
         }
         corr_neutral /= (signal.len() - pos_neutral) as f64;
 
-        (corr_prompt, corr_early, corr_late, corr_neutral)
+        (corr_prompt, corr_early, corr_late, corr_neutral, corr_data)
+    }
+
+    // early/late correlator offset in samples, as close as possible to SP_CORR
+    // chips
+    fn get_corr_spacing(&self) -> usize {
+        usize::max(1, (SP_CORR * self.sp_chip).round() as usize)
     }
 
     fn run_fll(&mut self) {
@@ -619,13 +784,14 @@ impl Channel {
 
     fn run_dll(&mut self, c_e: Complex64, c_l: Complex64) {
         let n = usize::max(1, (T_DLL / self.code_sec) as usize);
-        assert_eq!(n, 10);
         self.trk.sum_corr_e += c_e.norm();
         self.trk.sum_corr_l += c_l.norm();
         if self.num_trk_samples % n == 0 {
             let e = self.trk.sum_corr_e;
             let l = self.trk.sum_corr_l;
-            let err_code = (e - l) / (e + l) / 2.0 * self.code_sec / self.code_len as f64;
+            // the discriminator scales with the actual correlator spacing
+            let d = self.get_corr_spacing() as f64 / self.sp_chip;
+            let err_code = (e - l) / (e + l) * (1.0 - d) * self.code_sec / self.code_len as f64;
             self.trk.code_off_sec -= B_DLL / 0.25 * err_code * self.code_sec * n as f64;
             self.trk.sum_corr_e = 0.0;
             self.trk.sum_corr_l = 0.0;
@@ -648,15 +814,19 @@ impl Channel {
         }
     }
     fn get_code_and_carrier_phase(&mut self) {
-        let tau = self.code_sec;
+        let tau = self.code_sp as f64 / self.fs; // time between two windows
         let fc = self.fi + self.trk.doppler_hz;
         self.trk.adr += self.trk.doppler_hz * tau; // accumulated Doppler
         self.trk.code_off_sec -= self.trk.doppler_hz / self.fc * tau; // carrier-aided code offset
+        // code epochs drift within the windows unless code_sp covers exactly
+        // one code period
+        self.trk.code_off_sec += self.code_sec - tau;
 
         if self.trk.code_off_sec >= self.code_sec {
             self.trk.code_off_sec -= self.code_sec;
             self.num_trk_samples -= 1;
             self.hist.corr_p.pop();
+            self.hist.corr_d.pop();
             // 0-1-2-3-4
             // 0-0-1-2-3
             // 0-1-2-3-5
@@ -665,6 +835,9 @@ impl Channel {
             self.num_trk_samples += 1;
             let v = self.hist.corr_p.last().unwrap();
             self.hist.corr_p.push(*v);
+            if let Some(v) = self.hist.corr_d.last() {
+                self.hist.corr_d.push(*v);
+            }
             // 0-1-2-3-4
             // 1-2-3-4-4
             // 2-3-4-4-5
@@ -672,7 +845,9 @@ impl Channel {
 
         // code offset in samples
         let code_off = self.trk.code_off_sec * self.fs;
-        self.trk.phi = self.fi * tau + self.trk.adr + fc * code_off / self.fs;
+        // IF carrier phase at the beginning of the 2-code window
+        let phi_if = self.fi * (self.ts_sec - tau);
+        self.trk.phi = phi_if + self.trk.adr + fc * code_off / self.fs;
         self.update_state_phi();
 
         self.hist.code_phase_offset.push(code_off);
@@ -697,12 +872,160 @@ impl Channel {
         }
     }
 
+    fn get_secondary_chip(sec_code: &[i8], sec_sync: usize, idx: usize) -> f64 {
+        let len = sec_code.len();
+        sec_code[(idx + len - sec_sync % len) % len] as f64
+    }
+
+    // Look for the secondary code boundary using the sign of the products of
+    // consecutive prompt correlations: these only depend on the secondary
+    // code, except across a secondary code boundary where a navigation bit
+    // transition may occur.
+    fn secondary_code_sync(&mut self) {
+        let Some(sec_code) = &self.sec_code else {
+            return;
+        };
+        let len = sec_code.len();
+        let num = self.hist.corr_p.len();
+        if num < 2 * len + 1 {
+            return;
+        }
+        let corr = &self.hist.corr_p[num - 2 * len - 1..];
+        let mut scores = vec![0.0; len];
+        let mut num_pairs = 0;
+
+        for (k, score) in scores.iter_mut().enumerate() {
+            num_pairs = 0;
+            for i in 0..2 * len {
+                if (i + k + 1) % len == 0 {
+                    continue;
+                }
+                let d = (corr[i].re * corr[i + 1].re).signum();
+                let c = (sec_code[(i + k) % len] * sec_code[(i + k + 1) % len]) as f64;
+                *score += d * c;
+                num_pairs += 1;
+            }
+        }
+        let (k, score) = get_max_with_idx(&scores);
+        let num_best = scores.iter().filter(|&&v| v == score).count();
+        if score < THRESHOLD_SEC_SYNC * num_pairs as f64 || num_best > 1 {
+            return;
+        }
+
+        // corr[0] has index num_trk_samples - 2 * len and chip k
+        self.trk.sec_sync = self.num_trk_samples + len - k;
+        log::info!(
+            "{}: SECONDARY CODE SYNC: k={k} score={score}/{num_pairs} ts={:.3}",
+            self.sv,
+            self.ts_sec
+        );
+
+        // wipe off the secondary code from the correlation history
+        let last = self.num_trk_samples;
+        for j in 0..usize::min(num, last) {
+            let chip = Self::get_secondary_chip(sec_code, self.trk.sec_sync, last - j);
+            self.hist.corr_p[num - 1 - j] *= chip;
+        }
+    }
+
+    // A pilot carries no navigation data: its overlay code is found by
+    // correlating the prompt history with every rotation of the code. The sign
+    // of the correlation resolves the half-cycle ambiguity of the PLL.
+    fn overlay_code_sync(&mut self) {
+        let Some(sec_code) = &self.sec_code else {
+            return;
+        };
+        let len = sec_code.len();
+        let num = self.hist.corr_p.len();
+        if num < len || self.num_trk_samples < len {
+            return;
+        }
+        let corr = &self.hist.corr_p[num - len..];
+        let total: f64 = corr.iter().map(|c| c.re.abs()).sum();
+        let scores: Vec<f64> = (0..len)
+            .map(|k| {
+                corr.iter()
+                    .enumerate()
+                    .map(|(i, c)| c.re * sec_code[(i + k) % len] as f64)
+                    .sum()
+            })
+            .collect();
+        let scores_abs: Vec<_> = scores.iter().map(|v| v.abs()).collect();
+        let (k, score) = get_max_with_idx(&scores_abs);
+        if score < THRESHOLD_SEC_SYNC * total {
+            return;
+        }
+
+        // corr[0] has index num_trk_samples - len + 1 and chip k
+        self.trk.sec_sync = self.num_trk_samples + 1 + len - k;
+        self.trk.sec_pol = scores[k].signum();
+        log::info!(
+            "{}: OVERLAY CODE SYNC: k={k} score={score:.2}/{total:.2} pol={} ts={:.3}",
+            self.sv,
+            self.trk.sec_pol,
+            self.ts_sec
+        );
+
+        // wipe off the overlay code from the correlation history
+        let last = self.num_trk_samples;
+        for j in 0..usize::min(num, last) {
+            let chip = Self::get_secondary_chip(sec_code, self.trk.sec_sync, last - j);
+            self.hist.corr_p[num - 1 - j] *= chip * self.trk.sec_pol;
+        }
+        for c in self.hist.corr_d.iter_mut() {
+            *c *= self.trk.sec_pol;
+        }
+    }
+
+    // true when the last code period carried the last chip of the secondary
+    // code, e.g. at the end of a CNAV-2 frame for L1C
+    pub fn is_secondary_code_end(&self) -> bool {
+        match &self.sec_code {
+            Some(sec_code) if self.trk.sec_sync != 0 => {
+                let len = sec_code.len();
+                (self.num_trk_samples + len - self.trk.sec_sync % len) % len == len - 1
+            }
+            _ => false,
+        }
+    }
+
     fn tracking_process(&mut self, iq_vec: &[Complex64]) {
         self.get_code_and_carrier_phase();
-        let (c_p, c_e, c_l, c_n) = self.tracking_compute_correlation(iq_vec);
+        if !self.long_code.is_empty() {
+            let num_seg = self.long_code.len() / self.code_len;
+            let k = (self.num_trk_samples + self.trk.seg_off) % num_seg;
+            let code = &self.long_code[k * self.code_len..(k + 1) * self.code_len];
+            self.trk.prn_code = Self::gen_replica(&self.sig, code, self.code_sp, self.sp_chip);
+        }
+        let (mut c_p, c_e, c_l, c_n, mut c_d) = self.tracking_compute_correlation(iq_vec);
+        if let Some(sec_code) = &self.sec_code
+            && self.trk.sec_sync != 0
+        {
+            let idx = self.num_trk_samples + 1;
+            c_p *= Self::get_secondary_chip(sec_code, self.trk.sec_sync, idx) * self.trk.sec_pol;
+            c_d *= self.trk.sec_pol;
+        }
         self.hist.corr_p.push(c_p);
+        if !self.trk.prn_code_d.is_empty() {
+            self.hist.corr_d.push(c_d);
+        }
         self.num_trk_samples += 1;
 
+        if self.sec_code.is_some()
+            && self.trk.sec_sync == 0
+            && self.num_trk_samples as f64 * self.code_sec >= T_FPULLIN
+        {
+            if self.trk.prn_code_d.is_empty() {
+                self.secondary_code_sync();
+            } else {
+                self.trk.num_sec_sync_try += 1;
+                if self.trk.num_sec_sync_try as f64 * self.code_sec >= T_SEC_SYNC_PILOT {
+                    self.trk.num_sec_sync_try = 0;
+                    self.overlay_code_sync();
+                }
+            }
+        }
+
         if self.num_trk_samples as f64 * self.code_sec < T_FPULLIN {
             self.run_fll();
         } else {
@@ -712,7 +1035,9 @@ impl Channel {
         self.run_dll(c_e, c_l);
         self.update_cn0(c_p, c_n);
 
-        if self.num_trk_samples as f64 * self.code_sec >= T_NPULLIN {
+        if self.num_trk_samples as f64 * self.code_sec >= T_NPULLIN
+            && (self.sec_code.is_none() || self.trk.sec_sync != 0)
+        {
             self.nav_decode();
         }
 
@@ -728,8 +1053,48 @@ impl Channel {
         }
     }
 
+    // The feed skipped skip_sec worth of samples, e.g. samples lost by a live
+    // source: the code and carrier phases of a tracked signal move on
+    // accordingly, and the code periods missed get empty correlations to
+    // keep the symbol sync. An acquisition in progress starts over.
+    pub fn skip_samples(&mut self, skip_sec: f64) {
+        match self.state {
+            State::Tracking => {
+                self.trk.adr += self.trk.doppler_hz * skip_sec;
+                let code_off_sec =
+                    self.trk.code_off_sec - skip_sec * (1.0 + self.trk.doppler_hz / self.fc);
+                let num_codes = (-code_off_sec / self.code_sec).ceil().max(0.0) as usize;
+                self.trk.code_off_sec = code_off_sec + num_codes as f64 * self.code_sec;
+                for _ in 0..num_codes {
+                    self.hist.corr_p.push(Complex64::default());
+                    if !self.trk.prn_code_d.is_empty() {
+                        self.hist.corr_d.push(Complex64::default());
+                    }
+                }
+                self.num_trk_samples += num_codes;
+                log::warn!(
+                    "{}: {:.3} sec skipped: {num_codes} codes",
+                    self.sv,
+                    skip_sec
+                );
+            }
+            State::Acquisition => self.acquisition_init(),
+            State::Idle => {}
+        }
+    }
+
+    // iq_vec holds the most recent samples, ts_sec is the timestamp of the
+    // last receiver period. Codes longer than a period are only processed
+    // once a full code worth of samples has been received.
     pub fn process_samples(&mut self, iq_vec: &[Complex64], ts_sec: f64) {
-        self.ts_sec = ts_sec;
+        self.num_periods += 1;
+        if self.num_periods < self.code_ms {
+            return;
+        }
+        self.num_periods = 0;
+        let iq_vec = &iq_vec[iq_vec.len() - 2 * self.code_sp..];
+        // start of the last code: code_ms - 1 periods before the last one
+        self.ts_sec = ts_sec - (self.code_sp - self.code_sp / self.code_ms) as f64 / self.fs;
 
         #[allow(clippy::overly_complex_bool_expr)]
         if false && self.state != State::Idle {
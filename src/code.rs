@@ -1,8 +1,77 @@
+use gnss_rs::constellation::Constellation;
+use gnss_rs::sv::SV;
+use std::fs;
+
 pub const L1CA_CODE_LEN: usize = 1023;
+// QZSS L1C/A codes are part of the GPS L1C/A code family: J01 uses PRN 193
+pub const QZSS_PRN_OFFSET: u8 = 192;
+pub const E1_CODE_LEN: usize = 4092;
+// Galileo PRNs allocated by the OS SIS ICD
+pub const GAL_PRN_MAX: u8 = 36;
+pub const B1I_CODE_LEN: usize = 2046;
+pub const L1OF_CODE_LEN: usize = 511;
+pub const L1C_CODE_LEN: usize = 10230;
+pub const L1CO_CODE_LEN: usize = 1800;
+pub const L5_CODE_LEN: usize = 10230;
+pub const L2CM_CODE_LEN: usize = 10230;
+pub const L2CL_CODE_LEN: usize = 767250;
+pub const E5AQ_SEC_CODE_LEN: usize = 100;
+
+// GLONASS FDMA frequency channel spacing on L1
+pub const L1OF_FREQ_STEP: f64 = 562.5e3;
+
+// Neuman-Hofman code modulating the D1 navigation message (MEO/IGSO)
+pub const NH20_CODE: [i8; 20] = [
+    1, 1, 1, 1, 1, -1, 1, 1, -1, -1, 1, -1, 1, -1, 1, 1, -1, -1, -1, 1,
+];
+
+// Neuman-Hofman codes of GPS L5I and L5Q
+pub const NH10_CODE: [i8; 10] = [1, 1, 1, 1, -1, -1, 1, -1, 1, -1];
+
+// Galileo E5aI secondary code CS20_1 (842E9)
+pub const E5AI_SEC_CODE: [i8; 20] = [
+    -1, 1, 1, 1, 1, -1, 1, 1, 1, 1, -1, 1, -1, -1, -1, 1, -1, 1, 1, -1,
+];
+
+// Galileo E1 primary codes are memory codes: they cannot be generated by
+// a shift register and are listed in hex in the Galileo OS SIS ICD, annex C.
+// One line per PRN, in PRN order. The tables are looked up in the source
+// tree, whatever the working directory.
+const E1B_CODE_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/galileo_e1b.txt"
+);
+const E1C_CODE_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/galileo_e1c.txt"
+);
+
+// GPS L1C: Weil index and insertion index of the L1CD and L1CP codes, and
+// L1CO overlay codes (IS-GPS-800, tables 3.2-2 and 3.2-3).
+const L1C_WEIL_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/gps_l1c_weil.txt"
+);
+const L1CO_CODE_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/codes/gps_l1co.txt");
+
+// Galileo E5a register 2 start values and E5aQ secondary codes (Galileo OS
+// SIS ICD, table 17 and annex D).
+const E5A_START_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/galileo_e5a_start.txt"
+);
+const E5AQ_SEC_CODE_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/galileo_e5aq_sec.txt"
+);
+// E5a base register feedback taps, octal (Galileo OS SIS ICD, table 16)
+const E5A_TAPS1: u32 = 0o40503;
+const E5A_TAPS2: u32 = 0o50661;
 
 pub struct Code {}
 
 impl Code {
+    // G2 delays for PRN 1..210: GPS (1-63), SBAS (120-158), QZSS (193-202)
     fn gen_l1ca_code(prn: u8) -> Vec<i8> {
         const G2_DELAY: [usize; 210] = [
             5, 6, 7, 8, 17, 18, 139, 140, 141, 251, 252, 254, 255, 256, 257, 258, 469, 470, 471,
@@ -45,16 +114,465 @@ impl Code {
         g
     }
 
+    fn gen_b1i_code(prn: u8) -> Option<Vec<i8>> {
+        // G2 phase assignment (BDS-SIS-ICD-B1I)
+        const PH_SEL: [(usize, usize); 37] = [
+            (1, 3),
+            (1, 4),
+            (1, 5),
+            (1, 6),
+            (1, 8),
+            (1, 9),
+            (1, 10),
+            (1, 11),
+            (2, 7),
+            (3, 4),
+            (3, 5),
+            (3, 6),
+            (3, 8),
+            (3, 9),
+            (3, 10),
+            (3, 11),
+            (4, 5),
+            (4, 6),
+            (4, 8),
+            (4, 9),
+            (4, 10),
+            (4, 11),
+            (5, 6),
+            (5, 8),
+            (5, 9),
+            (5, 10),
+            (5, 11),
+            (6, 8),
+            (6, 9),
+            (6, 10),
+            (6, 11),
+            (8, 9),
+            (8, 10),
+            (8, 11),
+            (9, 10),
+            (9, 11),
+            (10, 11),
+        ];
+        if !(1..=PH_SEL.len()).contains(&(prn as usize)) {
+            return None;
+        }
+        let (p0, p1) = PH_SEL[prn as usize - 1];
+        let mut r1: [u8; 11] = [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0];
+        let mut r2: [u8; 11] = [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0];
+        let mut g = Vec::with_capacity(B1I_CODE_LEN);
+
+        // the 2047-chip gold code is truncated by one chip
+        for _ in 0..B1I_CODE_LEN {
+            let v = r1[10] ^ r2[p0 - 1] ^ r2[p1 - 1];
+            g.push(if v == 1 { -1 } else { 1 });
+            let c1 = r1[0] ^ r1[6] ^ r1[7] ^ r1[8] ^ r1[9] ^ r1[10];
+            let c2 = r2[0] ^ r2[1] ^ r2[2] ^ r2[3] ^ r2[4] ^ r2[7] ^ r2[8] ^ r2[10];
+            r1.rotate_right(1);
+            r2.rotate_right(1);
+            r1[0] = c1;
+            r2[0] = c2;
+        }
+        Some(g)
+    }
+
+    // GLONASS L1OF: the same 511-chip m-sequence (1 + x^5 + x^9) for every
+    // satellite, satellites are separated by their carrier frequency.
+    fn gen_l1of_code() -> Vec<i8> {
+        let mut r = [-1i8; 9];
+        let mut g = Vec::with_capacity(L1OF_CODE_LEN);
+
+        for _ in 0..L1OF_CODE_LEN {
+            g.push(-r[6]);
+            let c = r[4] * r[8];
+            r.rotate_right(1);
+            r[0] = c;
+        }
+        g
+    }
+
+    // Weil code of length 10223 extended to 10230 chips by inserting the
+    // 7-chip sequence 0110100 at (1-based) position p.
+    fn gen_l1c_code(w: usize, p: usize) -> Vec<i8> {
+        const N: usize = 10223;
+        const INSERT: [u8; 7] = [0, 1, 1, 0, 1, 0, 0];
+        let mut legendre = [0u8; N];
+        for x in 1..N {
+            legendre[x * x % N] = 1;
+        }
+        let weil = |t: usize| legendre[t] ^ legendre[(t + w) % N];
+
+        (0..L1C_CODE_LEN)
+            .map(|t| {
+                if t < p - 1 {
+                    weil(t)
+                } else if t < p + 6 {
+                    INSERT[t + 1 - p]
+                } else {
+                    weil(t - 7)
+                }
+            })
+            .map(|v| if v == 1 { -1 } else { 1 })
+            .collect()
+    }
+
+    // returns (w, p) for L1CD or L1CP
+    fn load_l1c_weil_params(prn: u8, pilot: bool) -> Option<(usize, usize)> {
+        let content = match fs::read_to_string(L1C_WEIL_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("failed to read code table {L1C_WEIL_FILE}: {e}");
+                return None;
+            }
+        };
+        let params: Vec<usize> = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| {
+                l.split_whitespace()
+                    .filter_map(|v| v.parse().ok())
+                    .collect()
+            })
+            .find(|v: &Vec<usize>| v.len() == 5 && v[0] == prn as usize)?;
+
+        let (w, p) = if pilot {
+            (params[3], params[4])
+        } else {
+            (params[1], params[2])
+        };
+        if !(1..=L1C_CODE_LEN - 6).contains(&p) {
+            log::warn!("{L1C_WEIL_FILE}: prn {prn}: bad insertion index {p}");
+            return None;
+        }
+        Some((w, p))
+    }
+
+    fn gen_l1cd_code(prn: u8) -> Option<Vec<i8>> {
+        let (w, p) = Self::load_l1c_weil_params(prn, false)?;
+        Some(Self::gen_l1c_code(w, p))
+    }
+
+    fn gen_l1cp_code(prn: u8) -> Option<Vec<i8>> {
+        let (w, p) = Self::load_l1c_weil_params(prn, true)?;
+        Some(Self::gen_l1c_code(w, p))
+    }
+
+    // GPS L5: XA (1 + x^9 + x^10 + x^12 + x^13) short-cycled after 8190 chips,
+    // xor-ed with XB (1 + x + x^3 + x^4 + x^6 + x^7 + x^8 + x^12 + x^13)
+    // advanced by a number of chips specific to the PRN and to I5/Q5.
+    fn gen_l5_code(prn: u8, pilot: bool) -> Option<Vec<i8>> {
+        // XB code advance (IS-GPS-705, table 3-Ia)
+        const XB_ADV_I5: [usize; 37] = [
+            266, 365, 804, 1138, 1509, 1559, 1756, 2084, 2170, 2303, 2527, 2687, 2930, 3471, 3940,
+            4132, 4332, 4924, 5343, 5443, 5641, 5816, 5898, 5918, 5955, 6243, 6345, 6477, 6518,
+            6875, 7168, 7187, 7329, 7577, 7720, 7777, 8057,
+        ];
+        const XB_ADV_Q5: [usize; 37] = [
+            1701, 323, 5292, 2020, 5429, 7136, 1041, 5947, 4315, 148, 535, 1939, 5206, 5910, 3595,
+            5135, 6082, 6990, 3546, 1523, 4548, 4484, 1893, 3961, 7106, 5299, 4660, 276, 4389,
+            3783, 1591, 1601, 749, 1387, 1661, 3210, 708,
+        ];
+        if !(1..=XB_ADV_I5.len()).contains(&(prn as usize)) {
+            return None;
+        }
+        let adv = if pilot {
+            XB_ADV_Q5[prn as usize - 1]
+        } else {
+            XB_ADV_I5[prn as usize - 1]
+        };
+
+        let mut r1 = [1u8; 13];
+        let mut r2 = [1u8; 13];
+        let mut xa = Vec::with_capacity(8190);
+        let mut xb = Vec::with_capacity(8191);
+        for i in 0..8191 {
+            if i < 8190 {
+                xa.push(r1[12]);
+            }
+            xb.push(r2[12]);
+            let c1 = r1[8] ^ r1[9] ^ r1[11] ^ r1[12];
+            let c2 = r2[0] ^ r2[2] ^ r2[3] ^ r2[5] ^ r2[6] ^ r2[7] ^ r2[11] ^ r2[12];
+            r1.rotate_right(1);
+            r2.rotate_right(1);
+            r1[0] = c1;
+            r2[0] = c2;
+        }
+        let g = (0..L5_CODE_LEN)
+            .map(|i| xa[i % 8190] ^ xb[(i + adv) % 8191])
+            .map(|v| if v == 1 { -1 } else { 1 })
+            .collect();
+        Some(g)
+    }
+
+    // 14-stage shift register of the Galileo E5 codes: the octal taps and
+    // start value list stages 1 to 14 from the MSB, stage 14 is the output.
+    fn gen_e5_lfsr(taps: u32, start: u32, len: usize) -> Vec<u8> {
+        let mask = (1..=14)
+            .filter(|j| (taps >> j) & 1 == 1)
+            .fold(0u32, |acc, j| acc | 1 << (14 - j));
+        let mut r = start & 0x3fff;
+        (0..len)
+            .map(|_| {
+                let out = (r & 1) as u8;
+                let fb = (r & mask).count_ones() & 1;
+                r = (r >> 1) | fb << 13;
+                out
+            })
+            .collect()
+    }
+
+    // returns the register 2 start value of E5aI or E5aQ
+    fn load_e5a_start(prn: u8, pilot: bool) -> Option<u32> {
+        let content = match fs::read_to_string(E5A_START_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("failed to read code table {E5A_START_FILE}: {e}");
+                return None;
+            }
+        };
+        let line = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .find(|l| l.split_whitespace().next() == Some(&prn.to_string()))?;
+        let v: Vec<u32> = line
+            .split_whitespace()
+            .skip(1)
+            .map(|v| u32::from_str_radix(v, 8).ok())
+            .collect::<Option<_>>()?;
+        if v.len() != 2 {
+            log::warn!("{E5A_START_FILE}: prn {prn}: invalid line");
+            return None;
+        }
+        Some(if pilot { v[1] } else { v[0] })
+    }
+
+    // Galileo E5a: register 1 starts with all ones, the code is truncated
+    // to 10230 chips
+    fn gen_e5a_code(prn: u8, pilot: bool) -> Option<Vec<i8>> {
+        if !(1..=GAL_PRN_MAX).contains(&prn) {
+            return None;
+        }
+        let start = Self::load_e5a_start(prn, pilot)?;
+        let r1 = Self::gen_e5_lfsr(E5A_TAPS1, 0x3fff, L5_CODE_LEN);
+        let r2 = Self::gen_e5_lfsr(E5A_TAPS2, start, L5_CODE_LEN);
+        let g = r1
+            .iter()
+            .zip(&r2)
+            .map(|(a, b)| if a ^ b == 1 { -1 } else { 1 })
+            .collect();
+        Some(g)
+    }
+
+    // GPS L2C: CM and CL codes come from the same 27-stage modular shift
+    // register (1 + x^3 + x^4 + x^5 + x^6 + x^9 + x^11 + x^13 + x^16 + x^19 +
+    // x^21 + x^24 + x^27) with a different initial state and period. Both
+    // are time-multiplexed at 1.023 Mcps, a CM chip first: the code of the
+    // other component is left as zero chips.
+    fn gen_l2c_code(prn: u8, long: bool) -> Option<Vec<i8>> {
+        // initial register states, octal (IS-GPS-200, table 3-IIa)
+        const L2CM_INIT: [u32; 37] = [
+            0o742417664,
+            0o756014035,
+            0o002747144,
+            0o066265724,
+            0o601403471,
+            0o703232733,
+            0o124510070,
+            0o617316361,
+            0o047541621,
+            0o733031046,
+            0o713512145,
+            0o024437606,
+            0o021264003,
+            0o230655351,
+            0o001314400,
+            0o222021506,
+            0o540264026,
+            0o205521705,
+            0o064022144,
+            0o120161274,
+            0o044023533,
+            0o724744327,
+            0o045743577,
+            0o741201660,
+            0o700274134,
+            0o010247261,
+            0o713433445,
+            0o737324162,
+            0o311627434,
+            0o710452007,
+            0o722462133,
+            0o050172213,
+            0o500653703,
+            0o755077436,
+            0o136717361,
+            0o756675453,
+            0o435506112,
+        ];
+        const L2CL_INIT: [u32; 37] = [
+            0o624145772,
+            0o506610362,
+            0o220360016,
+            0o710406104,
+            0o001143345,
+            0o053023326,
+            0o652521276,
+            0o206124777,
+            0o015563374,
+            0o561522076,
+            0o023163525,
+            0o117776450,
+            0o606516355,
+            0o003037343,
+            0o046515565,
+            0o671511621,
+            0o605402220,
+            0o002576207,
+            0o525163451,
+            0o266527765,
+            0o006760703,
+            0o501474556,
+            0o743747443,
+            0o615534726,
+            0o763621420,
+            0o720727474,
+            0o700521043,
+            0o222567263,
+            0o132765304,
+            0o746332245,
+            0o102300466,
+            0o255231716,
+            0o437661701,
+            0o717047302,
+            0o222614207,
+            0o561123307,
+            0o240713073,
+        ];
+        const TAPS: u32 = 0o445112474;
+        if !(1..=L2CM_INIT.len()).contains(&(prn as usize)) {
+            return None;
+        }
+        let (mut r, len) = if long {
+            (L2CL_INIT[prn as usize - 1], L2CL_CODE_LEN)
+        } else {
+            (L2CM_INIT[prn as usize - 1], L2CM_CODE_LEN)
+        };
+        let mut g = vec![0i8; 2 * len];
+        for i in 0..len {
+            g[2 * i + long as usize] = if r & 1 == 1 { -1 } else { 1 };
+            r = (r >> 1) ^ ((r & 1) * TAPS);
+        }
+        Some(g)
+    }
+
+    fn load_memory_code(file: &str, prn: u8, code_len: usize) -> Option<Vec<i8>> {
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("failed to read code table {file}: {e}");
+                return None;
+            }
+        };
+        let line = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .nth(prn as usize - 1)?;
+
+        let mut code = Vec::with_capacity(code_len);
+        for c in line.chars() {
+            let v = c.to_digit(16)?;
+            for i in (0..4).rev() {
+                code.push(if (v >> i) & 1 == 1 { -1 } else { 1 });
+            }
+        }
+        // hex listings of codes whose length is not a multiple of 4 are padded
+        if code.len() < code_len || code.len() >= code_len + 4 {
+            log::warn!("{file}: prn {prn}: bad code length {}", code.len());
+            return None;
+        }
+        code.truncate(code_len);
+        Some(code)
+    }
+
+    fn gen_e1b_code(prn: u8) -> Option<Vec<i8>> {
+        if !(1..=GAL_PRN_MAX).contains(&prn) {
+            return None;
+        }
+        Self::load_memory_code(E1B_CODE_FILE, prn, E1_CODE_LEN)
+    }
+
+    fn gen_e1c_code(prn: u8) -> Option<Vec<i8>> {
+        if !(1..=GAL_PRN_MAX).contains(&prn) {
+            return None;
+        }
+        Self::load_memory_code(E1C_CODE_FILE, prn, E1_CODE_LEN)
+    }
+
     pub fn gen_code(sig: &str, prn: u8) -> Option<Vec<i8>> {
         match sig {
-            "L1CA" => Some(Self::gen_l1ca_code(prn)),
+            "L1CA" if (1..=210).contains(&prn) => Some(Self::gen_l1ca_code(prn)),
+            "E1B" => Self::gen_e1b_code(prn),
+            "E1C" => Self::gen_e1c_code(prn),
+            "B1I" => Self::gen_b1i_code(prn),
+            "L1OF" => Some(Self::gen_l1of_code()),
+            // L1C is tracked on its pilot component
+            "L1C" | "L1CP" if (1..=63).contains(&prn) => Self::gen_l1cp_code(prn),
+            "L1CD" if (1..=63).contains(&prn) => Self::gen_l1cd_code(prn),
+            "L2CM" => Self::gen_l2c_code(prn, false),
+            // the CL code spans 75 periods of the CM code
+            "L2CL" => Self::gen_l2c_code(prn, true),
+            "L5I" => Self::gen_l5_code(prn, false),
+            "L5Q" => Self::gen_l5_code(prn, true),
+            "E5aI" => Self::gen_e5a_code(prn, false),
+            "E5aQ" => Self::gen_e5a_code(prn, true),
+            _ => None,
+        }
+    }
+
+    // Data component of a signal whose primary code is a pilot, if any.
+    pub fn get_data_signal(sig: &str) -> Option<&'static str> {
+        match sig {
+            "L1C" => Some("L1CD"),
+            _ => None,
+        }
+    }
+
+    // PRN of the ranging code used by a satellite
+    pub fn get_code_prn(sv: SV) -> u8 {
+        match sv.constellation {
+            Constellation::QZSS => sv.prn + QZSS_PRN_OFFSET,
+            _ => sv.prn,
+        }
+    }
+
+    // Secondary code modulating successive primary code periods, if any.
+    pub fn get_secondary_code(sig: &str, prn: u8) -> Option<Vec<i8>> {
+        match sig {
+            // GEO satellites broadcast D2 at 500 bps without NH code
+            "B1I" if prn > 5 && prn < 59 => Some(NH20_CODE.to_vec()),
+            "L1C" | "L1CP" if (1..=63).contains(&prn) => {
+                Self::load_memory_code(L1CO_CODE_FILE, prn, L1CO_CODE_LEN)
+            }
+            "L5I" => Some(NH10_CODE.to_vec()),
+            "L5Q" => Some(NH20_CODE.to_vec()),
+            "E5aI" => Some(E5AI_SEC_CODE.to_vec()),
+            "E5aQ" if (1..=GAL_PRN_MAX).contains(&prn) => {
+                Self::load_memory_code(E5AQ_SEC_CODE_FILE, prn, E5AQ_SEC_CODE_LEN)
+            }
             _ => None,
         }
     }
 
     pub fn get_code_period(sig: &str) -> f64 {
         match sig {
-            "L1CA" => 1e-3,
+            "L1CA" | "B1I" | "L1OF" | "L5I" | "L5Q" | "E5aI" | "E5aQ" => 1e-3,
+            "E1B" | "E1C" => 4e-3,
+            "L1C" | "L1CD" | "L1CP" => 10e-3,
+            "L2CM" | "L2CL" => 20e-3,
             _ => 0.0,
         }
     }
@@ -62,17 +580,52 @@ impl Code {
     pub fn get_code_len(sig: &str) -> usize {
         match sig {
             "L1CA" => L1CA_CODE_LEN,
+            "E1B" | "E1C" => E1_CODE_LEN,
+            "B1I" => B1I_CODE_LEN,
+            "L1OF" => L1OF_CODE_LEN,
+            "L1C" | "L1CD" | "L1CP" => L1C_CODE_LEN,
+            "L5I" | "L5Q" | "E5aI" | "E5aQ" => L5_CODE_LEN,
+            // chips per code period, CM and CL chips time-multiplexed
+            "L2CM" | "L2CL" => 2 * L2CM_CODE_LEN,
             _ => 0,
         }
     }
 
     pub fn get_code_freq(sig: &str) -> f64 {
         match sig {
-            "L1CA" => 1575.42e6,
+            "L1CA" | "E1B" | "E1C" | "L1C" | "L1CD" | "L1CP" => 1575.42e6,
+            "B1I" => 1561.098e6,
+            "L1OF" => 1602.0e6, // frequency channel k=0
+            "L2CM" | "L2CL" => 1227.60e6,
+            "L5I" | "L5Q" | "E5aI" | "E5aQ" => 1176.45e6,
             _ => 0.0,
         }
     }
 
+    // Value of the subcarrier at a given chip and chip phase (0..1), for a
+    // replica sampled at `sp_chip` samples per chip. E1B/E1C use CBOC(6,1,1/11)
+    // and L1CP uses TMBOC(6,1,4/33): the BOC(6,1) component can only be
+    // represented with at least 12 samples per chip, below that we fall back
+    // to plain BOC(1,1).
+    pub fn get_subcarrier(sig: &str, chip: usize, chip_phase: f64, sp_chip: f64) -> f64 {
+        let boc = |m: f64| {
+            if (2.0 * m * chip_phase).floor() as i64 % 2 == 0 {
+                1.0
+            } else {
+                -1.0
+            }
+        };
+        match sig {
+            "E1B" | "E1C" | "L1C" | "L1CD" | "L1CP" if sp_chip < 12.0 => boc(1.0),
+            // BOC(6,1) on chips 0, 4, 6 and 29 of every 33
+            "L1C" | "L1CP" if matches!(chip % 33, 0 | 4 | 6 | 29) => boc(6.0),
+            "L1C" | "L1CD" | "L1CP" => boc(1.0),
+            "E1B" => (10.0f64 / 11.0).sqrt() * boc(1.0) + (1.0f64 / 11.0).sqrt() * boc(6.0),
+            "E1C" => (10.0f64 / 11.0).sqrt() * boc(1.0) - (1.0f64 / 11.0).sqrt() * boc(6.0),
+            _ => 1.0,
+        }
+    }
+
     pub fn print_l1ca_codes() {
         println!("generating gold codes for L1CA");
         for i in 1..=32 {
@@ -81,3 +634,211 @@ impl Code {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chips as an octal number, chip value -1 for a 1
+    fn to_octal(chips: &[i8]) -> u64 {
+        chips.iter().fold(0, |acc, &c| acc << 1 | (c == -1) as u64)
+    }
+
+    #[test]
+    fn l1c_weil_code() {
+        // PRN 1 L1CP: Weil index 5111, insertion index 412, first 24 chips
+        // 05752067 (IS-GPS-800, table 3.2-2)
+        let code = Code::gen_l1c_code(5111, 412);
+        assert_eq!(code.len(), L1C_CODE_LEN);
+        assert_eq!(to_octal(&code[..24]), 0o05752067);
+        assert_eq!(to_octal(&code[411..418]), 0b0110100);
+    }
+
+    #[test]
+    fn l5_code() {
+        // XA starts with all ones: the first 13 chips are the complement of
+        // the XB initial state, stage 13 first (IS-GPS-705, table 3-Ia)
+        for (sig, prn, xb) in [
+            ("L5I", 1, 0b0101011100100),
+            ("L5Q", 1, 0b1001011001100),
+            ("L5I", 2, 0b1100000110101),
+            ("L5Q", 2, 0b0100011110110),
+        ] {
+            let code = Code::gen_code(sig, prn).unwrap();
+            assert_eq!(code.len(), L5_CODE_LEN);
+            let first: u64 = (0..13).fold(0, |acc, i| acc << 1 | (xb >> i & 1 ^ 1));
+            assert_eq!(to_octal(&code[..13]), first, "{sig} prn {prn}");
+        }
+    }
+
+    #[test]
+    fn l2c_code() {
+        // IS-GPS-200 table 3-IIa: initial and end states of PRN 1, the
+        // register follows 1 + x^3 + ... + x^27
+        let taps = [3, 4, 5, 6, 9, 11, 13, 16, 19, 21, 24, 27]
+            .iter()
+            .fold(0u32, |acc, k| acc | 1 << (k - 1));
+        for (sig, init, end, len, off) in [
+            ("L2CM", 0o742417664u32, 0o552566002u32, L2CM_CODE_LEN, 0),
+            ("L2CL", 0o624145772, 0o267724236, L2CL_CODE_LEN, 1),
+        ] {
+            let code = Code::gen_code(sig, 1).unwrap();
+            assert_eq!(code.len(), 2 * len);
+            let mut r = init;
+            for i in 0..len {
+                let chip = if r & 1 == 1 { -1 } else { 1 };
+                assert_eq!(code[2 * i + off], chip, "{sig} chip {i}");
+                assert_eq!(code[2 * i + 1 - off], 0, "{sig} chip {i}");
+                if i == len - 1 {
+                    assert_eq!(r, end, "{sig}");
+                }
+                r = (r >> 1) ^ ((r & 1) * taps);
+            }
+        }
+    }
+
+    #[test]
+    fn l1ca_code() {
+        // G1 = 1 + x^3 + x^10 and G2 = 1 + x^2 + x^3 + x^6 + x^8 + x^9 + x^10
+        // from all ones, G2 delayed per PRN, a 1 is +1. GPS PRN 1 starts with
+        // 1440 octal (IS-GPS-200, table 3-Ia). QZSS PRN 193 and 202 are
+        // delayed by 339 and 900 chips and start with 1050 and 1045 octal,
+        // listed with the opposite chip logic (IS-QZSS-PNT, table 3.2.2-2).
+        let seq = |exps: &[usize]| {
+            let mut a = vec![1u8; 10];
+            for t in 0..L1CA_CODE_LEN - 10 {
+                let v = exps.iter().fold(0, |acc, e| acc ^ a[t + 10 - e]);
+                a.push(v);
+            }
+            a
+        };
+        let g1 = seq(&[3, 10]);
+        let g2 = seq(&[2, 3, 6, 8, 9, 10]);
+        let reference = |delay: usize| -> Vec<i8> {
+            (0..L1CA_CODE_LEN)
+                .map(|t| g1[t] ^ g2[(t + L1CA_CODE_LEN - delay) % L1CA_CODE_LEN])
+                .map(|v| if v == 1 { 1 } else { -1 })
+                .collect()
+        };
+        let code = Code::gen_code("L1CA", 1).unwrap();
+        assert_eq!(code, reference(5));
+        assert_eq!(to_octal(&code[..10]) ^ 0o1777, 0o1440);
+        for (prn, delay, first) in [(1, 339, 0o1050), (10, 900, 0o1045)] {
+            let sv = SV::new(Constellation::QZSS, prn);
+            let code = Code::gen_code("L1CA", Code::get_code_prn(sv)).unwrap();
+            assert_eq!(code, reference(delay), "qzss prn {prn}");
+            assert_eq!(to_octal(&code[..10]), first, "qzss prn {prn}");
+        }
+    }
+
+    #[test]
+    fn b1i_code() {
+        // G1 = 1 + x + x^7 + ... + x^11 and G2 = 1 + x + ... + x^9 + x^11 as
+        // sequence recurrences, both registers start with 01010101010
+        // (BDS-SIS-ICD-B1I, figure 5-2)
+        let seq = |exps: &[usize]| {
+            let mut a: Vec<u8> = (0..11).map(|j| (10 - j) as u8 % 2).collect();
+            for t in 0..B1I_CODE_LEN {
+                let v = exps.iter().fold(0, |acc, e| acc ^ a[t + 11 - e]);
+                a.push(v);
+            }
+            a
+        };
+        let g1 = seq(&[1, 7, 8, 9, 10, 11]);
+        let g2 = seq(&[1, 2, 3, 4, 5, 8, 9, 11]);
+        for (prn, p0, p1) in [(1u8, 1, 3), (9, 2, 7), (37, 10, 11)] {
+            let code = Code::gen_code("B1I", prn).unwrap();
+            assert_eq!(code.len(), B1I_CODE_LEN);
+            for (t, &c) in code.iter().enumerate() {
+                let v = g1[t] ^ g2[t + 11 - p0] ^ g2[t + 11 - p1];
+                assert_eq!(c, if v == 1 { -1 } else { 1 }, "prn {prn} chip {t}");
+            }
+        }
+        assert!(Code::gen_code("B1I", 38).is_none());
+    }
+
+    #[test]
+    fn nh_codes() {
+        // NH20 = 00000100110101001110, NH10 = 0000110101, a 0 is +1
+        let nh20: Vec<i8> = "00000100110101001110"
+            .bytes()
+            .map(|b| if b == b'1' { -1 } else { 1 })
+            .collect();
+        let nh10: Vec<i8> = "0000110101"
+            .bytes()
+            .map(|b| if b == b'1' { -1 } else { 1 })
+            .collect();
+        assert_eq!(NH20_CODE.to_vec(), nh20);
+        assert_eq!(NH10_CODE.to_vec(), nh10);
+        for prn in [6, 30, 58] {
+            assert_eq!(Code::get_secondary_code("B1I", prn), Some(nh20.clone()));
+        }
+        for prn in [1, 5, 59] {
+            assert_eq!(Code::get_secondary_code("B1I", prn), None);
+        }
+        assert_eq!(Code::get_secondary_code("L5Q", 1), Some(nh20));
+        assert_eq!(Code::get_secondary_code("L5I", 1), Some(nh10));
+    }
+
+    #[test]
+    fn l1of_code() {
+        // 1 + x^5 + x^9 from all ones, output of stage 7: the code starts
+        // with 111111100, a 1 is +1 (GLONASS ICD, figure 3.1)
+        let code = Code::gen_code("L1OF", 1).unwrap();
+        assert_eq!(code.len(), L1OF_CODE_LEN);
+        assert_eq!(code[..9], [1, 1, 1, 1, 1, 1, 1, -1, -1]);
+        let mut a = vec![1u8; 9];
+        for t in 0..L1OF_CODE_LEN {
+            a.push(a[t + 4] ^ a[t]);
+        }
+        for (t, &c) in code.iter().enumerate() {
+            assert_eq!(c, if a[t + 2] == 1 { 1 } else { -1 }, "chip {t}");
+        }
+        // m-sequence: 256 ones and a two-valued periodic autocorrelation
+        assert_eq!(code.iter().filter(|&&c| c == 1).count(), 256);
+        for shift in 0..L1OF_CODE_LEN {
+            let corr: i32 = (0..L1OF_CODE_LEN)
+                .map(|i| (code[i] * code[(i + shift) % L1OF_CODE_LEN]) as i32)
+                .sum();
+            assert_eq!(corr, if shift == 0 { 511 } else { -1 });
+        }
+    }
+
+    #[test]
+    fn e5a_registers() {
+        // both base registers are maximal length: period 2^14 - 1, 2^13 ones
+        for taps in [E5A_TAPS1, E5A_TAPS2] {
+            let seq = Code::gen_e5_lfsr(taps, 0x3fff, 2 * 16383);
+            assert_eq!(seq.iter().filter(|&&b| b == 1).count(), 2 * 8192);
+            assert_eq!(seq[..16383], seq[16383..]);
+            for period in [3 * 43, 3 * 127, 43 * 127] {
+                assert_ne!(seq[..period], seq[period..2 * period]);
+            }
+        }
+        // the output is stage 14, the start value lists stage 1 first
+        let seq = Code::gen_e5_lfsr(E5A_TAPS2, 0o30305, 14);
+        let start: u64 = (0..14).fold(0, |acc, i| acc << 1 | seq[13 - i] as u64);
+        assert_eq!(start, 0o30305);
+    }
+
+    #[test]
+    fn memory_code_hex() {
+        let path = std::env::temp_dir().join(format!("gnss-rcv-codes-{}.txt", std::process::id()));
+        fs::write(&path, "# comment\n\nF0\n  A5 \n").unwrap();
+        let file = path.to_str().unwrap();
+
+        assert_eq!(
+            Code::load_memory_code(file, 1, 8).unwrap(),
+            [-1, -1, -1, -1, 1, 1, 1, 1]
+        );
+        // up to 3 padding bits
+        assert_eq!(
+            Code::load_memory_code(file, 2, 6).unwrap(),
+            [-1, 1, -1, 1, 1, -1]
+        );
+        assert!(Code::load_memory_code(file, 2, 4).is_none());
+        assert!(Code::load_memory_code(file, 2, 9).is_none());
+        assert!(Code::load_memory_code(file, 3, 8).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
 pub const P2_5: f64 = 0.03125; /* 2^-5 */
+pub const P2_8: f64 = 0.003_906_25; /* 2^-8 */
+pub const P2_9: f64 = 0.001_953_125; /* 2^-9 */
 pub const P2_11: f64 = 4.882_812_5e-4; /* 2^-11 */
 pub const P2_19: f64 = 1.907_348_632_812_5e-6; /* 2^-19 */
 pub const P2_20: f64 = 9.536_743_164_062_5e-7; /* 2^-20 */
@@ -9,15 +11,29 @@ pub const P2_27: f64 = 7.450_580_596_923_828e-9; /* 2^-27 */
 pub const P2_29: f64 = 1.862_645_149_230_957e-9; /* 2^-29 */
 pub const P2_30: f64 = 9.313_225_746_154_785e-10; /* 2^-30 */
 pub const P2_31: f64 = 4.656_612_873_077_393e-10; /* 2^-31 */
+pub const P2_32: f64 = 2.328_306_436_538_696e-10; /* 2^-32 */
 pub const P2_33: f64 = 1.164_153_218_269_348e-10; /* 2^-33 */
+pub const P2_34: f64 = 5.820_766_091_346_741e-11; /* 2^-34 */
+pub const P2_35: f64 = 2.910_383_045_673_370_4e-11; /* 2^-35 */
 pub const P2_38: f64 = 3.637_978_807_091_71e-12; /* 2^-38 */
+pub const P2_39: f64 = 1.818_989_403_545_856e-12; /* 2^-39 */
 pub const P2_43: f64 = 1.136_868_377_216_16e-13; /* 2^-43 */
+pub const P2_44: f64 = 5.684_341_886_080_802e-14; /* 2^-44 */
+pub const P2_48: f64 = 3.552_713_678_800_501e-15; /* 2^-48 */
 pub const P2_50: f64 = 8.881_784_197_001_252e-16; /* 2^-50 */
+pub const P2_51: f64 = 4.440_892_098_500_626e-16; /* 2^-51 */
 pub const P2_55: f64 = 2.775_557_561_562_891e-17; /* 2^-55 */
+pub const P2_57: f64 = 6.938_893_903_907_228e-18; /* 2^-57 */
+pub const P2_60: f64 = 8.673_617_379_884_035e-19; /* 2^-60 */
 
 #[allow(clippy::approx_constant)]
 pub const SC2RAD: f64 = 3.141_592_653_589_8; /* semi-circle to radian (IS-GPS) */
 
 pub const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+pub const SECS_PER_WEEK: u32 = 7 * 24 * 60 * 60;
 pub const EARTH_MU_GPS: f64 = 3.9860058e14; // earth gravitational constant
 pub const EARTH_ROTATION_RATE: f64 = 7.2921151467e-5;
+
+// CNAV/CNAV-2 reference values (IS-GPS-200, 30.3.3.1.3)
+pub const CNAV_A_REF: f64 = 26_559_710.0;
+pub const CNAV_OMG_DOT_REF: f64 = -2.6e-9; // semi-circles/s
@@ -1,139 +1,146 @@
 use rustfft::num_complex::Complex64;
-use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::thread;
+use std::sync::atomic::AtomicBool;
 
-use crate::code::Code;
+use crate::queue::{QUEUE_SEC, QueueReader, STALL_TIMEOUT, SampleQueue};
 use crate::receiver::IQReader;
+use crate::recording::IQFileType;
 
-pub struct RtlSdrDevice {
-    controller: rtlsdr_mt::Controller,
-    iq_deque: Arc<Mutex<VecDeque<Vec<Complex64>>>>,
-    num_samples_total: Arc<Mutex<usize>>,
-    num_samples: Arc<Mutex<usize>>,
-    num_sleep: u64,
+pub mod mock;
+#[cfg(feature = "rtlsdr")]
+pub mod rtlsdr;
+
+// A front end found on the host, as listed by --list-devices
+pub struct DeviceInfo {
+    pub driver: &'static str,
+    pub index: usize,
+    pub label: String,
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} -- {}", self.driver, self.index, self.label)
+    }
+}
+
+// An SDR front end. Each backend lives in device/ and, when it depends on a
+// vendor library, behind a cargo feature of the same name (e.g. "rtlsdr").
+// Once the stream is started, the device pushes complex baseband samples to
+// the queue until the stream is stopped.
+pub trait SdrDevice: Send {
+    fn enumerate() -> Vec<DeviceInfo>
+    where
+        Self: Sized;
+    fn get_name(&self) -> String;
+    fn set_frequency(&mut self, fc: f64) -> Result<(), Box<dyn Error>>;
+    fn set_sample_rate(&mut self, fs: f64) -> Result<(), Box<dyn Error>>;
+    // None: the default gain of the device
+    fn set_gain(&mut self, gain_db: Option<f64>) -> Result<(), Box<dyn Error>>;
+    fn get_gain_db(&self) -> Option<f64>;
+    fn set_bias_tee(&mut self, on: bool) -> Result<(), Box<dyn Error>>;
+    fn start_stream(&mut self, queue: Arc<SampleQueue>) -> Result<(), Box<dyn Error>>;
+    fn stop_stream(&mut self);
+}
+
+pub fn enumerate_devices() -> Vec<DeviceInfo> {
+    #[allow(unused_mut)]
+    let mut devices = mock::MockDevice::enumerate();
+    #[cfg(feature = "rtlsdr")]
+    devices.extend(rtlsdr::RtlSdrDevice::enumerate());
+    devices
 }
 
-impl Drop for RtlSdrDevice {
+// "driver[:arg]", e.g. "rtlsdr", "rtlsdr:1" or "mock:path/to/recording.bin"
+fn open_device(spec: &str, file_type: &IQFileType) -> Result<Box<dyn SdrDevice>, Box<dyn Error>> {
+    let (driver, arg) = spec.split_once(':').unwrap_or((spec, ""));
+    match driver {
+        "mock" => Ok(Box::new(mock::MockDevice::open(arg, file_type)?)),
+        #[cfg(feature = "rtlsdr")]
+        "rtlsdr" => {
+            let index = if arg.is_empty() { 0 } else { arg.parse()? };
+            Ok(Box::new(rtlsdr::RtlSdrDevice::open(index)?))
+        }
+        _ => Err(format!("unknown or disabled device driver: {driver}").into()),
+    }
+}
+
+// The samples of an SdrDevice, read by the receiver
+pub struct SdrSource {
+    dev: Box<dyn SdrDevice>,
+    reader: QueueReader,
+}
+
+impl Drop for SdrSource {
     fn drop(&mut self) {
         log::warn!(
-            "rtlsdr: stopping read. num_samples={}",
-            self.num_samples.lock().unwrap()
+            "{}: stopping stream. {}",
+            self.dev.get_name(),
+            self.reader.get_stats()
+        );
+        log::warn!(
+            "{}: num_wait={}",
+            self.dev.get_name(),
+            self.reader.get_num_wait()
         );
-        log::warn!("rtlsdr: num_sleep={}", self.num_sleep);
 
-        self.controller.cancel_async_read();
+        self.dev.stop_stream();
     }
 }
 
-impl IQReader for RtlSdrDevice {
+impl IQReader for SdrSource {
     fn get_iq_data(
         &mut self,
-        _off_samples: usize,
+        off_samples: usize,
         num_samples: usize,
     ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
-        loop {
-            if *self.num_samples.lock().unwrap() >= num_samples {
-                break;
-            }
-            thread::sleep(std::time::Duration::from_millis(1));
-            self.num_sleep += 1;
-        }
-        let mut vec = vec![];
-        let mut iq_deq = self.iq_deque.lock().unwrap();
-
-        let v_front = iq_deq.front_mut().unwrap();
-        let n = usize::min(num_samples, v_front.len());
-        for v in v_front.iter().take(n) {
-            vec.push(*v);
-        }
-        let _ = v_front.drain(0..n);
-        if v_front.is_empty() {
-            let _ = iq_deq.pop_front();
-        }
-
-        if n < num_samples {
-            let v_front = iq_deq.front_mut().unwrap();
-            for i in n..num_samples {
-                vec.push(v_front[i - n]);
-            }
-            let _ = v_front.drain(0..num_samples - n);
-        }
+        self.reader.get_iq_data(off_samples, num_samples)
+    }
 
-        *self.num_samples.lock().unwrap() -= num_samples;
+    fn get_gain_db(&self) -> Option<f64> {
+        self.dev.get_gain_db()
+    }
 
-        Ok(vec)
+    fn take_lost_samples(&mut self) -> usize {
+        self.reader.take_lost_samples()
     }
 }
 
-impl RtlSdrDevice {
-    #[allow(clippy::result_unit_err)]
-    pub fn new(sig: &str, fs: f64) -> Result<RtlSdrDevice, ()> {
-        let devices = rtlsdr_mt::devices();
-
-        for dev in devices {
-            log::warn!("found rtl-sdr: {:?}", dev);
+impl SdrSource {
+    pub fn new(
+        spec: &str,
+        file_type: &IQFileType,
+        fc: f64,
+        fs: f64,
+        gain_db: Option<f64>,
+        exit_req: Arc<AtomicBool>,
+    ) -> Result<SdrSource, Box<dyn Error>> {
+        for dev in enumerate_devices() {
+            log::warn!("found device: {dev}");
         }
+        let mut dev = open_device(spec, file_type)?;
+
+        // powers the antenna LNA
+        dev.set_bias_tee(true)?;
+        dev.set_frequency(fc)?;
+        dev.set_sample_rate(fs)?;
+        dev.set_gain(gain_db)?;
+        log::warn!(
+            "{}: fc={:.3} MHz fs={:.1} KHz gain={}",
+            dev.get_name(),
+            fc / 1_000_000.0,
+            fs / 1000.0,
+            dev.get_gain_db()
+                .map_or("auto".to_string(), |g| format!("{g:.1} dB")),
+        );
+
+        let queue = Arc::new(SampleQueue::new((QUEUE_SEC * fs) as usize));
+        dev.start_stream(queue.clone())?;
 
-        let (ctl, mut reader) = rtlsdr_mt::open(0)?;
-        let mut m = Self {
-            controller: ctl,
-            iq_deque: Arc::new(Mutex::new(VecDeque::new())),
-            num_samples_total: Arc::new(Mutex::new(0)),
-            num_samples: Arc::new(Mutex::new(0)),
-            num_sleep: 0,
-        };
-
-        let mut tunes = rtlsdr_mt::TunerGains::default();
-        let gains = m.controller.tuner_gains(&mut tunes);
-        log::warn!("gain: {:?}", gains);
-        let g_max = gains.iter().max().unwrap();
-
-        log::warn!("Using gain: {g_max}");
-
-        //m.controller.enable_agc().expect("Failed to enable agc");
-        m.controller
-            .set_tuner_gain(*g_max)
-            .expect("Failed to enable agc");
-        m.controller
-            .set_bias_tee(1)
-            .expect("Failed to set bias tee");
-        m.controller
-            .set_center_freq(Code::get_code_freq(sig) as u32)
-            .expect("Failed to change center freq");
-        m.controller
-            .set_sample_rate(fs as u32)
-            .expect("Failed to change sample rate");
-        m.controller.reset_buffer().expect("Failed to reset buffer");
-        let ppm = m.controller.ppm();
-
-        log::warn!("ppm={ppm}");
-
-        let iq_deq = m.iq_deque.clone();
-        let num_samples_total = m.num_samples_total.clone();
-        let num_samples = m.num_samples.clone();
-        thread::spawn(move || {
-            loop {
-                log::warn!("starting async_read");
-                reader
-                    .read_async(0, 0, |array| {
-                        let mut v = vec![Complex64::default(); array.len()];
-                        for i in 0..array.len() / 2 {
-                            let re = (array[2 * i] as f64 - 127.3) / 128.0;
-                            let im = (array[2 * i + 1] as f64 - 127.3) / 128.0;
-                            v[i] = Complex64 { re, im };
-                        }
-
-                        let n = v.len();
-                        iq_deq.lock().unwrap().push_back(v);
-                        *num_samples.lock().unwrap() += n;
-                        *num_samples_total.lock().unwrap() += n;
-                    })
-                    .unwrap();
-            }
-        });
-
-        Ok(m)
+        Ok(SdrSource {
+            dev,
+            reader: QueueReader::new(queue, exit_req, Some(STALL_TIMEOUT)),
+        })
     }
 }
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::device::{DeviceInfo, SdrDevice};
+use crate::queue::SampleQueue;
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording};
+
+const CHUNK_SEC: f64 = 0.01;
+
+// A device replaying a recording at the pace of its sampling rate, e.g.
+// "--device mock:resources/rec.bin": it exercises the live code paths (queue,
+// overflows, recorder) without hardware. The stream closes at the end of the
+// recording.
+pub struct MockDevice {
+    path: PathBuf,
+    file_type: IQFileType,
+    fs: f64,
+    gain_db: Option<f64>,
+    stop: Arc<AtomicBool>,
+    th: Option<JoinHandle<()>>,
+}
+
+impl MockDevice {
+    pub fn open(path: &str, file_type: &IQFileType) -> Result<MockDevice, Box<dyn Error>> {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(format!("mock: no recording at '{}'", path.display()).into());
+        }
+        // the receiver only down-converts real samples of files
+        if file_type.is_real() {
+            return Err(format!("mock: {file_type} samples are not supported").into());
+        }
+        Ok(MockDevice {
+            path,
+            file_type: file_type.clone(),
+            fs: 0.0,
+            gain_db: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            th: None,
+        })
+    }
+}
+
+impl SdrDevice for MockDevice {
+    // opened by path only: nothing to find
+    fn enumerate() -> Vec<DeviceInfo> {
+        vec![]
+    }
+
+    fn get_name(&self) -> String {
+        format!("mock:{}", self.path.display())
+    }
+
+    // the recording is what it is: nothing to tune
+    fn set_frequency(&mut self, _fc: f64) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn set_sample_rate(&mut self, fs: f64) -> Result<(), Box<dyn Error>> {
+        self.fs = fs;
+        Ok(())
+    }
+
+    fn set_gain(&mut self, gain_db: Option<f64>) -> Result<(), Box<dyn Error>> {
+        self.gain_db = gain_db;
+        Ok(())
+    }
+
+    fn get_gain_db(&self) -> Option<f64> {
+        self.gain_db
+    }
+
+    fn set_bias_tee(&mut self, _on: bool) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn start_stream(&mut self, queue: Arc<SampleQueue>) -> Result<(), Box<dyn Error>> {
+        if self.th.is_some() {
+            return Err("mock: stream already started".into());
+        }
+        let mut rec = IQRecording::new(&self.path, self.fs, &self.file_type)?;
+        let (fs, stop) = (self.fs, self.stop.clone());
+        let chunk = usize::max(1, (CHUNK_SEC * fs) as usize);
+
+        self.th = Some(thread::spawn(move || {
+            let ts = Instant::now();
+            let mut off_samples = 0;
+            while !stop.load(Ordering::SeqCst) {
+                let Ok(v) = rec.get_iq_data(off_samples, chunk) else {
+                    log::warn!("mock: end of recording");
+                    break;
+                };
+                queue.push(&v);
+                off_samples += chunk;
+                // sleeps until the samples pushed so far are due
+                let due = Duration::from_secs_f64(off_samples as f64 / fs);
+                thread::sleep(due.saturating_sub(ts.elapsed()));
+            }
+            queue.close();
+        }));
+        Ok(())
+    }
+
+    fn stop_stream(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(th) = self.th.take() {
+            th.join().unwrap();
+        }
+    }
+}
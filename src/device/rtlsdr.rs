@@ -0,0 +1,122 @@
+use rustfft::num_complex::Complex64;
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+
+use crate::device::{DeviceInfo, SdrDevice};
+use crate::queue::SampleQueue;
+
+pub struct RtlSdrDevice {
+    index: usize,
+    controller: rtlsdr_mt::Controller,
+    reader: Option<rtlsdr_mt::Reader>, // handed to the read thread on start
+    gain_db: f64,
+}
+
+impl RtlSdrDevice {
+    pub fn open(index: usize) -> Result<RtlSdrDevice, Box<dyn Error>> {
+        let (controller, reader) =
+            rtlsdr_mt::open(index as u32).map_err(|_| format!("rtlsdr:{index}: failed to open"))?;
+        Ok(RtlSdrDevice {
+            index,
+            controller,
+            reader: Some(reader),
+            gain_db: 0.0,
+        })
+    }
+}
+
+impl SdrDevice for RtlSdrDevice {
+    fn enumerate() -> Vec<DeviceInfo> {
+        rtlsdr_mt::devices()
+            .enumerate()
+            .map(|(index, name)| DeviceInfo {
+                driver: "rtlsdr",
+                index,
+                label: name.to_string_lossy().to_string(),
+            })
+            .collect()
+    }
+
+    fn get_name(&self) -> String {
+        format!("rtlsdr:{}", self.index)
+    }
+
+    fn set_frequency(&mut self, fc: f64) -> Result<(), Box<dyn Error>> {
+        self.controller
+            .set_center_freq(fc as u32)
+            .map_err(|_| "rtlsdr: failed to change center freq")?;
+        Ok(())
+    }
+
+    fn set_sample_rate(&mut self, fs: f64) -> Result<(), Box<dyn Error>> {
+        self.controller
+            .set_sample_rate(fs as u32)
+            .map_err(|_| "rtlsdr: failed to change sample rate")?;
+        Ok(())
+    }
+
+    // the closest gain the tuner supports, its highest one by default
+    fn set_gain(&mut self, gain_db: Option<f64>) -> Result<(), Box<dyn Error>> {
+        let mut tunes = rtlsdr_mt::TunerGains::default();
+        let gains = self.controller.tuner_gains(&mut tunes);
+        log::warn!("gain: {:?}", gains);
+        // in tenths of dB
+        let gain = match gain_db {
+            Some(gain_db) => gains
+                .iter()
+                .min_by_key(|g| (**g - (gain_db * 10.0).round() as i32).abs()),
+            None => gains.iter().max(),
+        };
+        let gain = *gain.ok_or("rtlsdr: no tuner gains")?;
+
+        log::warn!("Using gain: {gain}");
+        self.gain_db = gain as f64 / 10.0;
+        self.controller
+            .set_tuner_gain(gain)
+            .map_err(|_| "rtlsdr: failed to set tuner gain")?;
+        Ok(())
+    }
+
+    fn get_gain_db(&self) -> Option<f64> {
+        Some(self.gain_db)
+    }
+
+    fn set_bias_tee(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+        self.controller
+            .set_bias_tee(on as i32)
+            .map_err(|_| "rtlsdr: failed to set bias tee")?;
+        Ok(())
+    }
+
+    fn start_stream(&mut self, queue: Arc<SampleQueue>) -> Result<(), Box<dyn Error>> {
+        let mut reader = self.reader.take().ok_or("rtlsdr: stream already started")?;
+        self.controller
+            .reset_buffer()
+            .map_err(|_| "rtlsdr: failed to reset buffer")?;
+        log::warn!("ppm={}", self.controller.ppm());
+
+        thread::spawn(move || {
+            log::warn!("starting async_read");
+            let res = reader.read_async(0, 0, |array| {
+                let mut v = vec![Complex64::default(); array.len() / 2];
+                for i in 0..array.len() / 2 {
+                    let re = (array[2 * i] as f64 - 127.3) / 128.0;
+                    let im = (array[2 * i + 1] as f64 - 127.3) / 128.0;
+                    v[i] = Complex64 { re, im };
+                }
+                queue.push(&v);
+            });
+            if res.is_err() {
+                log::warn!("rtlsdr: async_read failed");
+            }
+            // wakes up the receiver instead of leaving it waiting for samples
+            queue.close();
+        });
+        Ok(())
+    }
+
+    fn stop_stream(&mut self) {
+        self.controller.cancel_async_read();
+    }
+}
@@ -3,10 +3,30 @@ use gnss_rs::sv::SV;
 use gnss_rtk::prelude::Epoch;
 
 use crate::{
-    constants::{P2_5, P2_19, P2_29, P2_31, P2_33, P2_43, P2_55, SC2RAD},
-    util::{getbits, getbits2, getbitu, getbitu2},
+    constants::{
+        CNAV_A_REF, CNAV_OMG_DOT_REF, EARTH_MU_GPS, EARTH_ROTATION_RATE, P2_5, P2_8, P2_9, P2_19,
+        P2_21, P2_29, P2_30, P2_31, P2_32, P2_33, P2_34, P2_35, P2_43, P2_44, P2_48, P2_55, P2_57,
+        P2_60, SC2RAD,
+    },
+    util::{getbits, getbits2, getbitu, getbitu2, setbitu, setbitu2},
 };
 
+const REL_CLOCK_F: f64 = -4.442807633e-10; // relativistic correction, s/m^1/2
+
+// value in units of lsb, as the two's complement bits of an LNAV field
+pub fn lnav_field(v: f64, lsb: f64) -> u32 {
+    (v / lsb).round() as i64 as u32
+}
+
+// 33-bit fields of CNAV and CNAV-2 messages
+fn getbitu_33(buf: &[u8], pos: usize) -> f64 {
+    getbitu(buf, pos, 1) as f64 * 4_294_967_296.0 + getbitu(buf, pos + 1, 32) as f64
+}
+
+fn getbits_33(buf: &[u8], pos: usize) -> f64 {
+    getbits(buf, pos, 1) as f64 * 4_294_967_296.0 + getbitu(buf, pos + 1, 32) as f64
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct Ephemeris {
     pub sv: SV,
@@ -99,6 +119,21 @@ impl Ephemeris {
         );
     }
 
+    // the reverse of nav_decode_lnav_subframe1, on top of the TLM and HOW
+    pub fn nav_encode_lnav_subframe1(&self, buf: &mut [u8]) {
+        setbitu(buf, 60, 10, self.week % 1024);
+        setbitu(buf, 70, 2, self.code);
+        setbitu(buf, 72, 4, self.sva);
+        setbitu(buf, 76, 6, self.svh);
+        setbitu2(buf, 82, 2, 210, 8, self.iodc);
+        setbitu(buf, 90, 1, self.flag);
+        setbitu(buf, 196, 8, lnav_field(self.tgd, P2_31));
+        setbitu(buf, 218, 16, self.toc / 16);
+        setbitu(buf, 240, 8, lnav_field(self.f2, P2_55));
+        setbitu(buf, 248, 16, lnav_field(self.f1, P2_43));
+        setbitu(buf, 270, 22, lnav_field(self.f0, P2_31));
+    }
+
     pub fn nav_decode_lnav_subframe2(&mut self, buf: &[u8], sv: SV) {
         self.tow = getbitu(buf, 30, 17) * 6;
         self.iode = getbitu(buf, 60, 8);
@@ -129,6 +164,19 @@ impl Ephemeris {
         );
     }
 
+    pub fn nav_encode_lnav_subframe2(&self, buf: &mut [u8]) {
+        setbitu(buf, 60, 8, self.iode);
+        setbitu(buf, 68, 16, lnav_field(self.crs, P2_5));
+        setbitu(buf, 90, 16, lnav_field(self.deln, P2_43 * SC2RAD));
+        setbitu2(buf, 106, 8, 120, 24, lnav_field(self.m0, P2_31 * SC2RAD));
+        setbitu(buf, 150, 16, lnav_field(self.cuc, P2_29));
+        setbitu2(buf, 166, 8, 180, 24, lnav_field(self.ecc, P2_33));
+        setbitu(buf, 210, 16, lnav_field(self.cus, P2_29));
+        setbitu2(buf, 226, 8, 240, 24, lnav_field(self.a.sqrt(), P2_19));
+        setbitu(buf, 270, 16, self.toe / 16);
+        setbitu(buf, 286, 1, self.fit);
+    }
+
     pub fn nav_decode_lnav_subframe3(&mut self, buf: &[u8], sv: SV) {
         self.tow = getbitu(buf, 30, 17) * 6;
         self.cic = getbits(buf, 60, 16) as f64 * P2_29;
@@ -154,4 +202,190 @@ impl Ephemeris {
             self.i_dot
         );
     }
+
+    pub fn nav_encode_lnav_subframe3(&self, buf: &mut [u8]) {
+        setbitu(buf, 60, 16, lnav_field(self.cic, P2_29));
+        setbitu2(buf, 76, 8, 90, 24, lnav_field(self.omg0, P2_31 * SC2RAD));
+        setbitu(buf, 120, 16, lnav_field(self.cis, P2_29));
+        setbitu2(buf, 136, 8, 150, 24, lnav_field(self.i0, P2_31 * SC2RAD));
+        setbitu(buf, 180, 16, lnav_field(self.crc, P2_5));
+        setbitu2(buf, 196, 8, 210, 24, lnav_field(self.omg, P2_31 * SC2RAD));
+        setbitu(buf, 240, 24, lnav_field(self.omg_dot, P2_43 * SC2RAD));
+        setbitu(buf, 270, 8, self.iode);
+        setbitu(buf, 278, 14, lnav_field(self.i_dot, P2_43 * SC2RAD));
+    }
+
+    pub fn get_eccentric_anomaly(&self, t_k: f64) -> f64 {
+        // computed mean motion
+        let n0 = (EARTH_MU_GPS / self.a.powi(3)).sqrt();
+        // corrected mean motion
+        let n = n0 + self.deln;
+        // mean anomaly
+        let mk = self.m0 + n * t_k;
+
+        let mut e = mk;
+        let mut e_k = 0.0;
+        let mut n_iter = 0;
+
+        while (e - e_k).abs() > 1e-14 && n_iter < 30 {
+            e_k = e;
+            e = e + (mk - e + self.ecc * e.sin()) / (1.0 - self.ecc * e.cos());
+            n_iter += 1;
+        }
+        assert!(n_iter < 20);
+
+        e
+    }
+
+    // ECEF position of the SV, dte seconds after toe (IS-GPS-200, 20.3.3.4.3)
+    pub fn get_sv_position_ecef(&self, dte: f64) -> (f64, f64, f64) {
+        let ecc_anomaly = self.get_eccentric_anomaly(dte);
+        let v_k = ((1.0 - self.ecc.powi(2)).sqrt() * ecc_anomaly.sin())
+            .atan2(ecc_anomaly.cos() - self.ecc);
+
+        let phi_k = v_k + self.omg;
+        let duk = self.cus * (2.0 * phi_k).sin() + self.cuc * (2.0 * phi_k).cos();
+        let drk = self.crs * (2.0 * phi_k).sin() + self.crc * (2.0 * phi_k).cos();
+        let dik = self.cis * (2.0 * phi_k).sin() + self.cic * (2.0 * phi_k).cos();
+
+        let uk = phi_k + duk;
+        let rk = self.a * (1.0 - self.ecc * ecc_anomaly.cos()) + drk;
+        let ik = self.i0 + self.i_dot * dte + dik;
+
+        let orb_plane_x = rk * uk.cos();
+        let orb_plane_y = rk * uk.sin();
+
+        let omega = self.omg0 + (self.omg_dot - EARTH_ROTATION_RATE) * dte
+            - EARTH_ROTATION_RATE * self.toe as f64;
+
+        let ecef_x = orb_plane_x * omega.cos() - orb_plane_y * ik.cos() * omega.sin();
+        let ecef_y = orb_plane_x * omega.sin() + orb_plane_y * ik.cos() * omega.cos();
+        let ecef_z = orb_plane_y * ik.sin();
+
+        (ecef_x, ecef_y, ecef_z)
+    }
+
+    // SV clock offset for L1 C/A, dtc seconds after toc and dte after toe,
+    // relativistic correction included (IS-GPS-200, 20.3.3.3.3.1)
+    pub fn get_clock_bias(&self, dtc: f64, dte: f64) -> f64 {
+        let ecc_anomaly = self.get_eccentric_anomaly(dte);
+        let rel = REL_CLOCK_F * self.ecc * self.a.sqrt() * ecc_anomaly.sin();
+        self.f0 + self.f1 * dtc + self.f2 * dtc * dtc + rel - self.tgd
+    }
+
+    // CNAV-2 subframe 2: clock and ephemeris (IS-GPS-800, 3.5.3.6)
+    pub fn nav_decode_cnav2_subframe2(&mut self, buf: &[u8], sv: SV) {
+        self.week = getbitu(buf, 0, 13);
+        let itow = getbitu(buf, 13, 8);
+        let _top = getbitu(buf, 21, 11) * 300;
+        self.svh = getbitu(buf, 32, 1);
+        self.sva = getbitu(buf, 33, 5);
+        self.toe = getbitu(buf, 38, 11) * 300;
+        self.toc = self.toe;
+        self.a = CNAV_A_REF + getbits(buf, 49, 26) as f64 * P2_9;
+        let _a_dot = getbits(buf, 75, 25) as f64 * P2_21;
+        self.deln = getbits(buf, 100, 17) as f64 * P2_44 * SC2RAD;
+        let _deln_dot = getbits(buf, 117, 23) as f64 * P2_57;
+        self.m0 = getbits_33(buf, 140) * P2_32 * SC2RAD;
+        self.ecc = getbitu_33(buf, 173) * P2_34;
+        self.omg = getbits_33(buf, 206) * P2_32 * SC2RAD;
+        self.omg0 = getbits_33(buf, 239) * P2_32 * SC2RAD;
+        self.i0 = getbits_33(buf, 272) * P2_32 * SC2RAD;
+        self.omg_dot = (CNAV_OMG_DOT_REF + getbits(buf, 305, 17) as f64 * P2_44) * SC2RAD;
+        self.i_dot = getbits(buf, 322, 15) as f64 * P2_44 * SC2RAD;
+        self.cis = getbits(buf, 337, 16) as f64 * P2_30;
+        self.cic = getbits(buf, 353, 16) as f64 * P2_30;
+        self.crs = getbits(buf, 369, 24) as f64 * P2_8;
+        self.crc = getbits(buf, 393, 24) as f64 * P2_8;
+        self.cus = getbits(buf, 417, 21) as f64 * P2_30;
+        self.cuc = getbits(buf, 438, 21) as f64 * P2_30;
+        self.f0 = getbits(buf, 470, 26) as f64 * P2_35;
+        self.f1 = getbits(buf, 496, 20) as f64 * P2_48;
+        self.f2 = getbits(buf, 516, 10) as f64 * P2_60;
+        self.tgd = getbits(buf, 526, 13) as f64 * P2_35;
+
+        log::warn!(
+            "{sv}: {} week={} itow={itow} toe={} a={} ecc={} m0={} i0={} f0={:+e} f1={:+e}",
+            "cnav2-subframe-2".blue(),
+            self.week,
+            self.toe,
+            self.a,
+            self.ecc,
+            self.m0,
+            self.i0,
+            self.f0,
+            self.f1,
+        );
+    }
+
+    // CNAV message type 10: ephemeris 1 (IS-GPS-200, 30.3.3.1)
+    pub fn nav_decode_cnav_msg10(&mut self, buf: &[u8], sv: SV) {
+        self.week = getbitu(buf, 38, 13);
+        self.svh = getbitu(buf, 51, 3);
+        let _top = getbitu(buf, 54, 11) * 300;
+        self.sva = getbitu(buf, 65, 5);
+        self.toe = getbitu(buf, 70, 11) * 300;
+        self.a = CNAV_A_REF + getbits(buf, 81, 26) as f64 * P2_9;
+        let _a_dot = getbits(buf, 107, 25) as f64 * P2_21;
+        self.deln = getbits(buf, 132, 17) as f64 * P2_44 * SC2RAD;
+        let _deln_dot = getbits(buf, 149, 23) as f64 * P2_57;
+        self.m0 = getbits_33(buf, 172) * P2_32 * SC2RAD;
+        self.ecc = getbitu_33(buf, 205) * P2_34;
+        self.omg = getbits_33(buf, 238) * P2_32 * SC2RAD;
+
+        log::warn!(
+            "{sv}: {} week={} svh={} toe={} a={} ecc={} m0={} omg={}",
+            "cnav-msg-10".blue(),
+            self.week,
+            self.svh,
+            self.toe,
+            self.a,
+            self.ecc,
+            self.m0,
+            self.omg,
+        );
+    }
+
+    // CNAV message type 11: ephemeris 2
+    pub fn nav_decode_cnav_msg11(&mut self, buf: &[u8], sv: SV) {
+        self.toe = getbitu(buf, 38, 11) * 300;
+        self.omg0 = getbits_33(buf, 49) * P2_32 * SC2RAD;
+        self.i0 = getbits_33(buf, 82) * P2_32 * SC2RAD;
+        self.omg_dot = (CNAV_OMG_DOT_REF + getbits(buf, 115, 17) as f64 * P2_44) * SC2RAD;
+        self.i_dot = getbits(buf, 132, 15) as f64 * P2_44 * SC2RAD;
+        self.cis = getbits(buf, 147, 16) as f64 * P2_30;
+        self.cic = getbits(buf, 163, 16) as f64 * P2_30;
+        self.crs = getbits(buf, 179, 24) as f64 * P2_8;
+        self.crc = getbits(buf, 203, 24) as f64 * P2_8;
+        self.cus = getbits(buf, 227, 21) as f64 * P2_30;
+        self.cuc = getbits(buf, 248, 21) as f64 * P2_30;
+
+        log::warn!(
+            "{sv}: {} toe={} omg0={} i0={} omgd={:+e} idot={:+e}",
+            "cnav-msg-11".blue(),
+            self.toe,
+            self.omg0,
+            self.i0,
+            self.omg_dot,
+            self.i_dot,
+        );
+    }
+
+    // clock parameters shared by CNAV message types 30 to 37
+    pub fn nav_decode_cnav_clock(&mut self, buf: &[u8], sv: SV) {
+        let _top = getbitu(buf, 38, 11) * 300;
+        self.toc = getbitu(buf, 60, 11) * 300;
+        self.f0 = getbits(buf, 71, 26) as f64 * P2_35;
+        self.f1 = getbits(buf, 97, 20) as f64 * P2_48;
+        self.f2 = getbits(buf, 117, 10) as f64 * P2_60;
+
+        log::warn!(
+            "{sv}: {} toc={} f0={:+e} f1={:+e} f2={:+e}",
+            "cnav-clock".blue(),
+            self.toc,
+            self.f0,
+            self.f1,
+            self.f2,
+        );
+    }
 }
@@ -0,0 +1,296 @@
+// Forward error correction: rate 1/2, constraint length 7 convolutional code
+// (G1=171o, G2=133o) as used by SBAS L1, GPS L2C/L5 CNAV and Galileo, and
+// the BCH and LDPC codes of GPS CNAV-2.
+
+const NUM_STATES: usize = 64;
+const POLY_G1: u32 = 0o171;
+const POLY_G2: u32 = 0o133;
+
+fn parity(v: u32) -> u8 {
+    (v.count_ones() & 1) as u8
+}
+
+// The encoder register holds 7 bits: the new input bit in bit 6 followed by
+// the 6 previous bits. Returns the two output symbols.
+fn conv_output(reg: u32) -> (u8, u8) {
+    (parity(reg & POLY_G1), parity(reg & POLY_G2))
+}
+
+pub fn conv_encode(bits: &[u8]) -> Vec<u8> {
+    let mut state = 0u32;
+    let mut syms = Vec::with_capacity(2 * bits.len());
+
+    for &b in bits {
+        let reg = ((b as u32) << 6) | state;
+        let (g1, g2) = conv_output(reg);
+        syms.push(g1);
+        syms.push(g2);
+        state = reg >> 1;
+    }
+    syms
+}
+
+// Soft decision Viterbi decoder. syms are soft symbols: a positive value
+// stands for a 1, a negative one for a 0. The encoder state at the beginning
+// and the end of the sequence is unknown.
+pub fn viterbi_decode(syms: &[f64]) -> Vec<u8> {
+    let n = syms.len() / 2;
+    let mut metric = [0.0f64; NUM_STATES];
+    let mut decisions: Vec<[u8; NUM_STATES]> = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let (s1, s2) = (syms[2 * i], syms[2 * i + 1]);
+        let mut new_metric = [f64::NEG_INFINITY; NUM_STATES];
+        let mut dec = [0u8; NUM_STATES];
+
+        for (state, m) in metric.iter().enumerate() {
+            for b in 0..2 {
+                let reg = (b << 6) | state as u32;
+                let (g1, g2) = conv_output(reg);
+                let bm = s1 * (2.0 * g1 as f64 - 1.0) + s2 * (2.0 * g2 as f64 - 1.0);
+                let next = (reg >> 1) as usize;
+                if m + bm > new_metric[next] {
+                    new_metric[next] = m + bm;
+                    // the bit shifted out identifies the predecessor
+                    dec[next] = (state & 1) as u8;
+                }
+            }
+        }
+        let max = new_metric.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        new_metric.iter_mut().for_each(|m| *m -= max);
+        metric = new_metric;
+        decisions.push(dec);
+    }
+
+    let mut state = 0;
+    for (s, m) in metric.iter().enumerate() {
+        if *m > metric[state] {
+            state = s;
+        }
+    }
+
+    let mut bits = vec![0u8; n];
+    for i in (0..n).rev() {
+        bits[i] = ((state >> 5) & 1) as u8;
+        state = ((state << 1) & (NUM_STATES - 1)) | decisions[i][state] as usize;
+    }
+    bits
+}
+
+// BCH(51,8) code of the CNAV-2 time of interval (IS-GPS-800, 3.2.3.2): the 8
+// LSBs of the 9-bit TOI are fed through the x^8+x^7+x^6+x^5+x^4+x+1 shift
+// register, then the MSB is prepended and xor-ed to the 51 output bits.
+pub fn bch_toi_encode(toi: u32) -> Vec<u8> {
+    let msb = ((toi >> 8) & 1) as u8;
+    let mut reg = toi & 0xff;
+    let mut bits = Vec::with_capacity(52);
+
+    bits.push(msb);
+    for _ in 0..51 {
+        let out = ((reg >> 7) & 1) as u8;
+        bits.push(out ^ msb);
+        let fb = parity(reg & 0b1111_0011);
+        reg = ((reg << 1) | fb as u32) & 0xff;
+    }
+    bits
+}
+
+// Maximum likelihood decoding of the 52 TOI symbols (positive means 1).
+// Returns the TOI and the correlation. The complement of a codeword is the
+// codeword of the TOI with the opposite MSB: the symbol polarity has to be
+// known beforehand.
+pub fn bch_toi_decode(syms: &[f64]) -> (u32, f64) {
+    let mut best = (0, f64::NEG_INFINITY);
+    for toi in 0..512 {
+        let corr: f64 = bch_toi_encode(toi)
+            .iter()
+            .zip(syms)
+            .map(|(&b, s)| (2.0 * b as f64 - 1.0) * s)
+            .sum();
+        if corr > best.1 {
+            best = (toi, corr);
+        }
+    }
+    best
+}
+
+// LDPC parity check matrix: one line per row, listing the 1-based columns of
+// its non-zero elements.
+pub fn load_ldpc_matrix(file: &str) -> Option<Vec<Vec<usize>>> {
+    let content = match std::fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            log::warn!("failed to read LDPC matrix {file}: {e}");
+            return None;
+        }
+    };
+    let rows = content
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            l.split_whitespace()
+                .map(|v| v.parse::<usize>().ok()?.checked_sub(1))
+                .collect::<Option<Vec<_>>>()
+        })
+        .collect::<Option<Vec<_>>>();
+    if rows.is_none() {
+        log::warn!("{file}: invalid LDPC matrix");
+    }
+    rows
+}
+
+// Normalized min-sum LDPC decoder. syms are soft symbols (positive means 1),
+// returns the codeword once all parity checks are satisfied.
+pub fn ldpc_decode(h: &[Vec<usize>], syms: &[f64], max_iter: usize) -> Option<Vec<u8>> {
+    const ALPHA: f64 = 0.75;
+    // log likelihood ratios, positive means 0
+    let llr: Vec<f64> = syms.iter().map(|s| -s).collect();
+    let mut c2v: Vec<Vec<f64>> = h.iter().map(|row| vec![0.0; row.len()]).collect();
+    let mut bits = vec![0u8; syms.len()];
+
+    for _ in 0..max_iter {
+        let mut total = llr.clone();
+        for (row, msgs) in h.iter().zip(&c2v) {
+            for (&v, m) in row.iter().zip(msgs) {
+                total[v] += m;
+            }
+        }
+        for (b, t) in bits.iter_mut().zip(&total) {
+            *b = if *t < 0.0 { 1 } else { 0 };
+        }
+        if h.iter()
+            .all(|row| row.iter().fold(0, |acc, &v| acc ^ bits[v]) == 0)
+        {
+            return Some(bits);
+        }
+
+        for (row, msgs) in h.iter().zip(c2v.iter_mut()) {
+            let v2c: Vec<f64> = row
+                .iter()
+                .zip(msgs.iter())
+                .map(|(&v, m)| total[v] - m)
+                .collect();
+            let sign = v2c
+                .iter()
+                .fold(1.0, |acc, x| if *x < 0.0 { -acc } else { acc });
+            let mut min1 = f64::INFINITY;
+            let mut min2 = f64::INFINITY;
+            let mut min_idx = 0;
+            for (i, x) in v2c.iter().enumerate() {
+                let a = x.abs();
+                if a < min1 {
+                    min2 = min1;
+                    min1 = a;
+                    min_idx = i;
+                } else if a < min2 {
+                    min2 = a;
+                }
+            }
+            for (i, m) in msgs.iter_mut().enumerate() {
+                let mag = if i == min_idx { min2 } else { min1 };
+                let s = if v2c[i] < 0.0 { -sign } else { sign };
+                *m = ALPHA * s * mag;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rand_distr::{Distribution, Normal};
+
+    // soft symbols of the bits (positive means 1), the given ones inverted
+    fn to_syms(bits: &[u8], flips: &[usize]) -> Vec<f64> {
+        let mut syms: Vec<f64> = bits.iter().map(|&b| 2.0 * b as f64 - 1.0).collect();
+        for &i in flips {
+            syms[i] = -syms[i];
+        }
+        syms
+    }
+
+    #[test]
+    fn viterbi_bit_errors() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let noise = Normal::new(0.0, 0.5).unwrap();
+        let bits: Vec<u8> = (0..250).map(|_| rng.gen_range(0..2)).collect();
+        let syms = conv_encode(&bits);
+        assert_eq!(syms.len(), 2 * bits.len());
+
+        let mut syms = to_syms(&syms, &[40, 101, 102, 250, 333, 420]);
+        for s in syms.iter_mut() {
+            *s += noise.sample(&mut rng);
+        }
+        assert_eq!(viterbi_decode(&syms), bits);
+    }
+
+    #[test]
+    fn bch_toi_round_trip() {
+        for toi in 0..512 {
+            let bits = bch_toi_encode(toi);
+            assert_eq!(bits.len(), 52);
+            assert_eq!(bits[0] as u32, toi >> 8);
+
+            let (dec, corr) = bch_toi_decode(&to_syms(&bits, &[]));
+            assert_eq!((dec, corr), (toi, 52.0));
+
+            let flips = [toi as usize % 52, 7, 19, 33, 50];
+            let (dec, _) = bch_toi_decode(&to_syms(&bits, &flips));
+            assert_eq!(dec, toi, "toi {toi} with 5 bit errors");
+        }
+    }
+
+    #[test]
+    fn ldpc_decode_bit_errors() {
+        // H = [A | T]: A with 3 ones per column, T dual-diagonal so that the
+        // parity bits are the running sums of the A rows
+        const K: usize = 200;
+        const M: usize = 200;
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut h: Vec<Vec<usize>> = (0..M)
+            .map(|r| {
+                if r == 0 {
+                    vec![K]
+                } else {
+                    vec![K + r - 1, K + r]
+                }
+            })
+            .collect();
+        for col in 0..K {
+            let mut rows = vec![];
+            while rows.len() < 3 {
+                let r = rng.gen_range(0..M);
+                if !rows.contains(&r) {
+                    rows.push(r);
+                }
+            }
+            for r in rows {
+                h[r].push(col);
+            }
+        }
+
+        let mut bits: Vec<u8> = (0..K).map(|_| rng.gen_range(0..2)).collect();
+        let mut p = 0;
+        for row in &h {
+            p ^= row
+                .iter()
+                .filter(|&&v| v < K)
+                .fold(0, |acc, &v| acc ^ bits[v]);
+            bits.push(p);
+        }
+        assert!(
+            h.iter()
+                .all(|row| row.iter().fold(0, |acc, &v| acc ^ bits[v]) == 0)
+        );
+
+        let mut syms = to_syms(&bits, &[3, 57, 121, 180, 233, 390]);
+        for s in syms.iter_mut() {
+            *s *= rng.gen_range(0.5..1.5);
+        }
+        assert_eq!(ldpc_decode(&h, &syms, 50), Some(bits));
+    }
+}
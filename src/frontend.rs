@@ -0,0 +1,134 @@
+use rustfft::num_complex::Complex64;
+
+use crate::receiver::IQReader;
+use crate::recording::ReadStats;
+
+const PI: f64 = std::f64::consts::PI;
+const TAPS_PER_DECIM: usize = 32;
+const CUTOFF_RATIO: f64 = 0.8; // low-pass cutoff relative to the usable bandwidth
+
+// Front end for recordings of real samples at an intermediate frequency,
+// e.g. MAX2769 style captures. The samples are down-converted by fi with a
+// complex NCO, low-pass filtered and decimated by decim: the receiver sees
+// a complex baseband stream sampled at fs / decim.
+pub struct RealIfFrontEnd {
+    iq_feed: Box<dyn IQReader>, // real samples in the re part
+    fs: f64,
+    fi: f64,
+    decim: usize,
+    taps: Vec<f64>,
+    hist: Vec<Complex64>, // last mixed samples, for the next filter window
+    next_off: usize,      // input offset following the last samples read
+    nco_off: usize,       // input samples lost, not yet in the offsets
+    num_lost: usize,      // input samples lost, not yet reported
+}
+
+impl RealIfFrontEnd {
+    pub fn new(iq_feed: Box<dyn IQReader>, fs: f64, fi: f64, decim: usize) -> Self {
+        let decim = usize::max(1, decim);
+        // after mixing, the image of the signal sits 2 * fi away from it
+        let mut bw = fs / decim as f64 / 2.0;
+        if fi != 0.0 {
+            bw = bw.min(fi.abs());
+        }
+        let taps = Self::gen_taps(TAPS_PER_DECIM * decim + 1, CUTOFF_RATIO * bw / fs);
+
+        log::warn!(
+            "real IF front end: fi={:.1} KHz decim={decim} fs_out={:.1} KHz cutoff={:.1} KHz",
+            fi / 1000.0,
+            fs / decim as f64 / 1000.0,
+            CUTOFF_RATIO * bw / 1000.0
+        );
+        Self {
+            iq_feed,
+            fs,
+            fi,
+            decim,
+            hist: vec![Complex64::default(); taps.len() - 1],
+            taps,
+            next_off: 0,
+            nco_off: 0,
+            num_lost: 0,
+        }
+    }
+
+    // Hamming windowed sinc low-pass filter, cutoff relative to fs, unit gain
+    fn gen_taps(num: usize, cutoff: f64) -> Vec<f64> {
+        let m = (num - 1) as f64;
+        let taps: Vec<f64> = (0..num)
+            .map(|i| {
+                let x = i as f64 - m / 2.0;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * x).sin() / (PI * x)
+                };
+                sinc * (0.54 - 0.46 * (2.0 * PI * i as f64 / m).cos())
+            })
+            .collect();
+        let sum: f64 = taps.iter().sum();
+        taps.iter().map(|v| v / sum).collect()
+    }
+}
+
+impl IQReader for RealIfFrontEnd {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        let off = off_samples * self.decim;
+        let samples = self.iq_feed.get_iq_data(off, num_samples * self.decim)?;
+
+        // Samples lost by a live source come before the ones just read: the
+        // NCO skips over them, until the reader moves its offsets past them.
+        let num_lost = self.iq_feed.take_lost_samples();
+        self.nco_off += num_lost;
+        self.num_lost += num_lost;
+
+        // the filter history only applies to contiguous reads
+        if off != self.next_off || num_lost > 0 {
+            self.hist.fill(Complex64::default());
+        }
+        let mut buf = std::mem::take(&mut self.hist);
+        buf.reserve(samples.len());
+        for (i, v) in samples.iter().enumerate() {
+            // NCO phase from the absolute sample index, in cycles
+            let cycles = ((self.nco_off + off + i) as f64 * self.fi / self.fs).fract();
+            buf.push(Complex64::from_polar(v.re, -2.0 * PI * cycles));
+        }
+
+        let iq_vec = (0..num_samples)
+            .map(|k| {
+                let window = &buf[k * self.decim..k * self.decim + self.taps.len()];
+                window
+                    .iter()
+                    .zip(self.taps.iter())
+                    .map(|(s, t)| s * t)
+                    .sum()
+            })
+            .collect();
+
+        self.hist = buf[buf.len() + 1 - self.taps.len()..].to_vec();
+        self.next_off = off + samples.len();
+        Ok(iq_vec)
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        self.iq_feed.get_stats()
+    }
+
+    fn get_gain_db(&self) -> Option<f64> {
+        self.iq_feed.get_gain_db()
+    }
+
+    // in output samples, the remainder is reported with the next gap: the
+    // reader moves its offsets by as many samples
+    fn take_lost_samples(&mut self) -> usize {
+        let num_lost = self.num_lost / self.decim;
+        self.num_lost %= self.decim;
+        self.nco_off -= num_lost * self.decim;
+        self.next_off += num_lost * self.decim;
+        num_lost
+    }
+}
@@ -5,15 +5,28 @@ pub mod code;
 pub mod constants;
 pub mod device;
 pub mod ephemeris;
+pub mod fec;
+pub mod frontend;
+pub mod multifile;
 pub mod navigation;
+pub mod netiq;
 pub mod network;
 pub mod plots;
+pub mod queue;
 pub mod receiver;
+pub mod recorder;
 pub mod recording;
+pub mod rinex;
+pub mod sbas;
+pub mod sigmf;
+pub mod simulator;
 pub mod solver;
 pub mod state;
+pub mod stream;
+pub mod throttle;
 pub mod util;
 
 pub use app::egui_main;
 
+#[cfg(feature = "rtlsdr")]
 extern crate rtlsdr_mt;
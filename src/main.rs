@@ -1,4 +1,4 @@
-use chrono::Local;
+use chrono::{Local, NaiveDateTime};
 use colored::Colorize;
 use coredump::register_panic_handler;
 use log::LevelFilter;
@@ -12,9 +12,16 @@ use std::time::Instant;
 use structopt::StructOpt;
 
 use gnss_rcv::code::Code;
+use gnss_rcv::device::enumerate_devices;
+use gnss_rcv::network::{IfGain, RtlSdrTcpConfig};
 use gnss_rcv::plots::plot_remove_old_graph;
 use gnss_rcv::receiver::Receiver;
 use gnss_rcv::recording::IQFileType;
+use gnss_rcv::rinex::{get_gps_week_sow, load_rinex_iono_utc, load_rinex_nav};
+use gnss_rcv::sigmf::{get_data_path, is_sigmf_file};
+use gnss_rcv::simulator::{
+    SimConfig, Simulator, get_almanac, get_visible_sats, load_trajectory, parse_position,
+};
 use gnss_rcv::state::GnssState;
 
 #[derive(StructOpt)]
@@ -23,31 +30,220 @@ struct Options {
     #[structopt(
         short = "f",
         long,
+        help = "IQ recording, pattern of its segments (e.g. \"rec.*\"), named pipe or - for stdin",
         default_value = "resources/nov_3_time_18_48_st_ives"
     )]
     file: PathBuf,
-    #[structopt(short = "s", long, help = "host for rtl-sdr-tcp", default_value = "")]
+    #[structopt(
+        long,
+        help = "record the samples of the rtl-sdr device or network source to a file of type -t, with its SigMF metadata",
+        default_value = ""
+    )]
+    record: PathBuf,
+    #[structopt(
+        long,
+        help = "play recordings back in real time, at fs times this speed (e.g. 0.5, 1, 10): 0 for as fast as possible",
+        default_value = "0.0"
+    )]
+    speed: f64,
+    #[structopt(
+        short = "s",
+        long,
+        help = "host:port of rtl_tcp, or tcp://host:port, udp://[addr]:port[?seq] for raw IQ of type -t",
+        default_value = ""
+    )]
     hostname: String,
-    #[structopt(long, help = "signal: L1CA, etc.", default_value = "L1CA")]
+    #[structopt(
+        long,
+        help = "device or rtl_tcp: manual tuner gain in dB (default: automatic)"
+    )]
+    gain: Option<f64>,
+    #[structopt(
+        long,
+        help = "rtl_tcp: manual tuner gain, as an index in the tuner gains"
+    )]
+    gain_idx: Option<u32>,
+    #[structopt(
+        long,
+        help = "rtl_tcp: frequency correction in ppm",
+        default_value = "0",
+        allow_hyphen_values = true
+    )]
+    ppm: i32,
+    #[structopt(
+        long,
+        help = "rtl_tcp: IF gain of an E4000 stage, e.g. 1:6 for 6 dB on stage 1"
+    )]
+    if_gain: Vec<IfGain>,
+    #[structopt(long, help = "rtl_tcp: enable offset tuning")]
+    offset_tuning: bool,
+    #[structopt(
+        long,
+        help = "rtl_tcp: direct sampling, 0: off, 1: I ADC, 2: Q ADC",
+        default_value = "0"
+    )]
+    direct_sampling: u32,
+    #[structopt(long, help = "rtl_tcp: test mode, the dongle outputs a counter")]
+    test_mode: bool,
+    #[structopt(
+        long,
+        help = "signal: L1CA, L1C, E1B, E1C, B1I, L1OF",
+        default_value = "L1CA"
+    )]
     sig: String,
-    #[structopt(short = "d", long, help = "use rtl-sdr device")]
+    #[structopt(
+        long,
+        help = "signal of a second band: L2CM, L2CL, L5I, L5Q, E5aI, E5aQ",
+        default_value = ""
+    )]
+    sig2: String,
+    #[structopt(long, help = "IQ file of the second band", default_value = "")]
+    file2: PathBuf,
+    #[structopt(
+        long,
+        help = "SDR device of the second band, cf --device",
+        default_value = ""
+    )]
+    device2: String,
+    #[structopt(
+        long,
+        help = "network source of the second band, cf --hostname",
+        default_value = ""
+    )]
+    hostname2: String,
+    #[structopt(
+        long,
+        help = "sampling frequency of the second band (default: the one of the first band)",
+        default_value = "0.0"
+    )]
+    fs2: f64,
+    #[structopt(
+        long,
+        help = "intermediate frequency of the second band (default: the one of the first band)"
+    )]
+    fi2: Option<f64>,
+    #[structopt(
+        long,
+        help = "decimation of the second band, cf --decim (default: the one of the first band)"
+    )]
+    decim2: Option<usize>,
+    #[structopt(
+        long,
+        help = "RF center frequency of the second band (default: signal frequency)",
+        default_value = "0.0"
+    )]
+    fc2: f64,
+    #[structopt(short = "d", long, help = "use an SDR device, cf --device")]
     use_device: bool,
+    #[structopt(
+        long,
+        help = "SDR device: rtlsdr[:index] or mock:<file> to replay a recording of type -t in real time",
+        default_value = "rtlsdr"
+    )]
+    device: String,
+    #[structopt(long, help = "list the SDR devices found and exit")]
+    list_devices: bool,
     #[structopt(short = "l", long, help = "path to log file", default_value = "")]
     log_file: PathBuf,
-    #[structopt(short = "t", long, help = "type of IQ file", default_value = "2xf32")]
+    #[structopt(
+        short = "t",
+        long,
+        help = "type of IQ file: 2xf32, 2xf32be, 2xf16, 2xi16, 2xi16be, 2xi12, 2xi8, rtlsdr-file, i8, i4 or i2 (SigMF: from metadata)",
+        default_value = "2xf32"
+    )]
     iq_file_type: IQFileType,
     #[structopt(long, help = "sampling frequency", default_value = "2046000.0")]
     fs: f64,
     #[structopt(long, help = "intermediate frequency", default_value = "0.0")]
     fi: f64,
+    #[structopt(
+        long,
+        help = "decimation of real IF recordings (i8), after down-conversion",
+        default_value = "1"
+    )]
+    decim: usize,
+    #[structopt(
+        long,
+        help = "RF center frequency of the capture (default: signal frequency)",
+        default_value = "0.0"
+    )]
+    fc: f64,
+    #[structopt(
+        long,
+        help = "write lock events as annotations to the .sigmf-meta of a SigMF recording"
+    )]
+    sigmf_annotate: bool,
     #[structopt(long, help = "offset in file", default_value = "0")]
     off_msec: usize,
     #[structopt(long, help = "duration of sample", default_value = "0")]
     num_msec: usize,
-    #[structopt(long, help = "satellites to use", default_value = "")]
+    #[structopt(
+        long,
+        help = "satellites to use: e.g. 5,12 or G05,J01,S23",
+        default_value = ""
+    )]
     sats: String,
     #[structopt(short = "-u", long, help = "use ui")]
     use_ui: bool,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    #[structopt(about = "generate a GPS L1 C/A recording from a RINEX navigation file")]
+    Simulate {
+        #[structopt(long, help = "RINEX navigation file, version 2 or 3")]
+        nav: PathBuf,
+        #[structopt(
+            short = "o",
+            long,
+            help = "output file, with its SigMF metadata for a .sigmf-data",
+            default_value = "sim.bin"
+        )]
+        output: PathBuf,
+        #[structopt(
+            short = "t",
+            long,
+            help = "type of IQ file: 2xf32, 2xf32be, 2xf16, 2xi16, 2xi16be, 2xi12, 2xi8, i8, i4 or i2",
+            default_value = "2xi16"
+        )]
+        iq_file_type: IQFileType,
+        #[structopt(long, help = "sampling frequency", default_value = "2046000.0")]
+        fs: f64,
+        #[structopt(long, help = "intermediate frequency", default_value = "0.0")]
+        fi: f64,
+        #[structopt(
+            long,
+            help = "GPS time of the first sample: YYYY/MM/DD,hh:mm:ss (default: toe of the first ephemeris)"
+        )]
+        time: Option<String>,
+        #[structopt(long, help = "duration in seconds", default_value = "60.0")]
+        duration: f64,
+        #[structopt(
+            long,
+            help = "static receiver position: lat,lon,h in degrees and meters",
+            default_value = "35.681298,139.766247,10.0"
+        )]
+        pos: String,
+        #[structopt(
+            long,
+            help = "receiver trajectory: t,lat,lon,h lines, t in seconds from the start"
+        )]
+        traj: Option<PathBuf>,
+        #[structopt(long, help = "C/N0 at zenith in dB-Hz", default_value = "45.0")]
+        cn0: f64,
+        #[structopt(long, help = "elevation mask in degrees", default_value = "10.0")]
+        elev_mask: f64,
+        #[structopt(
+            long,
+            help = "satellites to simulate, e.g. 5,12 (default: all in view)",
+            default_value = ""
+        )]
+        sats: String,
+        #[structopt(long, help = "seed of the noise generator", default_value = "0")]
+        seed: u64,
+    },
 }
 
 fn init_logging(log_file: &PathBuf) {
@@ -84,11 +280,105 @@ fn init_ctrl_c(exit_req: Arc<AtomicBool>) {
     .expect("Error setting Ctrl-C handler");
 }
 
+#[allow(clippy::too_many_arguments)]
+fn simulate(
+    nav: &PathBuf,
+    output: &PathBuf,
+    file_type: &IQFileType,
+    fs: f64,
+    fi: f64,
+    time: &Option<String>,
+    duration_sec: f64,
+    pos: &str,
+    traj: &Option<PathBuf>,
+    cn0: f64,
+    elev_mask: f64,
+    sats: &str,
+    seed: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // the image of the signal would land on top of it
+    if file_type.is_real() && fi == 0.0 {
+        return Err(format!(
+            "{file_type}: real samples need an intermediate frequency, e.g. --fi {}",
+            fs / 4.0
+        )
+        .into());
+    }
+    let ephs = load_rinex_nav(nav)?;
+    let (week, sow) = match time {
+        Some(time) => get_gps_week_sow(&NaiveDateTime::parse_from_str(time, "%Y/%m/%d,%H:%M:%S")?),
+        None => {
+            let eph = ephs.first().ok_or("no GPS ephemeris")?;
+            (eph.week, eph.toe)
+        }
+    };
+    let trajectory = match traj {
+        Some(path) => load_trajectory(path)?,
+        None => vec![(0.0, parse_position(pos)?)],
+    };
+    let cfg = SimConfig {
+        fs,
+        fi,
+        file_type: file_type.clone(),
+        week,
+        sow,
+        duration_sec,
+        trajectory,
+        seed,
+        almanac: get_almanac(&ephs, week, sow),
+        iono_utc: load_rinex_iono_utc(nav)?,
+    };
+    let sim_sats = get_visible_sats(&ephs, &cfg, sats, elev_mask, cn0);
+    if sim_sats.is_empty() {
+        return Err(format!("no satellite in view at week {week} sow {sow}").into());
+    }
+    let is_sigmf = is_sigmf_file(output);
+    let path = if is_sigmf {
+        get_data_path(output)
+    } else {
+        output.clone()
+    };
+    Simulator::new(cfg, sim_sats)?.write(&path, is_sigmf)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opt = Options::from_args();
     let exit_req = Arc::new(AtomicBool::new(false));
 
     init_logging(&opt.log_file);
+
+    if let Some(Command::Simulate {
+        nav,
+        output,
+        iq_file_type,
+        fs,
+        fi,
+        time,
+        duration,
+        pos,
+        traj,
+        cn0,
+        elev_mask,
+        sats,
+        seed,
+    }) = &opt.cmd
+    {
+        return simulate(
+            nav,
+            output,
+            iq_file_type,
+            *fs,
+            *fi,
+            time,
+            *duration,
+            pos,
+            traj,
+            *cn0,
+            *elev_mask,
+            sats,
+            *seed,
+        );
+    }
     init_ctrl_c(exit_req.clone());
     plot_remove_old_graph();
 
@@ -105,29 +395,89 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Code::get_code_freq(&opt.sig) / 1_000_000.0
     );
 
+    if opt.list_devices {
+        for dev in enumerate_devices() {
+            println!("{dev}");
+        }
+        return Ok(());
+    }
+
     if opt.use_ui {
         gnss_rcv::egui_main();
         return Ok(());
     }
 
+    if !opt.sig2.is_empty() {
+        if Code::get_code_freq(&opt.sig2) == 0.0 {
+            return Err(format!("--sig2: unknown signal {}", opt.sig2).into());
+        }
+        if opt.file2.as_os_str().is_empty() && opt.device2.is_empty() && opt.hostname2.is_empty() {
+            return Err("--sig2 needs --file2, --device2 or --hostname2".into());
+        }
+    }
+
+    let rtl_tcp_cfg = RtlSdrTcpConfig {
+        gain_db: opt.gain,
+        gain_idx: opt.gain_idx,
+        ppm: opt.ppm,
+        if_gains: opt.if_gain.clone(),
+        offset_tuning: opt.offset_tuning,
+        direct_sampling: opt.direct_sampling,
+        test_mode: opt.test_mode,
+    };
     let mut receiver = Receiver::new(
-        opt.use_device,
+        if opt.use_device { &opt.device } else { "" },
         &opt.hostname,
+        &rtl_tcp_cfg,
         &opt.file,
         &opt.iq_file_type,
+        &opt.record,
+        opt.speed,
         opt.fs,
         opt.fi,
+        opt.decim,
+        opt.fc,
         opt.off_msec,
         &opt.sig,
         &opt.sats,
         exit_req.clone(),
         Arc::new(Mutex::new(GnssState::new())),
-    );
+    )?;
+    if !opt.sig2.is_empty() {
+        log::warn!(
+            "gnss-rcv: second band: signal {} frequency: {:.2} MHz",
+            &opt.sig2,
+            Code::get_code_freq(&opt.sig2) / 1_000_000.0
+        );
+        receiver.add_band(
+            &opt.device2,
+            &opt.hostname2,
+            &rtl_tcp_cfg,
+            &opt.file2,
+            &opt.iq_file_type,
+            opt.fs2,
+            opt.fi2.unwrap_or(opt.fi),
+            opt.decim2.unwrap_or(opt.decim),
+            opt.fc2,
+            &opt.sig2,
+            &opt.sats,
+        )?;
+    }
+
+    if opt.sigmf_annotate {
+        receiver.enable_sigmf_annotations();
+    }
 
     let ts = Instant::now();
 
     receiver.run_loop(opt.num_msec);
 
+    if opt.sigmf_annotate
+        && let Err(e) = receiver.write_sigmf_annotations()
+    {
+        log::warn!("SigMF: failed to write annotations: {e}");
+    }
+
     println!("GNSS terminating: {:.2} sec", ts.elapsed().as_secs_f32());
     exit_req.store(true, Ordering::SeqCst);
 
@@ -0,0 +1,185 @@
+use glob::glob;
+use rustfft::num_complex::Complex64;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording, ReadStats};
+
+const GAP_TOLERANCE_SEC: f64 = 1.0;
+
+// true for a path with glob wildcards, e.g. "rec.*"
+pub fn is_multi_file(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.contains(['*', '?', '[']))
+}
+
+struct Segment {
+    rec: IQRecording,
+    off_samples: Option<usize>, // offset of its first sample in the stream
+}
+
+// Recordings split in several files, e.g. rec.000, rec.001.. as produced by
+// split or by hourly rotation, played back as one stream. The files are
+// taken in the lexicographic order of their names and are expected to hold
+// whole samples. The length of a gzip file is only known once read to its
+// end: the offsets of the following segments are learned along the way.
+pub struct IQMultiRecording {
+    segments: Vec<Segment>,
+    cur: usize, // segment of the last read
+}
+
+impl IQMultiRecording {
+    pub fn new(pattern: &Path, fs: f64, file_type: &IQFileType) -> Result<Self, Box<dyn Error>> {
+        let pattern = pattern.to_str().ok_or("invalid file pattern")?;
+        let mut paths = glob(pattern)?.collect::<Result<Vec<PathBuf>, _>>()?;
+        paths.sort();
+        if paths.is_empty() {
+            return Err(format!("no file matching {pattern}").into());
+        }
+
+        let mut segments: Vec<Segment> = vec![];
+        let mut off_samples = Some(0);
+        for path in &paths {
+            let rec = IQRecording::new(path, fs, file_type)?;
+            let num_samples = rec.get_num_samples();
+            segments.push(Segment { rec, off_samples });
+            off_samples = off_samples.zip(num_samples).map(|(off, n)| off + n);
+        }
+        Self::check_gaps(&paths, &segments, fs);
+
+        let duration = match off_samples {
+            Some(n) => format!("{:.1} secs", n as f64 / fs),
+            None => "unknown".to_string(),
+        };
+        log::warn!("{pattern}: {} files, duration: {duration}", paths.len());
+        Ok(Self { segments, cur: 0 })
+    }
+
+    // The modification time of a file is the end of its capture: consecutive
+    // files should be apart by the duration of the second one.
+    fn check_gaps(paths: &[PathBuf], segments: &[Segment], fs: f64) {
+        let mtimes: Vec<Option<SystemTime>> = paths
+            .iter()
+            .map(|p| p.metadata().and_then(|m| m.modified()).ok())
+            .collect();
+
+        for i in 1..paths.len() {
+            let (Some(t0), Some(t1)) = (mtimes[i - 1], mtimes[i]) else {
+                continue;
+            };
+            let Ok(delta) = t1.duration_since(t0) else {
+                continue;
+            };
+            let Some(num_samples) = segments[i].rec.get_num_samples() else {
+                continue;
+            };
+            let gap_sec = delta.as_secs_f64() - num_samples as f64 / fs;
+            if gap_sec > GAP_TOLERANCE_SEC {
+                log::warn!(
+                    "possible gap of {gap_sec:.1} secs before {}",
+                    paths[i].display()
+                );
+            }
+        }
+    }
+}
+
+impl IQReader for IQMultiRecording {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        let mut iq_vec = Vec::with_capacity(num_samples);
+        let mut off = off_samples;
+
+        while iq_vec.len() < num_samples {
+            // reads are mostly sequential: start from the current segment,
+            // whose offset is always known
+            if off < self.segments[self.cur].off_samples.unwrap() {
+                self.cur = 0;
+            }
+            loop {
+                let seg = &self.segments[self.cur];
+                let seg_off = seg.off_samples.unwrap();
+                let Some(len) = seg.rec.get_num_samples() else {
+                    break;
+                };
+                if off < seg_off + len {
+                    break;
+                }
+                if self.cur + 1 == self.segments.len() {
+                    return Err("end of file".into());
+                }
+                self.cur += 1;
+                self.segments[self.cur].off_samples = Some(seg_off + len);
+            }
+
+            // a short read reveals the length of the segment
+            let seg = &mut self.segments[self.cur];
+            let seg_off = off - seg.off_samples.unwrap();
+            let mut data = seg.rec.read_iq_data(seg_off, num_samples - iq_vec.len())?;
+            off += data.len();
+            iq_vec.append(&mut data);
+        }
+        Ok(iq_vec)
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        let mut stats = ReadStats::default();
+        for seg in &self.segments {
+            if let Some(seg_stats) = seg.rec.get_stats() {
+                stats.add(&seg_stats);
+            }
+        }
+        Some(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f64 = 2000.0; // 2 samples per msec
+
+    // 2xi16 samples whose I holds their index in the stream
+    fn write_file(path: &Path, range: std::ops::Range<i16>) {
+        let buf: Vec<u8> = range
+            .flat_map(|i| [i.to_le_bytes(), (-i).to_le_bytes()].concat())
+            .collect();
+        std::fs::write(path, buf).unwrap();
+    }
+
+    fn indices(iq_vec: &[Complex64]) -> Vec<i16> {
+        iq_vec
+            .iter()
+            .map(|v| (v.re * i16::MAX as f64).round() as i16)
+            .collect()
+    }
+
+    #[test]
+    fn multi_file() {
+        let dir = std::env::temp_dir().join(format!("gnss-rcv-multifile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_file(&dir.join("rec.000"), 0..100);
+        write_file(&dir.join("rec.001"), 100..250);
+        let pattern = dir.join("rec.*");
+        assert!(is_multi_file(&pattern));
+
+        // the first read starts in the second file, e.g. with an off_msec
+        let mut rec = IQMultiRecording::new(&pattern, FS, &IQFileType::TypePairInt16).unwrap();
+        let off_msec = 60;
+        let iq_vec = rec.get_iq_data(off_msec * FS as usize / 1000, 10).unwrap();
+        assert_eq!(indices(&iq_vec), (120..130).collect::<Vec<_>>());
+
+        // back to the first file, and a read across the boundary
+        let iq_vec = rec.get_iq_data(90, 20).unwrap();
+        assert_eq!(indices(&iq_vec), (90..110).collect::<Vec<_>>());
+        let iq_vec = rec.get_iq_data(240, 10).unwrap();
+        assert_eq!(indices(&iq_vec), (240..250).collect::<Vec<_>>());
+        assert!(rec.get_iq_data(250, 10).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,19 +1,70 @@
 use crate::{
+    almanac::{
+        Almanac, IonoUtc, lnav_page_svid, nav_decode_alm_health, nav_decode_svconf_health,
+        nav_encode_alm_health, nav_encode_svconf_health,
+    },
     channel::Channel,
-    constants::{P2_24, P2_27, P2_30, P2_50},
+    constants::{P2_35, SECS_PER_WEEK},
     ephemeris::Ephemeris,
-    util::{bits_equal, bits_opposed, getbits, getbits2, getbitu, hex_str, setbitu, xor_bits},
+    fec::{bch_toi_decode, ldpc_decode, load_ldpc_matrix, viterbi_decode},
+    sbas::{SBAS_MSG_LEN, SBAS_PREAMBLES},
+    util::{
+        bits_equal, bits_opposed, bits_to_bytes, crc24q_bits, getbits, getbitu, hex_str, setbitu,
+        xor_bits,
+    },
 };
 use colored::Colorize;
 use gnss_rs::sv::SV;
 use gnss_rtk::prelude::Epoch;
+use once_cell::sync::Lazy;
 
-const SECS_PER_WEEK: u32 = 7 * 24 * 60 * 60;
+const LNAV_PREAMBLE: u32 = 0x8b;
+pub const LNAV_SUBFRAME_BITS: usize = 300;
+pub const LNAV_SUBFRAME_SEC: u32 = 6;
+
+// parity of an LNAV word: the bits of D29*, D30* and d1..d24 it covers, for
+// each of D25..D30 (IS-GPS-200, table 20-XIV)
+const LNAV_PARITY_MASK: [u32; 6] = [
+    0x2EC7CD2, 0x1763E69, 0x2BB1F34, 0x15D8F9A, 0x1AEC7CD, 0x22DEA27,
+];
 const SDR_MAX_NSYM: usize = 18000;
 
 const THRESHOLD_SYNC: f64 = 0.4; // 0.02
 const THRESHOLD_LOST: f64 = 0.03; // 0.002
 
+const SBAS_NSYM: usize = 1200; // soft symbol history: 2 ms (SBAS) or 20 ms (CNAV) each
+const SBAS_SEARCH_SYMS: usize = 1100; // symbols decoded per preamble search
+const SBAS_SEARCH_STEP: usize = 50; // symbols between preamble searches
+const SBAS_MARGIN: usize = 12; // bits decoded around a message for the Viterbi to settle
+const SBAS_MAX_CRC_ERR: usize = 3;
+
+const CNAV_MSG_LEN: usize = 300; // 12 s at 25 bps
+const CNAV_PREAMBLE: u32 = 0x8b;
+const CNAV_SYM_SEC: f64 = 0.02;
+
+const CNAV2_FRAME_SYMS: usize = 1800; // 18 s at 100 sps
+const CNAV2_TOI_SYMS: usize = 52;
+const CNAV2_SF2_SYMS: usize = 1200;
+const CNAV2_SF3_SYMS: usize = 548;
+const CNAV2_ILV_ROWS: usize = 38;
+const CNAV2_ILV_COLS: usize = 46;
+const CNAV2_LDPC_ITER: usize = 50;
+
+// LDPC parity check matrices of subframes 2 and 3 (IS-GPS-800, 3.5.3.4)
+const CNAV2_LDPC_SF2_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/gps_cnav2_ldpc_sf2.txt"
+);
+const CNAV2_LDPC_SF3_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/codes/gps_cnav2_ldpc_sf3.txt"
+);
+
+static CNAV2_LDPC_SF2: Lazy<Option<Vec<Vec<usize>>>> =
+    Lazy::new(|| load_ldpc_matrix(CNAV2_LDPC_SF2_FILE));
+static CNAV2_LDPC_SF3: Lazy<Option<Vec<Vec<usize>>>> =
+    Lazy::new(|| load_ldpc_matrix(CNAV2_LDPC_SF3_FILE));
+
 #[derive(PartialEq, Debug, Default)]
 enum SyncState {
     #[default]
@@ -29,6 +80,9 @@ pub struct Navigation {
     sync_state: SyncState,
     bits: Vec<u8>, // navigation bits
     count_parity_err: usize,
+    syms: Vec<f64>,  // SBAS/CNAV soft symbols
+    num_syms: usize, // SBAS/CNAV symbols received since bit sync
+    count_step: usize,
     pub eph: Ephemeris,
 }
 
@@ -41,6 +95,9 @@ impl Navigation {
             sync_state: SyncState::Normal,
             bits: vec![0; SDR_MAX_NSYM],
             count_parity_err: 0,
+            syms: vec![0.0; SBAS_NSYM],
+            num_syms: 0,
+            count_step: 0,
             eph: Ephemeris::new(sv),
         }
     }
@@ -50,7 +107,102 @@ impl Navigation {
         self.nav_sync = 0;
         self.sync_state = SyncState::Normal;
         self.bits.fill(0);
+        self.syms.fill(0.0);
+        self.num_syms = 0;
+        self.count_step = 0;
+    }
+}
+
+// TLM and HOW words of an LNAV subframe. tow is the time of the next
+// subframe, as decoded in the HOW: a multiple of 6 seconds.
+pub fn nav_encode_lnav_header(buf: &mut [u8], tlm: u32, tow: u32, subframe_id: u32) {
+    setbitu(buf, 0, 8, LNAV_PREAMBLE);
+    setbitu(buf, 8, 14, tlm);
+    setbitu(
+        buf,
+        30,
+        17,
+        tow / LNAV_SUBFRAME_SEC % (SECS_PER_WEEK / LNAV_SUBFRAME_SEC),
+    );
+    setbitu(buf, 49, 3, subframe_id);
+}
+
+// Parity of the 10 words of a subframe: nav_data holds their 24 data bits at
+// 30 * i, as passed to the nav_decode functions. Returns the 300 bits sent,
+// one per u8: the data bits of a word are inverted when D30* of the previous
+// one is set, and the last 2 data bits of words 2 and 10 are picked so that
+// their D29 and D30 are 0 (IS-GPS-200, 20.3.5.2). Subframes start with D29*
+// and D30* at 0.
+pub fn nav_encode_lnav_parity(nav_data: &[u8]) -> Vec<u8> {
+    let mut bits = vec![0; LNAV_SUBFRAME_BITS];
+    let mut prev = 0u32; // D29* and D30*
+    for i in 0..10 {
+        let mut d = getbitu(nav_data, 30 * i, 24);
+        let parity = |d: u32| -> u32 {
+            let data = (prev << 24) | d;
+            (0..6).fold(0, |p, j| {
+                (p << 1) | xor_bits(data & LNAV_PARITY_MASK[j]) as u32
+            })
+        };
+        if i == 1 || i == 9 {
+            d = (0..4)
+                .map(|t| (d & !3) | t)
+                .find(|&d| parity(d) & 3 == 0)
+                .unwrap();
+        }
+        let p = parity(d);
+        let sent = if prev & 1 != 0 { !d & 0xFFFFFF } else { d };
+        for j in 0..24 {
+            bits[30 * i + j] = ((sent >> (23 - j)) & 1) as u8;
+        }
+        for j in 0..6 {
+            bits[30 * i + 24 + j] = ((p >> (5 - j)) & 1) as u8;
+        }
+        prev = p & 3;
     }
+    bits
+}
+
+// The 300 bits of an LNAV subframe sent at tow - 6, tow being the time of
+// the next subframe as in its HOW. Subframes 4 and 5 carry page 1..25 of
+// their cycle: the almanac of alm_array (SVs with sat 0 get the dummy SV id
+// 0), the SV configurations and health, or iono_utc. Their reserved pages
+// are left empty.
+pub fn nav_encode_lnav_subframe(
+    subframe_id: u32,
+    tow: u32,
+    page: u32,
+    eph: &Ephemeris,
+    alm_array: &[Almanac],
+    iono_utc: &IonoUtc,
+) -> Vec<u8> {
+    let mut buf = [0u8; LNAV_SUBFRAME_BITS.div_ceil(8)];
+    nav_encode_lnav_header(&mut buf, 0, tow, subframe_id);
+    match subframe_id {
+        1 => eph.nav_encode_lnav_subframe1(&mut buf),
+        2 => eph.nav_encode_lnav_subframe2(&mut buf),
+        3 => eph.nav_encode_lnav_subframe3(&mut buf),
+        _ => match lnav_page_svid(subframe_id, page) {
+            // dummy page when the almanac of the SV is missing
+            svid @ 1..=32 => match alm_array.get(svid as usize - 1) {
+                Some(alm) if alm.sat != 0 => alm.nav_encode_alm(&mut buf),
+                _ => setbitu(&mut buf, 60, 2, 1),
+            },
+            51 => nav_encode_alm_health(
+                &mut buf,
+                alm_array,
+                alm_array.iter().map(|alm| alm.toas).max().unwrap_or(0),
+                alm_array.iter().map(|alm| alm.week).max().unwrap_or(0),
+            ),
+            56 => iono_utc.nav_encode_iono_utc(&mut buf),
+            63 => nav_encode_svconf_health(&mut buf, alm_array),
+            svid => {
+                setbitu(&mut buf, 60, 2, 1);
+                setbitu(&mut buf, 62, 6, svid);
+            }
+        },
+    }
+    nav_encode_lnav_parity(&buf)
 }
 
 impl Channel {
@@ -150,60 +302,25 @@ impl Channel {
 
         if data_id == 1 {
             let pub_state = &mut self.pub_state.lock().unwrap();
-            let alm_array = &mut pub_state.almanac;
 
             if (25..=32).contains(&svid) {
-                let alm = alm_array.get_mut(svid as usize - 1).unwrap();
+                let alm = pub_state.almanac.get_mut(svid as usize - 1).unwrap();
                 alm.nav_decode_alm(buf, svid);
                 log::warn!("{}: {:?}", self.sv, alm);
             } else if svid == 63 {
                 /* page 25 */
-                const ARRAY_SVCONF_IDX: [usize; 32] = [
-                    68, 72, 76, 80, 90, 94, 98, 102, 106, 110, 120, 124, 128, 132, 136, 140, 150,
-                    154, 158, 162, 166, 170, 180, 184, 188, 192, 196, 200, 210, 214, 218, 222,
-                ];
-
-                for sv in 1..=32 {
-                    let alm = alm_array.get_mut(sv - 1).unwrap();
-                    let pos = ARRAY_SVCONF_IDX[sv - 1];
-
-                    alm.svconf = getbitu(buf, pos, 4);
-                }
-
-                const ARRAY_SVH_IDX: [usize; 8] = [228, 240, 246, 252, 258, 270, 276, 282];
-                for sv in 25..=32 {
-                    let alm = alm_array.get_mut(sv - 1).unwrap();
-                    let pos = ARRAY_SVH_IDX[sv - 25];
-                    alm.svh = getbitu(buf, pos, 6);
+                nav_decode_svconf_health(buf, &mut pub_state.almanac);
+                for (sv, alm) in pub_state.almanac.iter().enumerate().skip(24) {
                     if alm.svh != 0 {
-                        log::warn!("{}: sv {} is unhealthy", self.sv, sv)
+                        log::warn!("{}: sv {} is unhealthy", self.sv, sv + 1)
                     }
                 }
             } else if svid == 55 {
                 // page 17: special message
             } else if svid == 56 {
                 /* page 18 */
-                // handle iono, utc and leap seconds
-                let mut ion = [0.0; 8];
-
-                ion[0] = getbits(buf, 68, 8) as f64 * P2_30;
-                ion[1] = getbits(buf, 76, 8) as f64 * P2_27;
-                ion[2] = getbits(buf, 90, 8) as f64 * P2_24;
-                ion[3] = getbits(buf, 98, 8) as f64 * P2_24;
-                ion[4] = getbits(buf, 106, 8) as f64 * 2.0_f64.powi(11);
-                ion[5] = getbits(buf, 120, 8) as f64 * 2.0_f64.powi(14);
-                ion[6] = getbits(buf, 128, 8) as f64 * 2.0_f64.powi(16);
-                ion[7] = getbits(buf, 136, 8) as f64 * 2.0_f64.powi(16);
-
+                pub_state.iono_utc.nav_decode_iono_utc(buf);
                 pub_state.ion_adj = true;
-
-                let mut utc: [f64; 4] = [0.0; 4];
-
-                utc[0] = getbits2(buf, 180, 24, 210, 8) as f64 * P2_30;
-                utc[1] = getbits(buf, 150, 24) as f64 * P2_50;
-                utc[2] = getbits(buf, 218, 8) as f64 * 2.0_f64.powi(12);
-                utc[3] = getbits(buf, 226, 8) as f64;
-
                 pub_state.utc_adj = true;
             }
         }
@@ -219,7 +336,7 @@ impl Channel {
     fn nav_decode_lnav_subframe5(&mut self, buf: &[u8]) {
         self.nav.eph.tow = getbitu(buf, 30, 17) * 6;
         let data_id = getbitu(buf, 60, 2);
-        let svid = getbitu(buf, 62, 4);
+        let svid = getbitu(buf, 62, 6);
         let alm_array = &mut self.pub_state.lock().unwrap().almanac;
 
         if data_id == 1 {
@@ -228,27 +345,13 @@ impl Channel {
                 alm.nav_decode_alm(buf, svid);
                 log::warn!("{}: {:?}", self.sv, alm);
             } else if svid == 51 {
-                let toas = getbitu(buf, 68, 8) * 4096;
-                let week = getbitu(buf, 76, 8) + 2048;
-
-                const ARRAY_SVH_IDX: [usize; 24] = [
-                    90, 96, 102, 108, 120, 126, 132, 138, 150, 156, 162, 168, 180, 186, 192, 198,
-                    210, 216, 222, 228, 240, 246, 252, 258,
-                ];
-                for sv in 1..=24 {
-                    let alm = alm_array.get_mut(sv - 1).unwrap();
-                    let pos = ARRAY_SVH_IDX[sv - 25];
-                    alm.svh = getbitu(buf, pos, 6);
+                nav_decode_alm_health(buf, alm_array);
+                for (sv, alm) in alm_array[..24].iter().enumerate() {
                     if alm.svh != 0 {
-                        log::warn!("{}: sv {} is unhealthy", self.sv, sv)
+                        log::warn!("{}: sv {} is unhealthy", self.sv, sv + 1)
                     }
                 }
-                for sv in 1..=32 {
-                    let alm = alm_array.get_mut(sv - 1).unwrap();
-                    alm.week = week;
-                    alm.toas = toas;
-                }
-            } else {
+            } else if svid != 0 {
                 log::warn!("XXX unknown svid={}", svid);
             }
         }
@@ -273,7 +376,7 @@ impl Channel {
                 .lock()
                 .unwrap()
                 .channels
-                .get_mut(&self.sv)
+                .get_mut(&self.id)
                 .unwrap()
                 .has_eph = true;
         }
@@ -303,7 +406,7 @@ impl Channel {
 
     fn nav_decode_lnav_subframe(&mut self, buf: &[u8]) -> u32 {
         let preamble = getbitu(buf, 0, 8);
-        assert_eq!(preamble, 0x8b);
+        assert_eq!(preamble, LNAV_PREAMBLE);
         self.nav.eph.tlm = getbitu(buf, 8, 14);
         let _isf = getbitu(buf, 22, 1);
         let _rsvd = getbitu(buf, 23, 1);
@@ -350,11 +453,8 @@ impl Channel {
         }
     }
 
-    fn nav_test_lnav_parity(bits: &[u8], nav_data: &mut [u8]) -> bool {
-        const MASK: [u32; 6] = [
-            0x2EC7CD2, 0x1763E69, 0x2BB1F34, 0x15D8F9A, 0x1AEC7CD, 0x22DEA27,
-        ];
-        assert_eq!(bits.len(), 300);
+    pub fn nav_test_lnav_parity(bits: &[u8], nav_data: &mut [u8]) -> bool {
+        assert_eq!(bits.len(), LNAV_SUBFRAME_BITS);
 
         let mut data: u32 = 0;
         for i in 0..10 {
@@ -366,7 +466,7 @@ impl Channel {
             }
             #[allow(clippy::needless_range_loop)]
             for j in 0..6 {
-                let v0 = (data >> 6) & MASK[j];
+                let v0 = (data >> 6) & LNAV_PARITY_MASK[j];
                 let v1: u8 = ((data >> (5 - j)) & 1) as u8;
                 if xor_bits(v0) != v1 {
                     return false;
@@ -378,14 +478,389 @@ impl Channel {
         true
     }
 
+    fn nav_add_soft_sym(&mut self, sym: f64) {
+        self.nav.syms.rotate_left(1);
+        *self.nav.syms.last_mut().unwrap() = sym;
+        self.nav.num_syms += 1;
+    }
+
+    // looks for two consecutive messages, 250 bits apart, starting with
+    // rotating preambles and a valid CRC. Returns the message start bit.
+    fn nav_search_sbas_msg(bits: &[u8]) -> Option<(usize, SyncState)> {
+        let preamble_at = |pos: usize| getbitu(&bits_to_bytes(&bits[pos..pos + 8]), 0, 8);
+
+        for i in SBAS_MARGIN..bits.len() - SBAS_MSG_LEN - 8 {
+            let p0 = preamble_at(i);
+            let p1 = preamble_at(i + SBAS_MSG_LEN);
+            for k in 0..SBAS_PREAMBLES.len() {
+                let (pre0, pre1) = (SBAS_PREAMBLES[k], SBAS_PREAMBLES[(k + 1) % 3]);
+                let sync = if p0 == pre0 && p1 == pre1 {
+                    SyncState::Normal
+                } else if p0 == pre0 ^ 0xff && p1 == pre1 ^ 0xff {
+                    SyncState::Reversed
+                } else {
+                    continue;
+                };
+                if Self::nav_test_sbas_crc(&bits[i..i + SBAS_MSG_LEN], &sync).is_some() {
+                    return Some((i, sync));
+                }
+            }
+        }
+        None
+    }
+
+    fn nav_test_sbas_crc(bits: &[u8], sync: &SyncState) -> Option<Vec<u8>> {
+        let rev = if *sync == SyncState::Normal { 0 } else { 1 };
+        let msg: Vec<_> = bits.iter().map(|v| v ^ rev).collect();
+        let crc = getbitu(&bits_to_bytes(&msg[226..]), 0, 24);
+
+        if crc24q_bits(&msg[..226]) == crc {
+            Some(bits_to_bytes(&msg))
+        } else {
+            None
+        }
+    }
+
+    fn nav_decode_sbas_msg(&mut self, buf: &[u8]) {
+        let mut st = self.pub_state.lock().unwrap();
+        let sbas = st.sbas.entry(self.sv.prn).or_default();
+        sbas.decode_msg(buf, self.sv, self.ts_sec);
+        log::info!("{}: SBAS: {}", self.sv, hex_str(buf));
+    }
+
     fn nav_decode_sbas(&mut self) {
-        log::warn!("{}: SBAS frame", self.sv);
+        if !self.nav_sync_symbol(2) {
+            if self.nav.bit_sync == 0 {
+                self.nav.nav_sync = 0;
+                self.nav.num_syms = 0;
+            }
+            return;
+        }
+        let sym = self.nav_mean_ip(2);
+        self.nav_add_soft_sym(sym);
+
+        if self.nav.nav_sync > 0 {
+            // nav_sync: symbol index of the next expected message
+            let end = self.nav.nav_sync + 2 * (SBAS_MSG_LEN + SBAS_MARGIN);
+            if self.nav.num_syms < end {
+                return;
+            }
+            // position of the message in the symbol history, with margin
+            let beg =
+                (self.nav.nav_sync + SBAS_NSYM).saturating_sub(self.nav.num_syms + 2 * SBAS_MARGIN);
+            let n = 2 * (SBAS_MSG_LEN + 2 * SBAS_MARGIN);
+            let bits = viterbi_decode(&self.nav.syms[beg..beg + n]);
+            let msg_bits = &bits[SBAS_MARGIN..SBAS_MARGIN + SBAS_MSG_LEN];
+            self.nav.nav_sync += 2 * SBAS_MSG_LEN;
+
+            match Self::nav_test_sbas_crc(msg_bits, &self.nav.sync_state) {
+                Some(buf) => {
+                    self.nav.count_parity_err = 0;
+                    self.nav_decode_sbas_msg(&buf);
+                }
+                None => {
+                    self.nav.count_parity_err += 1;
+                    log::warn!("{}: SBAS CRC ERROR", self.sv);
+                    if self.nav.count_parity_err >= SBAS_MAX_CRC_ERR {
+                        self.nav.nav_sync = 0;
+                        self.nav.sync_state = SyncState::Normal;
+                        self.nav.count_parity_err = 0;
+                    }
+                }
+            }
+            return;
+        }
+
+        self.nav.count_step += 1;
+        if self.nav.num_syms < SBAS_SEARCH_SYMS || self.nav.count_step < SBAS_SEARCH_STEP {
+            return;
+        }
+        self.nav.count_step = 0;
+
+        // the symbol pairing is unknown: try both phases
+        for phase in 0..2 {
+            let beg = SBAS_NSYM - SBAS_SEARCH_SYMS + phase;
+            let syms = &self.nav.syms[beg..beg + SBAS_SEARCH_SYMS - 2];
+            let bits = viterbi_decode(syms);
+
+            if let Some((i, sync)) = Self::nav_search_sbas_msg(&bits) {
+                let start_sym = self.nav.num_syms - (SBAS_NSYM - beg) + 2 * i;
+                log::info!(
+                    "{}: SBAS FRAME SYNC {sync:?}: ts={:.3}",
+                    self.sv,
+                    self.ts_sec
+                );
+                let buf = Self::nav_test_sbas_crc(&bits[i..i + SBAS_MSG_LEN], &sync).unwrap();
+                self.nav.sync_state = sync;
+                self.nav.count_parity_err = 0;
+                self.nav.nav_sync = start_sym + 2 * SBAS_MSG_LEN;
+                self.nav_decode_sbas_msg(&buf);
+                return;
+            }
+        }
+    }
+
+    // looks for a message starting with the CNAV preamble, for this PRN and
+    // with a valid CRC. Returns the message start bit.
+    fn nav_search_cnav_msg(&self, bits: &[u8]) -> Option<(usize, SyncState)> {
+        for i in SBAS_MARGIN..bits.len() - CNAV_MSG_LEN {
+            let sync = match getbitu(&bits_to_bytes(&bits[i..i + 8]), 0, 8) {
+                CNAV_PREAMBLE => SyncState::Normal,
+                p if p == CNAV_PREAMBLE ^ 0xff => SyncState::Reversed,
+                _ => continue,
+            };
+            if let Some(buf) = Self::nav_test_cnav_crc(&bits[i..i + CNAV_MSG_LEN], &sync)
+                && getbitu(&buf, 8, 6) == self.sv.prn as u32
+            {
+                return Some((i, sync));
+            }
+        }
+        None
+    }
+
+    fn nav_test_cnav_crc(bits: &[u8], sync: &SyncState) -> Option<Vec<u8>> {
+        let rev = if *sync == SyncState::Normal { 0 } else { 1 };
+        let msg: Vec<_> = bits.iter().map(|v| v ^ rev).collect();
+        let crc = getbitu(&bits_to_bytes(&msg[276..]), 0, 24);
+
+        if crc24q_bits(&msg[..276]) == crc {
+            Some(bits_to_bytes(&msg))
+        } else {
+            None
+        }
+    }
+
+    fn nav_decode_cnav_msg30(&mut self, buf: &[u8]) {
+        self.nav.eph.tgd = getbits(buf, 127, 13) as f64 * P2_35;
+
+        let pub_state = &mut self.pub_state.lock().unwrap();
+        pub_state.iono_utc.nav_decode_cnav_iono(buf, 192);
+        pub_state.ion_adj = true;
+        log::info!(
+            "{}: CNAV ionospheric parameters: {:?}",
+            self.sv,
+            pub_state.iono_utc.ion
+        );
+    }
+
+    fn nav_decode_cnav_msg33(&mut self, buf: &[u8]) {
+        let pub_state = &mut self.pub_state.lock().unwrap();
+        pub_state.iono_utc.nav_decode_cnav_utc(buf, 127);
+        pub_state.utc_adj = true;
+        log::info!(
+            "{}: CNAV UTC parameters: {:?}",
+            self.sv,
+            pub_state.iono_utc.utc
+        );
+    }
+
+    // CNAV message (IS-GPS-200, 30.3.3): types 10 and 11 hold the ephemeris,
+    // types 30 to 37 the clock followed by other data.
+    fn nav_decode_cnav_msg(&mut self, buf: &[u8], delay_sec: f64) {
+        let msg_type = getbitu(buf, 14, 6);
+        let tow = getbitu(buf, 20, 17) * 6;
+
+        match msg_type {
+            10 => self.nav.eph.nav_decode_cnav_msg10(buf, self.sv),
+            11 => self.nav.eph.nav_decode_cnav_msg11(buf, self.sv),
+            30..=37 => {
+                self.nav.eph.nav_decode_cnav_clock(buf, self.sv);
+                match msg_type {
+                    30 => self.nav_decode_cnav_msg30(buf),
+                    33 => self.nav_decode_cnav_msg33(buf),
+                    // 31, 32, 34..37: almanacs, EOP, differential
+                    // corrections, GGTO and text are not used
+                    _ => {}
+                }
+            }
+            _ => log::warn!("{}: invalid CNAV message type={msg_type}", self.sv),
+        }
+        log::warn!(
+            "{}: {}: type={msg_type} tow={tow}",
+            self.sv,
+            "cnav-msg".blue()
+        );
+
+        // the TOW count refers to the start of the next message
+        self.nav.eph.tow = tow % SECS_PER_WEEK;
+        self.nav_subframe_post();
+        self.nav.eph.ts_sec -= delay_sec;
+    }
+
+    // L2C CNAV: one rate 1/2 convolutionally coded symbol per 20 ms CM code
+    // period, 300-bit messages. The encoder runs continuously across
+    // messages.
+    fn nav_decode_cnav(&mut self) {
+        let c = *self.hist.corr_p.last().unwrap();
+        self.nav_add_soft_sym(c.re / c.norm());
+
+        if self.nav.nav_sync > 0 {
+            // nav_sync: symbol index of the next expected message
+            let end = self.nav.nav_sync + 2 * (CNAV_MSG_LEN + SBAS_MARGIN);
+            if self.nav.num_syms < end {
+                return;
+            }
+            let beg =
+                (self.nav.nav_sync + SBAS_NSYM).saturating_sub(self.nav.num_syms + 2 * SBAS_MARGIN);
+            let n = 2 * (CNAV_MSG_LEN + 2 * SBAS_MARGIN);
+            let bits = viterbi_decode(&self.nav.syms[beg..beg + n]);
+            let msg_bits = &bits[SBAS_MARGIN..SBAS_MARGIN + CNAV_MSG_LEN];
+            self.nav.nav_sync += 2 * CNAV_MSG_LEN;
+
+            match Self::nav_test_cnav_crc(msg_bits, &self.nav.sync_state) {
+                Some(buf) => {
+                    self.nav.count_parity_err = 0;
+                    let delay_sec = (2 * SBAS_MARGIN) as f64 * CNAV_SYM_SEC;
+                    self.nav_decode_cnav_msg(&buf, delay_sec);
+                }
+                None => {
+                    self.nav.count_parity_err += 1;
+                    log::warn!("{}: CNAV CRC ERROR", self.sv);
+                    if self.nav.count_parity_err >= SBAS_MAX_CRC_ERR {
+                        self.nav.nav_sync = 0;
+                        self.nav.sync_state = SyncState::Normal;
+                        self.nav.count_parity_err = 0;
+                    }
+                }
+            }
+            return;
+        }
+
+        self.nav.count_step += 1;
+        if self.nav.num_syms < SBAS_SEARCH_SYMS || self.nav.count_step < SBAS_SEARCH_STEP {
+            return;
+        }
+        self.nav.count_step = 0;
+
+        // the symbol pairing is unknown: try both phases
+        for phase in 0..2 {
+            let beg = SBAS_NSYM - SBAS_SEARCH_SYMS + phase;
+            let syms = &self.nav.syms[beg..beg + SBAS_SEARCH_SYMS - 2];
+            let bits = viterbi_decode(syms);
+
+            if let Some((i, sync)) = self.nav_search_cnav_msg(&bits) {
+                let start_sym = self.nav.num_syms - (SBAS_NSYM - beg) + 2 * i;
+                log::info!(
+                    "{}: CNAV FRAME SYNC {sync:?}: ts={:.3}",
+                    self.sv,
+                    self.ts_sec
+                );
+                let buf = Self::nav_test_cnav_crc(&bits[i..i + CNAV_MSG_LEN], &sync).unwrap();
+                self.nav.sync_state = sync;
+                self.nav.count_parity_err = 0;
+                self.nav.nav_sync = start_sym + 2 * CNAV_MSG_LEN;
+                let delay_sec = (self.nav.num_syms - self.nav.nav_sync) as f64 * CNAV_SYM_SEC;
+                self.nav_decode_cnav_msg(&buf, delay_sec);
+                return;
+            }
+        }
+    }
+
+    // LDPC decoding of a CNAV-2 subframe followed by the CRC-24Q check of its
+    // data bits. Returns the data bits packed in bytes.
+    fn nav_decode_cnav2_subframe(h: &Option<Vec<Vec<usize>>>, syms: &[f64]) -> Option<Vec<u8>> {
+        let bits = ldpc_decode(h.as_ref()?, syms, CNAV2_LDPC_ITER)?;
+        let data = &bits[..syms.len() / 2];
+        let crc = getbitu(&bits_to_bytes(&data[data.len() - 24..]), 0, 24);
+        if crc24q_bits(&data[..data.len() - 24]) != crc {
+            return None;
+        }
+        Some(bits_to_bytes(data))
+    }
+
+    fn nav_decode_cnav2_subframe3(&mut self, buf: &[u8]) {
+        let prn = getbitu(buf, 0, 8);
+        let page = getbitu(buf, 8, 6);
+
+        if page == 1 {
+            // UTC and ionospheric parameters (IS-GPS-800, 3.5.4.1)
+            let pub_state = &mut self.pub_state.lock().unwrap();
+            pub_state.iono_utc.nav_decode_cnav_utc(buf, 14);
+            pub_state.iono_utc.nav_decode_cnav_iono(buf, 112);
+            pub_state.utc_adj = true;
+            pub_state.ion_adj = true;
+            log::info!("{}: CNAV-2 iono/utc: {:?}", self.sv, pub_state.iono_utc);
+        }
+        log::warn!(
+            "{}: {}: prn={prn} page={page}",
+            self.sv,
+            "cnav2-subframe-3".blue()
+        );
+    }
+
+    // A CNAV-2 frame lasts one period of the L1CO overlay code: subframe 1
+    // (BCH coded TOI) is followed by subframes 2 and 3, LDPC coded and
+    // interleaved together.
+    fn nav_decode_cnav2(&mut self) {
+        if !self.is_secondary_code_end()
+            || self.num_trk_samples < CNAV2_FRAME_SYMS
+            || self.hist.corr_d.len() < CNAV2_FRAME_SYMS
+        {
+            return;
+        }
+        let len = self.hist.corr_d.len();
+        let syms: Vec<f64> = self.hist.corr_d[len - CNAV2_FRAME_SYMS..]
+            .iter()
+            .map(|c| c.re / c.norm())
+            .collect();
+
+        // the sign of the data component relative to the pilot is not
+        // resolved by the TOI code: try both, the subframe 2 CRC decides.
+        for pol in [1.0, -1.0] {
+            let syms: Vec<f64> = syms.iter().map(|s| s * pol).collect();
+            let (toi, corr) = bch_toi_decode(&syms[..CNAV2_TOI_SYMS]);
+
+            let mut data = vec![0.0; CNAV2_SF2_SYMS + CNAV2_SF3_SYMS];
+            for r in 0..CNAV2_ILV_ROWS {
+                for c in 0..CNAV2_ILV_COLS {
+                    data[r * CNAV2_ILV_COLS + c] = syms[CNAV2_TOI_SYMS + c * CNAV2_ILV_ROWS + r];
+                }
+            }
+            let Some(buf) =
+                Self::nav_decode_cnav2_subframe(&CNAV2_LDPC_SF2, &data[..CNAV2_SF2_SYMS])
+            else {
+                continue;
+            };
+            log::info!(
+                "{}: CNAV-2 FRAME: toi={toi} corr={corr:.1} pol={pol} ts={:.3}",
+                self.sv,
+                self.ts_sec
+            );
+
+            let itow = getbitu(&buf, 13, 8);
+            self.nav.eph.nav_decode_cnav2_subframe2(&buf, self.sv);
+            // ITOW/TOI refer to the start of the next frame
+            self.nav.eph.tow = (itow * 7200 + toi * 18) % SECS_PER_WEEK;
+            self.nav_subframe_post();
+
+            match Self::nav_decode_cnav2_subframe(&CNAV2_LDPC_SF3, &data[CNAV2_SF2_SYMS..]) {
+                Some(buf) => self.nav_decode_cnav2_subframe3(&buf),
+                None => log::warn!("{}: CNAV-2 subframe 3: DECODING ERROR", self.sv),
+            }
+            return;
+        }
+        self.nav.count_parity_err += 1;
+        log::warn!("{}: CNAV-2 subframe 2: DECODING ERROR", self.sv);
     }
 
     pub fn nav_decode(&mut self) {
         const PREAMBULE: [u8; 8] = [1, 0, 0, 0, 1, 0, 1, 1];
         let preambule = &PREAMBULE[0..];
 
+        match self.sig.as_str() {
+            "L1CA" => {}
+            "L1C" => {
+                self.nav_decode_cnav2();
+                return;
+            }
+            "L2CM" => {
+                self.nav_decode_cnav();
+                return;
+            }
+            // no I/NAV, D1/D2, GLONASS or L5 CNAV decoding yet
+            _ => return,
+        }
+
         if self.sv.prn >= 120 && self.sv.prn <= 158 {
             self.nav_decode_sbas();
             return;
@@ -415,3 +890,370 @@ impl Channel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{
+        CNAV_A_REF, P2_5, P2_9, P2_11, P2_19, P2_20, P2_21, P2_23, P2_24, P2_27, P2_29, P2_30,
+        P2_31, P2_32, P2_33, P2_34, P2_38, P2_43, P2_50, P2_55, SC2RAD,
+    };
+    use crate::fec::conv_encode;
+    use crate::state::GnssState;
+    use gnss_rs::constellation::Constellation;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::{Arc, Mutex};
+
+    // value of an n-bit field in units of lsb, off the grid by less than
+    // half a lsb so that the encoder has to round
+    fn gen_field(rng: &mut StdRng, n: u32, lsb: f64, signed: bool) -> f64 {
+        let k = if signed {
+            rng.gen_range(-(1i64 << (n - 1))..1i64 << (n - 1))
+        } else {
+            rng.gen_range(0..1i64 << n)
+        };
+        (k as f64 + rng.gen_range(-0.4..0.4)) * lsb
+    }
+
+    fn gen_int(rng: &mut StdRng, n: u32) -> u32 {
+        rng.gen_range(0..1u32 << n)
+    }
+
+    fn assert_lsb(name: &str, v: f64, res: f64, lsb: f64) {
+        assert!((v - res).abs() <= 0.5 * lsb, "{name}: {v} decoded as {res}");
+    }
+
+    fn gen_ephemeris(rng: &mut StdRng) -> Ephemeris {
+        let a = gen_field(rng, 32, P2_19, false);
+        Ephemeris {
+            week: 2048 + gen_int(rng, 10),
+            code: gen_int(rng, 2),
+            sva: gen_int(rng, 4),
+            svh: gen_int(rng, 6),
+            iodc: gen_int(rng, 10),
+            flag: gen_int(rng, 1),
+            tgd: gen_field(rng, 8, P2_31, true),
+            toc: gen_int(rng, 16) * 16,
+            f2: gen_field(rng, 8, P2_55, true),
+            f1: gen_field(rng, 16, P2_43, true),
+            f0: gen_field(rng, 22, P2_31, true),
+            iode: gen_int(rng, 8),
+            crs: gen_field(rng, 16, P2_5, true),
+            deln: gen_field(rng, 16, P2_43 * SC2RAD, true),
+            m0: gen_field(rng, 32, P2_31 * SC2RAD, true),
+            cuc: gen_field(rng, 16, P2_29, true),
+            ecc: gen_field(rng, 32, P2_33, false).abs(),
+            cus: gen_field(rng, 16, P2_29, true),
+            a: a * a,
+            toe: gen_int(rng, 16) * 16,
+            fit: gen_int(rng, 1),
+            cic: gen_field(rng, 16, P2_29, true),
+            omg0: gen_field(rng, 32, P2_31 * SC2RAD, true),
+            cis: gen_field(rng, 16, P2_29, true),
+            i0: gen_field(rng, 32, P2_31 * SC2RAD, true),
+            crc: gen_field(rng, 16, P2_5, true),
+            omg: gen_field(rng, 32, P2_31 * SC2RAD, true),
+            omg_dot: gen_field(rng, 24, P2_43 * SC2RAD, true),
+            i_dot: gen_field(rng, 14, P2_43 * SC2RAD, true),
+            ..Ephemeris::new(SV::new(Constellation::GPS, 5))
+        }
+    }
+
+    // the almanacs of SVs 1..32, of a common toa
+    fn gen_almanac(rng: &mut StdRng) -> Vec<Almanac> {
+        let (week, toas) = (2048 + gen_int(rng, 8), gen_int(rng, 8) * 4096);
+        (1..=32)
+            .map(|sat| {
+                let sqrt_a = gen_field(rng, 24, P2_11, false);
+                Almanac {
+                    sat,
+                    svh: gen_int(rng, 6),
+                    svconf: gen_int(rng, 4),
+                    a: sqrt_a * sqrt_a,
+                    e: gen_field(rng, 16, P2_21, false).abs(),
+                    i0: (0.3 + gen_field(rng, 16, P2_19, true)) * SC2RAD,
+                    omg0: gen_field(rng, 24, P2_23 * SC2RAD, true),
+                    omg: gen_field(rng, 24, P2_23 * SC2RAD, true),
+                    m0: gen_field(rng, 24, P2_23 * SC2RAD, true),
+                    omg_dot: gen_field(rng, 16, P2_38 * SC2RAD, true),
+                    week,
+                    toas,
+                    f0: gen_field(rng, 11, P2_20, true),
+                    f1: gen_field(rng, 11, P2_38, true),
+                }
+            })
+            .collect()
+    }
+
+    fn gen_iono_utc(rng: &mut StdRng) -> IonoUtc {
+        IonoUtc {
+            ion: [
+                gen_field(rng, 8, P2_30, true),
+                gen_field(rng, 8, P2_27, true),
+                gen_field(rng, 8, P2_24, true),
+                gen_field(rng, 8, P2_24, true),
+                gen_field(rng, 8, 2f64.powi(11), true),
+                gen_field(rng, 8, 2f64.powi(14), true),
+                gen_field(rng, 8, 2f64.powi(16), true),
+                gen_field(rng, 8, 2f64.powi(16), true),
+            ],
+            utc: [
+                gen_field(rng, 32, P2_30, true),
+                gen_field(rng, 24, P2_50, true),
+                (gen_int(rng, 8) * 4096) as f64,
+                gen_int(rng, 8) as f64,
+                gen_field(rng, 8, 1.0, true).round(),
+                gen_int(rng, 8) as f64,
+                rng.gen_range(1..=7) as f64,
+                gen_field(rng, 8, 1.0, true).round(),
+            ],
+        }
+    }
+
+    // checks the parity and the header of the bits of a subframe, returns
+    // its data as passed to the decoders
+    fn check_subframe(bits: &[u8], subframe_id: u32, tow: u32) -> Vec<u8> {
+        assert_eq!(bits.len(), LNAV_SUBFRAME_BITS);
+        // D29 and D30 of words 2 and 10
+        for pos in [58, 59, 298, 299] {
+            assert_eq!(bits[pos], 0, "subframe {subframe_id}: bit {pos}");
+        }
+        let mut buf = vec![0u8; LNAV_SUBFRAME_BITS.div_ceil(8)];
+        assert!(Channel::nav_test_lnav_parity(bits, &mut buf));
+        assert_eq!(getbitu(&buf, 0, 8), LNAV_PREAMBLE);
+        assert_eq!(
+            getbitu(&buf, 30, 17) * LNAV_SUBFRAME_SEC,
+            tow % SECS_PER_WEEK
+        );
+        assert_eq!(getbitu(&buf, 49, 3), subframe_id);
+        buf
+    }
+
+    #[test]
+    fn lnav_parity_errors() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let eph = gen_ephemeris(&mut rng);
+        let bits = nav_encode_lnav_subframe(2, 6000, 1, &eph, &[], &IonoUtc::default());
+        let mut buf = vec![0u8; LNAV_SUBFRAME_BITS.div_ceil(8)];
+        for pos in 0..LNAV_SUBFRAME_BITS {
+            let mut bad = bits.clone();
+            bad[pos] ^= 1;
+            assert!(!Channel::nav_test_lnav_parity(&bad, &mut buf), "bit {pos}");
+        }
+    }
+
+    // CNAV message type 10 of GPS 5 with its CRC, one bit per u8
+    fn gen_cnav_msg10() -> Vec<u8> {
+        let mut buf = vec![0u8; CNAV_MSG_LEN.div_ceil(8)];
+        setbitu(&mut buf, 0, 8, CNAV_PREAMBLE);
+        setbitu(&mut buf, 8, 6, 5);
+        setbitu(&mut buf, 14, 6, 10);
+        setbitu(&mut buf, 20, 17, 1000);
+        setbitu(&mut buf, 38, 13, 2190);
+        setbitu(&mut buf, 65, 5, 3);
+        setbitu(&mut buf, 70, 11, 100);
+        setbitu(&mut buf, 81, 26, -1000i32 as u32);
+        // m0: 33-bit two's complement
+        setbitu(&mut buf, 172, 1, 1);
+        setbitu(&mut buf, 173, 32, ((1i64 << 32) - 123_456_789) as u32);
+        setbitu(&mut buf, 205, 1, 0);
+        setbitu(&mut buf, 206, 32, 0x1234_5678);
+        let mut bits: Vec<u8> = (0..CNAV_MSG_LEN)
+            .map(|i| getbitu(&buf, i, 1) as u8)
+            .collect();
+        let crc = crc24q_bits(&bits[..276]);
+        for i in 0..24 {
+            bits[276 + i] = ((crc >> (23 - i)) & 1) as u8;
+        }
+        bits
+    }
+
+    #[test]
+    fn cnav_crc() {
+        let bits = gen_cnav_msg10();
+        let buf = Channel::nav_test_cnav_crc(&bits, &SyncState::Normal).unwrap();
+        assert_eq!(getbitu(&buf, 14, 6), 10);
+        let inv: Vec<u8> = bits.iter().map(|b| b ^ 1).collect();
+        assert_eq!(
+            Channel::nav_test_cnav_crc(&inv, &SyncState::Reversed),
+            Some(buf)
+        );
+        for pos in [0, 100, 275, 276, 299] {
+            let mut bad = bits.clone();
+            bad[pos] ^= 1;
+            assert!(Channel::nav_test_cnav_crc(&bad, &SyncState::Normal).is_none());
+        }
+    }
+
+    #[test]
+    fn cnav_msg10_round_trip() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let sv = SV::new(Constellation::GPS, 5);
+        let state = Arc::new(Mutex::new(GnssState::new()));
+        let mut ch = Channel::new("L1CA", sv, 2_046_000.0, 0.0, 0.0, state).unwrap();
+
+        // convolutionally encoded between random bits, a few symbols wrong
+        let mut bits: Vec<u8> = (0..40).map(|_| rng.gen_range(0..2)).collect();
+        bits.extend(gen_cnav_msg10());
+        bits.extend((0..40).map(|_| rng.gen_range(0..2)));
+        let mut syms: Vec<f64> = conv_encode(&bits)
+            .iter()
+            .map(|&b| 2.0 * b as f64 - 1.0)
+            .collect();
+        for i in [90, 201, 350, 511] {
+            syms[i] = -syms[i];
+        }
+
+        let dec = viterbi_decode(&syms);
+        let (i, sync) = ch.nav_search_cnav_msg(&dec).unwrap();
+        assert_eq!((i, &sync), (40, &SyncState::Normal));
+        let buf = Channel::nav_test_cnav_crc(&dec[i..i + CNAV_MSG_LEN], &sync).unwrap();
+        ch.nav_decode_cnav_msg(&buf, 0.0);
+
+        let eph = &ch.nav.eph;
+        assert_eq!((eph.week, eph.svh, eph.sva, eph.toe), (2190, 0, 3, 30000));
+        assert_eq!(eph.tow, 6000);
+        assert_eq!(eph.a, CNAV_A_REF - 1000.0 * P2_9);
+        assert_eq!(eph.m0, -123_456_789.0 * P2_32 * SC2RAD);
+        assert_eq!(eph.ecc, 0x1234_5678 as f64 * P2_34);
+    }
+
+    #[test]
+    fn lnav_missing_almanac() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let eph = gen_ephemeris(&mut rng);
+        let alm_array = gen_almanac(&mut rng);
+        for alm_array in [&[][..], &alm_array[..3]] {
+            for subframe_id in 4..=5 {
+                for page in 1..=25 {
+                    let bits = nav_encode_lnav_subframe(
+                        subframe_id,
+                        6000,
+                        page,
+                        &eph,
+                        alm_array,
+                        &IonoUtc::default(),
+                    );
+                    let buf = check_subframe(&bits, subframe_id, 6000);
+                    let svid = lnav_page_svid(subframe_id, page);
+                    if svid as usize > alm_array.len() && svid <= 32 {
+                        // dummy page: data ID 1, SV ID 0
+                        assert_eq!(getbitu(&buf, 60, 8), 0b01_000000);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lnav_ephemeris_round_trip() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..100 {
+            let eph = gen_ephemeris(&mut rng);
+            let tow = gen_int(&mut rng, 17) % (SECS_PER_WEEK / 6) * 6;
+            let mut res = Ephemeris::new(eph.sv);
+            for subframe_id in 1..=3 {
+                let bits =
+                    nav_encode_lnav_subframe(subframe_id, tow, 1, &eph, &[], &IonoUtc::default());
+                let buf = check_subframe(&bits, subframe_id, tow);
+                match subframe_id {
+                    1 => res.nav_decode_lnav_subframe1(&buf, eph.sv),
+                    2 => res.nav_decode_lnav_subframe2(&buf, eph.sv),
+                    _ => res.nav_decode_lnav_subframe3(&buf, eph.sv),
+                }
+            }
+            assert_eq!(
+                (res.week, res.code, res.sva, res.svh, res.iodc, res.flag),
+                (eph.week, eph.code, eph.sva, eph.svh, eph.iodc, eph.flag)
+            );
+            assert_eq!(
+                (res.iode, res.toc, res.toe, res.fit),
+                (eph.iode, eph.toc, eph.toe, eph.fit)
+            );
+            assert_lsb("tgd", eph.tgd, res.tgd, P2_31);
+            assert_lsb("f2", eph.f2, res.f2, P2_55);
+            assert_lsb("f1", eph.f1, res.f1, P2_43);
+            assert_lsb("f0", eph.f0, res.f0, P2_31);
+            assert_lsb("crs", eph.crs, res.crs, P2_5);
+            assert_lsb("deln", eph.deln, res.deln, P2_43 * SC2RAD);
+            assert_lsb("m0", eph.m0, res.m0, P2_31 * SC2RAD);
+            assert_lsb("cuc", eph.cuc, res.cuc, P2_29);
+            assert_lsb("ecc", eph.ecc, res.ecc, P2_33);
+            assert_lsb("cus", eph.cus, res.cus, P2_29);
+            assert_lsb("sqrt_a", eph.a.sqrt(), res.a.sqrt(), P2_19);
+            assert_lsb("cic", eph.cic, res.cic, P2_29);
+            assert_lsb("omg0", eph.omg0, res.omg0, P2_31 * SC2RAD);
+            assert_lsb("cis", eph.cis, res.cis, P2_29);
+            assert_lsb("i0", eph.i0, res.i0, P2_31 * SC2RAD);
+            assert_lsb("crc", eph.crc, res.crc, P2_5);
+            assert_lsb("omg", eph.omg, res.omg, P2_31 * SC2RAD);
+            assert_lsb("omg_dot", eph.omg_dot, res.omg_dot, P2_43 * SC2RAD);
+            assert_lsb("i_dot", eph.i_dot, res.i_dot, P2_43 * SC2RAD);
+        }
+    }
+
+    #[test]
+    fn lnav_almanac_iono_utc_round_trip() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..10 {
+            let eph = gen_ephemeris(&mut rng);
+            let alm_array = gen_almanac(&mut rng);
+            let iono_utc = gen_iono_utc(&mut rng);
+            let mut res_array = vec![Almanac::default(); 32];
+            let mut res_iono_utc = IonoUtc::default();
+
+            for subframe_id in 4..=5 {
+                for page in 1..=25 {
+                    let tow = page * 30;
+                    let bits = nav_encode_lnav_subframe(
+                        subframe_id,
+                        tow,
+                        page,
+                        &eph,
+                        &alm_array,
+                        &iono_utc,
+                    );
+                    let buf = check_subframe(&bits, subframe_id, tow);
+                    let svid = getbitu(&buf, 62, 6);
+                    assert_eq!(getbitu(&buf, 60, 2), 1);
+                    assert_eq!(svid, lnav_page_svid(subframe_id, page));
+                    match svid {
+                        1..=32 => res_array[svid as usize - 1].nav_decode_alm(&buf, svid),
+                        51 => nav_decode_alm_health(&buf, &mut res_array),
+                        56 => res_iono_utc.nav_decode_iono_utc(&buf),
+                        63 => nav_decode_svconf_health(&buf, &mut res_array),
+                        _ => {}
+                    }
+                }
+            }
+
+            for (alm, res) in alm_array.iter().zip(res_array.iter()) {
+                assert_eq!(
+                    (res.sat, res.svh, res.svconf, res.week, res.toas),
+                    (alm.sat, alm.svh, alm.svconf, alm.week, alm.toas)
+                );
+                assert_lsb("e", alm.e, res.e, P2_21);
+                assert_lsb("i0", alm.i0, res.i0, P2_19 * SC2RAD);
+                assert_lsb("omg_dot", alm.omg_dot, res.omg_dot, P2_38 * SC2RAD);
+                assert_lsb("sqrt_a", alm.a.sqrt(), res.a.sqrt(), P2_11);
+                assert_lsb("omg0", alm.omg0, res.omg0, P2_23 * SC2RAD);
+                assert_lsb("omg", alm.omg, res.omg, P2_23 * SC2RAD);
+                assert_lsb("m0", alm.m0, res.m0, P2_23 * SC2RAD);
+                assert_lsb("f0", alm.f0, res.f0, P2_20);
+                assert_lsb("f1", alm.f1, res.f1, P2_38);
+            }
+
+            let lsb_ion = [
+                P2_30, P2_27, P2_24, P2_24, 2048.0, 16384.0, 65536.0, 65536.0,
+            ];
+            for i in 0..8 {
+                assert_lsb("ion", iono_utc.ion[i], res_iono_utc.ion[i], lsb_ion[i]);
+            }
+            let lsb_utc = [P2_30, P2_50, 4096.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+            for i in 0..8 {
+                assert_lsb("utc", iono_utc.utc[i], res_iono_utc.utc[i], lsb_utc[i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use core::sync::atomic::Ordering;
+use std::error::Error;
+use std::io::{self, Read};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rustfft::num_complex::Complex64;
+
+use crate::queue::{QUEUE_SEC, QueueReader, SampleQueue};
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording};
+
+const TCP_PREFIX: &str = "tcp://";
+const UDP_PREFIX: &str = "udp://";
+const UDP_SEQ_SUFFIX: &str = "?seq";
+const READ_TIMEOUT: Duration = Duration::from_millis(100); // exit_req/stop checks
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const READ_SIZE: usize = 64 * 1024;
+const SEQ_SIZE: usize = 8;
+
+// true for the urls of raw IQ streams, as opposed to an rtl_tcp host:port
+pub fn is_net_source(hostname: &str) -> bool {
+    hostname.starts_with(TCP_PREFIX) || hostname.starts_with(UDP_PREFIX)
+}
+
+enum NetProto {
+    Tcp,
+    Udp { seq: bool },
+}
+
+// "tcp://host:port", "udp://addr:port" or "udp://:port" to listen on all
+// addresses. "?seq" when each datagram starts with a 64-bit little-endian
+// sequence number, e.g. the "64-bit sequence number" header of the GNU Radio
+// UDP sink.
+fn parse_url(url: &str) -> Result<(NetProto, String), Box<dyn Error>> {
+    if let Some(addr) = url.strip_prefix(TCP_PREFIX) {
+        return Ok((NetProto::Tcp, addr.to_string()));
+    }
+    let addr = url
+        .strip_prefix(UDP_PREFIX)
+        .ok_or(format!("Failed to parse {url}: tcp:// or udp:// expected"))?;
+    let (addr, seq) = match addr.strip_suffix(UDP_SEQ_SUFFIX) {
+        Some(addr) => (addr, true),
+        None => (addr, false),
+    };
+    let addr = if addr.starts_with(':') {
+        format!("0.0.0.0{addr}")
+    } else {
+        addr.to_string()
+    };
+    Ok((NetProto::Udp { seq }, addr))
+}
+
+// Decodes the bytes received, keeping the tail of a sample split across two
+// reads for the next one.
+struct Decoder {
+    file_type: IQFileType,
+    sample_size: usize,
+    carry: Vec<u8>,
+}
+
+impl Decoder {
+    fn new(file_type: &IQFileType) -> Self {
+        Self {
+            file_type: file_type.clone(),
+            sample_size: usize::max(1, IQRecording::get_sample_size_bits(file_type) / 8),
+            carry: vec![],
+        }
+    }
+
+    fn decode(&mut self, buf: &[u8]) -> Vec<Complex64> {
+        self.carry.extend_from_slice(buf);
+        let len = self.carry.len() - self.carry.len() % self.sample_size;
+        let v = IQRecording::decode(&self.carry[..len], &self.file_type);
+        self.carry.drain(..len);
+        v
+    }
+
+    // samples held in num_bytes, e.g. in the datagrams that were lost
+    fn get_num_samples(&self, num_bytes: usize) -> usize {
+        num_bytes * 8 / IQRecording::get_sample_size_bits(&self.file_type)
+    }
+}
+
+// samples lost when datagram cur arrives while next was expected, assuming
+// the datagrams of a source are all the same size
+fn get_seq_num_lost(next: u64, cur: u64, datagram_samples: usize) -> usize {
+    cur.saturating_sub(next) as usize * datagram_samples
+}
+
+// Raw interleaved IQ samples over plain TCP or UDP, e.g. from a GNU Radio
+// TCP/UDP sink or a remote front end, of the type given by -t. A TCP source
+// that goes away is reconnected to, the samples missed in between are
+// reported as a gap. Over UDP, gaps are detected with the sequence numbers
+// of the datagrams when the source sends them.
+pub struct IQNetSource {
+    reader: QueueReader,
+    read_th: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    url: String,
+    ts: Instant,
+}
+
+impl Drop for IQNetSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(th) = self.read_th.take() {
+            let _ = th.join();
+        }
+        let stats = self.reader.get_stats();
+        log::warn!(
+            "{}: {stats} wait={} -- {:.2} sec. rate={:.1}/sec",
+            self.url,
+            self.reader.get_num_wait(),
+            self.ts.elapsed().as_secs_f64(),
+            stats.num_samples as f64 / self.ts.elapsed().as_secs_f64()
+        );
+    }
+}
+
+impl IQReader for IQNetSource {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        self.reader.get_iq_data(off_samples, num_samples)
+    }
+
+    fn take_lost_samples(&mut self) -> usize {
+        self.reader.take_lost_samples()
+    }
+}
+
+fn tcp_connect(addr: &str) -> io::Result<TcpStream> {
+    let socket = TcpStream::connect(addr)?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    Ok(socket)
+}
+
+fn tcp_read_loop(
+    mut socket: TcpStream,
+    addr: String,
+    mut decoder: Decoder,
+    queue: Arc<SampleQueue>,
+    fs: f64,
+    exit_req: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut buf = vec![0u8; READ_SIZE];
+    while !exit_req.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+        let err = match socket.read(&mut buf) {
+            Ok(0) => io::Error::from(io::ErrorKind::UnexpectedEof),
+            Ok(n) => {
+                queue.push(&decoder.decode(&buf[..n]));
+                continue;
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(e) => e,
+        };
+        log::warn!("tcp://{addr}: connection lost: {err}");
+        let ts = Instant::now();
+        socket = loop {
+            if exit_req.load(Ordering::SeqCst) || stop.load(Ordering::SeqCst) {
+                queue.close();
+                return;
+            }
+            thread::sleep(RECONNECT_DELAY);
+            match tcp_connect(&addr) {
+                Ok(socket) => break socket,
+                Err(e) => log::warn!("tcp://{addr}: failed to reconnect: {e}"),
+            }
+        };
+        // the source doesn't say what we missed: go by the time it took
+        let num_lost = (ts.elapsed().as_secs_f64() * fs) as usize;
+        log::warn!(
+            "tcp://{addr}: reconnected after {:.1} sec, ~{num_lost} samples lost",
+            ts.elapsed().as_secs_f64()
+        );
+        decoder.carry.clear();
+        queue.push_gap(num_lost);
+    }
+    queue.close();
+}
+
+fn udp_read_loop(
+    socket: UdpSocket,
+    addr: String,
+    seq: bool,
+    mut decoder: Decoder,
+    queue: Arc<SampleQueue>,
+    exit_req: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut buf = vec![0u8; READ_SIZE];
+    let mut next_seq: Option<u64> = None;
+    while !exit_req.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(e) => {
+                log::warn!("udp://{addr}: failed to receive: {e}");
+                break;
+            }
+        };
+        let mut data = &buf[..n];
+        if seq {
+            if n < SEQ_SIZE {
+                log::warn!("udp://{addr}: datagram of {n} bytes without sequence number");
+                continue;
+            }
+            let cur = u64::from_le_bytes(data[..SEQ_SIZE].try_into().unwrap());
+            data = &data[SEQ_SIZE..];
+            if let Some(next) = next_seq {
+                let num_lost = get_seq_num_lost(next, cur, decoder.get_num_samples(data.len()));
+                if num_lost > 0 {
+                    log::warn!(
+                        "udp://{addr}: {} datagrams lost, seq {next}..{cur}",
+                        cur - next
+                    );
+                    queue.push_gap(num_lost);
+                } else if cur < next {
+                    log::warn!("udp://{addr}: seq {cur} after {next}: source restarted?");
+                }
+            }
+            next_seq = Some(cur + 1);
+        }
+        // each datagram carries whole samples: nothing to carry over
+        decoder.carry.clear();
+        queue.push(&decoder.decode(data));
+    }
+    queue.close();
+}
+
+impl IQNetSource {
+    pub fn new(
+        url: &str,
+        file_type: &IQFileType,
+        fs: f64,
+        exit_req: Arc<AtomicBool>,
+    ) -> Result<IQNetSource, Box<dyn Error>> {
+        let (proto, addr) = parse_url(url)?;
+        let decoder = Decoder::new(file_type);
+        let queue = Arc::new(SampleQueue::new((QUEUE_SEC * fs) as usize));
+        let th_queue = queue.clone();
+        let th_exit_req = exit_req.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let th_stop = stop.clone();
+
+        let th = match proto {
+            NetProto::Tcp => {
+                let socket = tcp_connect(&addr)?;
+                log::warn!("tcp://{addr}: connected -- {file_type}");
+                thread::spawn(move || {
+                    tcp_read_loop(socket, addr, decoder, th_queue, fs, th_exit_req, th_stop)
+                })
+            }
+            NetProto::Udp { seq } => {
+                let socket = UdpSocket::bind(&addr)?;
+                socket.set_read_timeout(Some(READ_TIMEOUT))?;
+                log::warn!("udp://{addr}: listening -- {file_type} seq={seq}");
+                thread::spawn(move || {
+                    udp_read_loop(socket, addr, seq, decoder, th_queue, th_exit_req, th_stop)
+                })
+            }
+        };
+
+        // no stall timeout: reconnecting to a source, or waiting for the
+        // first datagram, can take a while. Ctrl-C still gets us out.
+        Ok(IQNetSource {
+            reader: QueueReader::new(queue, exit_req, None),
+            read_th: Some(th),
+            stop,
+            url: url.to_string(),
+            ts: Instant::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_urls() {
+        let (proto, addr) = parse_url("tcp://192.168.1.10:1234").unwrap();
+        assert!(matches!(proto, NetProto::Tcp));
+        assert_eq!(addr, "192.168.1.10:1234");
+
+        let (proto, addr) = parse_url("udp://127.0.0.1:5000").unwrap();
+        assert!(matches!(proto, NetProto::Udp { seq: false }));
+        assert_eq!(addr, "127.0.0.1:5000");
+
+        let (proto, addr) = parse_url("udp://:5000?seq").unwrap();
+        assert!(matches!(proto, NetProto::Udp { seq: true }));
+        assert_eq!(addr, "0.0.0.0:5000");
+
+        assert!(parse_url("localhost:1234").is_err());
+        assert!(is_net_source("udp://:5000"));
+        assert!(!is_net_source("localhost:1234"));
+    }
+
+    #[test]
+    fn seq_num_lost() {
+        // 1024 bytes of 2xi16: 256 samples per datagram
+        let decoder = Decoder::new(&IQFileType::TypePairInt16);
+        let n = decoder.get_num_samples(1024);
+        assert_eq!(n, 256);
+        assert_eq!(get_seq_num_lost(7, 7, n), 0);
+        assert_eq!(get_seq_num_lost(7, 10, n), 3 * 256);
+        // a source that restarts counting doesn't make a gap
+        assert_eq!(get_seq_num_lost(7, 0, n), 0);
+    }
+}
@@ -1,38 +1,132 @@
 use core::sync::atomic::Ordering;
 use rustfft::num_complex::Complex64;
-use std::collections::VecDeque;
 use std::io::Read;
 use std::io::Write;
 use std::net::TcpStream;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::sync::Mutex;
 use std::sync::atomic::AtomicBool;
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::code::Code;
+use crate::queue::{QUEUE_SEC, QueueReader, STALL_TIMEOUT, SampleQueue};
 use crate::receiver::IQReader;
 
+const RTL_TCP_MAGIC: &[u8; 4] = b"RTL0";
+const READ_TIMEOUT: Duration = Duration::from_millis(100); // exit_req checks
+const READ_SIZE: usize = 2036 * 2;
+
+// rtl_tcp commands: 1 byte of command, 4 bytes of parameter, big-endian
+const CMD_SET_FREQ: u8 = 0x01;
+const CMD_SET_SAMPLE_RATE: u8 = 0x02;
+const CMD_SET_GAIN_MODE: u8 = 0x03;
+const CMD_SET_GAIN: u8 = 0x04;
+const CMD_SET_FREQ_CORRECTION: u8 = 0x05;
+const CMD_SET_IF_GAIN: u8 = 0x06;
+const CMD_SET_TEST_MODE: u8 = 0x07;
+const CMD_SET_AGC_MODE: u8 = 0x08;
+const CMD_SET_DIRECT_SAMPLING: u8 = 0x09;
+const CMD_SET_OFFSET_TUNING: u8 = 0x0a;
+const CMD_SET_GAIN_BY_INDEX: u8 = 0x0d;
+const CMD_SET_BIAS_TEE: u8 = 0x0e;
+
+// tuner gains in tenths of dB, in the order of the gain indices of librtlsdr
+const E4000_GAINS: &[i32] = &[
+    -10, 15, 40, 65, 90, 115, 140, 165, 190, 215, 240, 290, 340, 420,
+];
+const FC0012_GAINS: &[i32] = &[-99, -40, 71, 179, 192];
+const FC0013_GAINS: &[i32] = &[
+    -99, -73, -65, -63, -60, -58, -54, 58, 61, 63, 65, 67, 68, 70, 71, 179, 181, 182, 184, 186,
+    188, 191, 197,
+];
+const R820T_GAINS: &[i32] = &[
+    0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328, 338, 364, 372,
+    386, 402, 421, 434, 439, 445, 480, 496,
+];
+
+// IF gain of one stage of the E4000 tuner, e.g. "1:6" for 6 dB on stage 1
+#[derive(Clone)]
+pub struct IfGain {
+    pub stage: u16,
+    pub gain_db: f64,
+}
+
+impl FromStr for IfGain {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(input: &str) -> Result<IfGain, Self::Err> {
+        let (stage, gain_db) = input
+            .split_once(':')
+            .ok_or(format!("Failed to parse {input}: stage:gain_db expected"))?;
+        Ok(IfGain {
+            stage: stage.parse()?,
+            gain_db: gain_db.parse()?,
+        })
+    }
+}
+
+// Settings of the dongle behind rtl_tcp, on top of frequency and sample rate
+#[derive(Clone, Default)]
+pub struct RtlSdrTcpConfig {
+    pub gain_db: Option<f64>,  // manual tuner gain, automatic otherwise
+    pub gain_idx: Option<u32>, // same, as an index in the gains of the tuner
+    pub ppm: i32,              // frequency correction of the dongle crystal
+    pub if_gains: Vec<IfGain>,
+    pub offset_tuning: bool,
+    pub direct_sampling: u32, // 0: off, 1: I ADC, 2: Q ADC
+    pub test_mode: bool,
+}
+
+// The 12 bytes rtl_tcp sends first
+struct DongleInfo {
+    tuner_type: u32,
+    num_gains: u32,
+}
+
+impl DongleInfo {
+    fn get_tuner_name(&self) -> &'static str {
+        match self.tuner_type {
+            1 => "E4000",
+            2 => "FC0012",
+            3 => "FC0013",
+            4 => "FC2580",
+            5 => "R820T",
+            6 => "R828D",
+            _ => "unknown",
+        }
+    }
+
+    fn get_gains(&self) -> &'static [i32] {
+        match self.tuner_type {
+            1 => E4000_GAINS,
+            2 => FC0012_GAINS,
+            3 => FC0013_GAINS,
+            5 | 6 => R820T_GAINS,
+            _ => &[],
+        }
+    }
+}
+
 pub struct RtlSdrTcp {
-    iq_deque: Arc<Mutex<VecDeque<Vec<Complex64>>>>,
-    num_samples_total: Arc<Mutex<usize>>,
-    num_samples: Arc<Mutex<usize>>,
-    num_sleep: u64,
+    reader: QueueReader,
     read_th: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
     ts: Instant,
+    gain_db: Option<f64>,
 }
 
 impl Drop for RtlSdrTcp {
     fn drop(&mut self) {
-        let tot = *self.num_samples_total.lock().unwrap();
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(th) = self.read_th.take() {
+            let _ = th.join();
+        }
+        let stats = self.reader.get_stats();
         log::warn!(
-            "num_samples={}/{} sleep={} -- {:.2} sec. rate={:.1}/sec",
-            self.num_samples.lock().unwrap(),
-            tot,
-            self.num_sleep,
+            "rtl_tcp: {stats} wait={} -- {:.2} sec. rate={:.1}/sec",
+            self.reader.get_num_wait(),
             self.ts.elapsed().as_secs_f64(),
-            tot as f64 / self.ts.elapsed().as_secs_f64()
+            stats.num_samples as f64 / self.ts.elapsed().as_secs_f64()
         );
     }
 }
@@ -40,40 +134,18 @@ impl Drop for RtlSdrTcp {
 impl IQReader for RtlSdrTcp {
     fn get_iq_data(
         &mut self,
-        _off_samples: usize,
+        off_samples: usize,
         num_samples: usize,
     ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
-        loop {
-            if *self.num_samples.lock().unwrap() >= num_samples {
-                break;
-            }
-            thread::sleep(std::time::Duration::from_millis(1));
-            self.num_sleep += 1;
-        }
-        let mut vec = vec![];
-        let mut iq_deq = self.iq_deque.lock().unwrap();
-
-        let v_front = iq_deq.front_mut().unwrap();
-        let n = usize::min(num_samples, v_front.len());
-        for v in v_front.iter().take(n) {
-            vec.push(*v);
-        }
-        let _ = v_front.drain(0..n);
-        if v_front.is_empty() {
-            let _ = iq_deq.pop_front();
-        }
-
-        if n < num_samples {
-            let v_front = iq_deq.front_mut().unwrap();
-            for i in n..num_samples {
-                vec.push(v_front[i - n]);
-            }
-            let _ = v_front.drain(0..num_samples - n);
-        }
+        self.reader.get_iq_data(off_samples, num_samples)
+    }
 
-        *self.num_samples.lock().unwrap() -= num_samples;
+    fn get_gain_db(&self) -> Option<f64> {
+        self.gain_db
+    }
 
-        Ok(vec)
+    fn take_lost_samples(&mut self) -> usize {
+        self.reader.take_lost_samples()
     }
 }
 
@@ -85,63 +157,139 @@ fn rtl_sdr_send_cmd(socket: &mut TcpStream, cmd: u8, param: u32) -> std::io::Res
     Ok(())
 }
 
+fn rtl_sdr_read_dongle_info(socket: &mut TcpStream) -> std::io::Result<DongleInfo> {
+    let mut header = [0u8; 12];
+    socket.read_exact(&mut header)?;
+    if &header[0..4] != RTL_TCP_MAGIC {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not an rtl_tcp server",
+        ));
+    }
+    Ok(DongleInfo {
+        tuner_type: u32::from_be_bytes(header[4..8].try_into().unwrap()),
+        num_gains: u32::from_be_bytes(header[8..12].try_into().unwrap()),
+    })
+}
+
 impl RtlSdrTcp {
     pub fn new(
         hostname: &str,
         exit_req: Arc<AtomicBool>,
-        sig: &str,
+        fc: f64,
         fs: f64,
+        cfg: &RtlSdrTcpConfig,
     ) -> std::io::Result<RtlSdrTcp> {
         let mut socket = TcpStream::connect(hostname)?;
 
+        let info = rtl_sdr_read_dongle_info(&mut socket)?;
+        log::warn!(
+            "rtl_tcp: tuner {} with {} gains",
+            info.get_tuner_name(),
+            info.num_gains
+        );
+
+        let queue = Arc::new(SampleQueue::new((QUEUE_SEC * fs) as usize));
         let mut m = RtlSdrTcp {
-            iq_deque: Arc::new(Mutex::new(VecDeque::new())),
-            num_samples_total: Arc::new(Mutex::new(0)),
-            num_samples: Arc::new(Mutex::new(0)),
-            num_sleep: 0,
+            reader: QueueReader::new(queue.clone(), exit_req.clone(), Some(STALL_TIMEOUT)),
             read_th: None,
+            stop: Arc::new(AtomicBool::new(false)),
             ts: Instant::now(),
+            gain_db: None,
         };
 
-        let iq_deq = m.iq_deque.clone();
-        let num_samples_total = m.num_samples_total.clone();
-        let num_samples = m.num_samples.clone();
-
-        // set bias-t
-        rtl_sdr_send_cmd(&mut socket, 0xe, 1)?;
-        // set automatic gain control
-        rtl_sdr_send_cmd(&mut socket, 0x8, 1)?;
-        // set center frequency
-        rtl_sdr_send_cmd(&mut socket, 0x1, Code::get_code_freq(sig) as u32)?;
-        // set sample rate
-        rtl_sdr_send_cmd(&mut socket, 0x2, fs as u32)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_BIAS_TEE, 1)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_SAMPLE_RATE, fs as u32)?;
+        // the correction applies to both the tuning and the sampling: for
+        // GPS L1, 50 ppm would otherwise show up as ~78 KHz of doppler
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_FREQ_CORRECTION, cfg.ppm as u32)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_OFFSET_TUNING, cfg.offset_tuning as u32)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_DIRECT_SAMPLING, cfg.direct_sampling)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_FREQ, fc as u32)?;
+        rtl_sdr_send_cmd(&mut socket, CMD_SET_TEST_MODE, cfg.test_mode as u32)?;
 
-        // set tuner gain
-        //rtl_sdr_send_cmd(&mut socket, 0x4, 480)?;
+        // manual tuner gain, or automatic gain control of both the tuner and
+        // the rtl2832
+        if let Some(idx) = cfg.gain_idx {
+            if idx >= info.num_gains {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("gain index {idx} out of range: {} gains", info.num_gains),
+                ));
+            }
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_GAIN_MODE, 1)?;
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_GAIN_BY_INDEX, idx)?;
+            m.gain_db = info.get_gains().get(idx as usize).map(|g| *g as f64 / 10.0);
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_AGC_MODE, 0)?;
+        } else if let Some(gain_db) = cfg.gain_db {
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_GAIN_MODE, 1)?;
+            rtl_sdr_send_cmd(
+                &mut socket,
+                CMD_SET_GAIN,
+                (gain_db * 10.0).round() as i32 as u32,
+            )?;
+            m.gain_db = Some(gain_db);
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_AGC_MODE, 0)?;
+        } else {
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_GAIN_MODE, 0)?;
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_AGC_MODE, 1)?;
+        }
+        for if_gain in &cfg.if_gains {
+            let gain = (if_gain.gain_db * 10.0).round() as i16 as u16;
+            let param = (if_gain.stage as u32) << 16 | gain as u32;
+            rtl_sdr_send_cmd(&mut socket, CMD_SET_IF_GAIN, param)?;
+        }
+        log::warn!(
+            "rtl_tcp: gain={} ppm={} offset_tuning={} direct_sampling={} test_mode={}",
+            m.gain_db
+                .map_or("auto".to_string(), |g| format!("{g:.1} dB")),
+            cfg.ppm,
+            cfg.offset_tuning,
+            cfg.direct_sampling,
+            cfg.test_mode
+        );
 
+        // the read thread checks for exit requests between reads
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+        let stop = m.stop.clone();
         let th = thread::spawn(move || {
-            loop {
-                let mut data = [0u8; 2036 * 2];
-                let mut v = vec![Complex64::default(); data.len()];
-                let res = socket.read_exact(&mut data);
-                if res.is_err() {
-                    log::warn!("Failed to read from rtl-sdr");
-                    exit_req.store(true, Ordering::SeqCst);
-                    break;
-                }
-
-                for i in 0..data.len() / 2 {
-                    let re = (data[2 * i] as f64 - 127.3) / 128.0;
-                    let im = (data[2 * i + 1] as f64 - 127.3) / 128.0;
-
-                    v[i] = Complex64 { re, im };
+            let mut data = [0u8; READ_SIZE];
+            let mut len = 0; // a sample split across reads
+            while !exit_req.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+                match socket.read(&mut data[len..]) {
+                    Ok(0) => {
+                        log::warn!("rtl_tcp: connection closed");
+                        exit_req.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                    Ok(n) => len += n,
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to read from rtl-sdr: {e}");
+                        exit_req.store(true, Ordering::SeqCst);
+                        break;
+                    }
                 }
 
-                let n = v.len();
-                iq_deq.lock().unwrap().push_back(v);
-                *num_samples.lock().unwrap() += n;
-                *num_samples_total.lock().unwrap() += n;
+                let v: Vec<Complex64> = data[..len / 2 * 2]
+                    .chunks_exact(2)
+                    .map(|iq| Complex64 {
+                        re: (iq[0] as f64 - 127.3) / 128.0,
+                        im: (iq[1] as f64 - 127.3) / 128.0,
+                    })
+                    .collect();
+                queue.push(&v);
+                data.copy_within(len / 2 * 2..len, 0);
+                len %= 2;
             }
+            queue.close();
         });
         m.read_th = Some(th);
 
@@ -0,0 +1,286 @@
+use rustfft::num_complex::Complex64;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::receiver::IQReader;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100); // exit_req checks
+pub const QUEUE_SEC: f64 = 1.0; // samples buffered by live sources before dropping any
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// samples, and the number of samples lost right before them
+type QueueSamples = (Vec<Complex64>, usize);
+
+#[derive(Clone, Default)]
+pub struct QueueStats {
+    pub num_samples: usize, // pushed by the source, dropped ones included
+    pub num_dropped: usize,
+    pub num_overflows: usize,
+    pub num_gaps: usize, // reported by the source, e.g. lost UDP datagrams
+    pub max_fill: usize,
+    pub capacity: usize,
+}
+
+impl fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "num_samples={} dropped={} overflows={} gaps={} max_fill={:.0}%",
+            self.num_samples,
+            self.num_dropped,
+            self.num_overflows,
+            self.num_gaps,
+            100.0 * self.max_fill as f64 / self.capacity as f64
+        )
+    }
+}
+
+struct QueueInner {
+    samples: VecDeque<Complex64>,
+    num_lost: usize, // dropped right before the samples in the queue
+    closed: bool,
+    stats: QueueStats,
+}
+
+// Bounded queue between the thread of a live source and the receiver.
+// When the receiver falls behind and the queue gets full, all the samples
+// it holds are dropped: the gap then sits right before the next samples
+// read, and is reported along with them.
+pub struct SampleQueue {
+    inner: Mutex<QueueInner>,
+    cond: Condvar,
+    capacity: usize,
+}
+
+impl SampleQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(QueueInner {
+                samples: VecDeque::with_capacity(capacity),
+                num_lost: 0,
+                closed: false,
+                stats: QueueStats {
+                    capacity,
+                    ..Default::default()
+                },
+            }),
+            cond: Condvar::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, samples: &[Complex64]) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.stats.num_samples += samples.len();
+        if inner.samples.len() + samples.len() > self.capacity {
+            let n = inner.samples.len() + samples.len();
+            inner.samples.clear();
+            inner.num_lost += n;
+            inner.stats.num_dropped += n;
+            inner.stats.num_overflows += 1;
+            return;
+        }
+        inner.samples.extend(samples);
+        inner.stats.max_fill = usize::max(inner.stats.max_fill, inner.samples.len());
+        self.cond.notify_one();
+    }
+
+    // Samples lost before reaching the queue, e.g. while a TCP source
+    // reconnects. As for an overflow, the samples held are dropped so that
+    // the gap sits right before the next samples read.
+    pub fn push_gap(&self, num_lost: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        let n = inner.samples.len();
+        inner.samples.clear();
+        inner.num_lost += n + num_lost;
+        inner.stats.num_dropped += n;
+        inner.stats.num_gaps += 1;
+    }
+
+    // the source is gone: readers get an error once the queue is drained
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.cond.notify_all();
+    }
+
+    // Waits up to timeout for num_samples: None if they are not there by then.
+    pub fn pop_timeout(
+        &self,
+        num_samples: usize,
+        timeout: Duration,
+    ) -> Result<Option<QueueSamples>, Box<dyn std::error::Error>> {
+        let inner = self.inner.lock().unwrap();
+        let (mut inner, _) = self
+            .cond
+            .wait_timeout_while(inner, timeout, |inner| {
+                inner.samples.len() < num_samples && !inner.closed
+            })
+            .unwrap();
+        if inner.samples.len() < num_samples {
+            if inner.closed {
+                return Err("live source closed".into());
+            }
+            return Ok(None);
+        }
+        let samples = inner.samples.drain(..num_samples).collect();
+        Ok(Some((samples, std::mem::take(&mut inner.num_lost))))
+    }
+
+    pub fn get_stats(&self) -> QueueStats {
+        self.inner.lock().unwrap().stats.clone()
+    }
+}
+
+// The reading side of a SampleQueue, shared by the live sources. A read
+// fails when exit is requested, when the source closes the queue, or when
+// no samples came in for timeout, e.g. a stalled dongle: it doesn't wait
+// forever unless timeout is None.
+pub struct QueueReader {
+    queue: Arc<SampleQueue>,
+    exit_req: Arc<AtomicBool>,
+    timeout: Option<Duration>,
+    num_lost: usize, // before the samples of the last read
+    num_wait: usize,
+}
+
+impl QueueReader {
+    pub fn new(
+        queue: Arc<SampleQueue>,
+        exit_req: Arc<AtomicBool>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            queue,
+            exit_req,
+            timeout,
+            num_lost: 0,
+            num_wait: 0,
+        }
+    }
+
+    pub fn get_stats(&self) -> QueueStats {
+        self.queue.get_stats()
+    }
+
+    // reads that had to wait for the source
+    pub fn get_num_wait(&self) -> usize {
+        self.num_wait
+    }
+}
+
+impl IQReader for QueueReader {
+    fn get_iq_data(
+        &mut self,
+        _off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        let ts = Instant::now();
+        loop {
+            let wait = match self.timeout {
+                Some(timeout) => timeout.saturating_sub(ts.elapsed()).min(POLL_INTERVAL),
+                None => POLL_INTERVAL,
+            };
+            if let Some((vec, num_lost)) = self.queue.pop_timeout(num_samples, wait)? {
+                self.num_lost += num_lost;
+                return Ok(vec);
+            }
+            self.num_wait += 1;
+            if self.exit_req.load(Ordering::SeqCst) {
+                return Err("exit requested".into());
+            }
+            if let Some(timeout) = self.timeout
+                && ts.elapsed() >= timeout
+            {
+                return Err(format!(
+                    "live source stalled: no samples for {:.1} sec",
+                    timeout.as_secs_f64()
+                )
+                .into());
+            }
+        }
+    }
+
+    fn take_lost_samples(&mut self) -> usize {
+        std::mem::take(&mut self.num_lost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(n: usize) -> Vec<Complex64> {
+        (0..n).map(|i| Complex64::new(i as f64, 0.0)).collect()
+    }
+
+    #[test]
+    fn overflow() {
+        let queue = SampleQueue::new(10);
+        queue.push(&samples(6));
+        // 6 + 6 > 10: the queue and the new samples are dropped
+        queue.push(&samples(6));
+        queue.push(&samples(4));
+        let (vec, num_lost) = queue.pop_timeout(4, Duration::ZERO).unwrap().unwrap();
+        assert_eq!(vec, samples(4));
+        assert_eq!(num_lost, 12);
+        let stats = queue.get_stats();
+        assert_eq!(stats.num_samples, 16);
+        assert_eq!(stats.num_dropped, 12);
+        assert_eq!(stats.num_overflows, 1);
+        assert_eq!(stats.max_fill, 6);
+    }
+
+    #[test]
+    fn gap() {
+        let queue = Arc::new(SampleQueue::new(100));
+        let mut reader = QueueReader::new(queue.clone(), Arc::new(AtomicBool::new(false)), None);
+        queue.push(&samples(8));
+        assert_eq!(reader.get_iq_data(0, 4).unwrap(), samples(4));
+        assert_eq!(reader.take_lost_samples(), 0);
+        // the 4 samples held are dropped along with the gap
+        queue.push_gap(20);
+        queue.push(&samples(5));
+        assert_eq!(reader.get_iq_data(0, 5).unwrap(), samples(5));
+        assert_eq!(reader.take_lost_samples(), 24);
+        assert_eq!(reader.take_lost_samples(), 0);
+        let stats = queue.get_stats();
+        assert_eq!(stats.num_dropped, 4);
+        assert_eq!(stats.num_gaps, 1);
+    }
+
+    #[test]
+    fn pop_timeout() {
+        let queue = SampleQueue::new(10);
+        queue.push(&samples(3));
+        let ts = Instant::now();
+        let res = queue.pop_timeout(4, Duration::from_millis(50)).unwrap();
+        assert!(res.is_none());
+        assert!(ts.elapsed() >= Duration::from_millis(50));
+        // the samples are still there for a smaller read
+        let (vec, _) = queue.pop_timeout(3, Duration::ZERO).unwrap().unwrap();
+        assert_eq!(vec, samples(3));
+    }
+
+    #[test]
+    fn close() {
+        let queue = SampleQueue::new(10);
+        queue.push(&samples(6));
+        queue.close();
+        let (vec, _) = queue.pop_timeout(4, Duration::ZERO).unwrap().unwrap();
+        assert_eq!(vec, samples(4));
+        // 2 samples left: not enough for a read of 4
+        assert!(queue.pop_timeout(4, Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn stall_timeout() {
+        let queue = Arc::new(SampleQueue::new(10));
+        let timeout = Some(Duration::from_millis(50));
+        let mut reader = QueueReader::new(queue, Arc::new(AtomicBool::new(false)), timeout);
+        assert!(reader.get_iq_data(0, 1).is_err());
+        assert!(reader.get_num_wait() > 0);
+    }
+}
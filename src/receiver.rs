@@ -5,19 +5,34 @@ use rayon::prelude::*;
 use rustfft::num_complex::Complex64;
 use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::channel::Channel;
-use crate::device::RtlSdrDevice;
-use crate::network::RtlSdrTcp;
+use crate::code::Code;
+use crate::code::GAL_PRN_MAX;
+use crate::code::L1OF_FREQ_STEP;
+use crate::code::QZSS_PRN_OFFSET;
+use crate::device::SdrSource;
+use crate::frontend::RealIfFrontEnd;
+use crate::multifile::{IQMultiRecording, is_multi_file};
+use crate::netiq::{IQNetSource, is_net_source};
+use crate::network::{RtlSdrTcp, RtlSdrTcpConfig};
+use crate::recorder::IQRecorder;
 use crate::recording::IQFileType;
 use crate::recording::IQRecording;
+use crate::recording::ReadStats;
+use crate::sigmf::{SigMFMeta, SigMFRecording, is_sigmf_file};
 use crate::solver::PositionSolver;
 use crate::state::GnssState;
+use crate::stream::{IQStream, is_stream};
+use crate::throttle::IQThrottle;
 
 const PERIOD_RCV: f64 = 0.001;
+// periods cached past the window before the cache gets shifted
+const CACHE_PERIODS: usize = 64;
 
 pub trait IQReader {
     fn get_iq_data(
@@ -25,153 +40,572 @@ pub trait IQReader {
         off_samples: usize,
         num_samples: usize,
     ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>>;
+
+    // read statistics, for feeds that keep them
+    fn get_stats(&self) -> Option<ReadStats> {
+        None
+    }
+
+    // tuner gain of live sources, None when unknown or automatic
+    fn get_gain_db(&self) -> Option<f64> {
+        None
+    }
+
+    // samples lost right before the ones of the last read, e.g. by a live
+    // source the receiver couldn't keep up with
+    fn take_lost_samples(&mut self) -> usize {
+        0
+    }
 }
 
-pub struct Receiver {
+// One RF band: an IQ feed and the channels processing its samples. All the
+// feeds are expected to be time-aligned, each band has its own sampling rate.
+struct Band {
+    sig: String,
     iq_feed: Box<dyn IQReader>,
+    fs: f64,
     period_sp: usize, // samples per period
+    window_sp: usize, // samples handed to the channels: 2 codes worth
     off_samples: usize,
-    cached_iq_vec: Vec<Complex64>,
+    cached_iq_vec: Vec<Complex64>, // the window is its tail
     cached_ts_sec_tail: f64,
     channels: HashMap<SV, Channel>,
+    state: Arc<Mutex<GnssState>>,
+}
+
+// SigMF input, for the annotations written back once the run is over
+struct SigMFInput {
+    meta: SigMFMeta,
+    meta_path: PathBuf,
+    fs: f64,            // sampling rate of the dataset, before decimation
+    off_samples: usize, // dataset sample of receiver time 0
+}
+
+pub struct Receiver {
+    bands: Vec<Band>,
+    fs_in: f64, // sampling rate of the input of the first band
+    off_msec: usize,
     solver: PositionSolver,
+    state: Arc<Mutex<GnssState>>,
     last_fix_sec: f64,
     exit_req: Arc<AtomicBool>,
+    sigmf: Option<SigMFInput>,
+}
+
+fn get_sig_constellation(sig: &str) -> Constellation {
+    match sig {
+        "E1B" | "E1C" | "E5aI" | "E5aQ" => Constellation::Galileo,
+        "B1I" => Constellation::BeiDou,
+        "L1OF" => Constellation::Glonass,
+        _ => Constellation::GPS,
+    }
+}
+
+// GLONASS frequency channel number k per orbital slot. Antipodal satellites
+// share the same k. The assignment is broadcast in the almanac and may change.
+fn get_glonass_freq_channel(slot: u8) -> Option<i32> {
+    const FREQ_CHANNEL: [i32; 24] = [
+        1, -4, 5, 6, 1, -4, 5, 6, -2, -7, 0, -1, -2, -7, 0, -1, 4, -3, 3, 2, 4, -3, 3, 2,
+    ];
+    if !(1..=24).contains(&slot) {
+        return None;
+    }
+    Some(FREQ_CHANNEL[slot as usize - 1])
+}
+
+// carrier offset of a satellite relative to the signal frequency
+fn get_sat_freq_offset(sv: SV) -> Option<f64> {
+    match sv.constellation {
+        Constellation::Glonass => {
+            get_glonass_freq_channel(sv.prn).map(|k| k as f64 * L1OF_FREQ_STEP)
+        }
+        _ => Some(0.0),
+    }
+}
+
+// accepts a plain PRN, or a RINEX style id: G05, J01, E11, C20, R03, S23
+fn parse_sv(s: &str, constellation: Constellation) -> Option<SV> {
+    let s = s.trim();
+    let (constellation, num) = match s.chars().next()? {
+        'G' => (Constellation::GPS, &s[1..]),
+        'J' => (Constellation::QZSS, &s[1..]),
+        'E' => (Constellation::Galileo, &s[1..]),
+        'C' => (Constellation::BeiDou, &s[1..]),
+        'R' => (Constellation::Glonass, &s[1..]),
+        'S' => {
+            // SBAS satellites are tracked as GPS PRN 120..158
+            let prn = s[1..].parse::<u8>().ok()?;
+            return Some(SV::new(Constellation::GPS, prn.checked_add(100)?));
+        }
+        _ => (constellation, s),
+    };
+    let prn = num.parse::<u8>().ok()?;
+    if constellation == Constellation::GPS && prn > QZSS_PRN_OFFSET {
+        return Some(SV::new(Constellation::QZSS, prn - QZSS_PRN_OFFSET));
+    }
+    Some(SV::new(constellation, prn))
 }
 
-fn get_sat_list(sats: &str) -> Vec<SV> {
+// PRNs of the SBAS GEOs in service: WAAS, EGNOS, MSAS, GAGAN, SDCM, KASS,
+// BDSBAS and SouthPAN
+const SBAS_GEO_PRNS: [u8; 20] = [
+    121, 122, 123, 125, 126, 127, 128, 129, 131, 132, 133, 134, 135, 136, 137, 138, 140, 141, 143,
+    144,
+];
+
+pub fn get_sat_list(sats: &str, sig: &str) -> Vec<SV> {
+    let constellation = get_sig_constellation(sig);
     let mut sat_vec = vec![];
     if !sats.is_empty() {
         for s in sats.split(',') {
-            let prn = s.parse::<u8>().unwrap();
-            sat_vec.push(SV::new(Constellation::GPS, prn));
+            match parse_sv(s, constellation) {
+                Some(sv) => sat_vec.push(sv),
+                None => log::warn!("invalid satellite: {s}"),
+            }
+        }
+    } else if constellation == Constellation::Galileo {
+        for prn in 1..=GAL_PRN_MAX {
+            sat_vec.push(SV::new(Constellation::Galileo, prn));
+        }
+    } else if constellation == Constellation::Glonass {
+        for slot in 1..=24_u8 {
+            sat_vec.push(SV::new(Constellation::Glonass, slot));
+        }
+    } else if constellation == Constellation::BeiDou {
+        for prn in 1..=37_u8 {
+            sat_vec.push(SV::new(Constellation::BeiDou, prn));
         }
     } else {
         for prn in 1..=32_u8 {
             sat_vec.push(SV::new(Constellation::GPS, prn));
         }
-        let use_sbas = false;
-        if use_sbas {
-            for prn in 120..=158_u8 {
-                sat_vec.push(SV::new(Constellation::GPS, prn));
-            }
+        // QZSS broadcasts GPS compatible L1 C/A: PRN 193..202
+        for prn in 1..=10_u8 {
+            sat_vec.push(SV::new(Constellation::QZSS, prn));
+        }
+        // SBAS GEOs share the L1 C/A code family: other PRNs of 120..158
+        // can be given with --sats
+        for prn in SBAS_GEO_PRNS {
+            sat_vec.push(SV::new(Constellation::GPS, prn));
         }
     }
     sat_vec
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_iq_feed(
-    use_device: bool,
+    device: &str,
     hostname: &str,
-    sig: &str,
+    rtl_tcp_cfg: &RtlSdrTcpConfig,
+    fc: f64,
     fs: f64,
     file: &Path,
     iq_file_type: &IQFileType,
     exit_req: Arc<AtomicBool>,
-) -> Option<Box<dyn IQReader>> {
-    if use_device {
-        let res = RtlSdrDevice::new(sig, fs);
-        if res.is_err() {
-            log::warn!("Failed to open rtl-sdr device.");
-            return None;
-        }
-        let dev = res.unwrap();
+) -> Result<Box<dyn IQReader>, Box<dyn std::error::Error>> {
+    if !device.is_empty() {
+        let dev = SdrSource::new(
+            device,
+            iq_file_type,
+            fc,
+            fs,
+            rtl_tcp_cfg.gain_db,
+            exit_req.clone(),
+        )
+        .map_err(|e| format!("Failed to open device {device}: {e}"))?;
 
-        Some(Box::new(dev))
+        Ok(Box::new(dev))
+    } else if is_net_source(hostname) {
+        Ok(Box::new(IQNetSource::new(
+            hostname,
+            iq_file_type,
+            fs,
+            exit_req.clone(),
+        )?))
     } else if !hostname.is_empty() {
-        let net = RtlSdrTcp::new(hostname, exit_req.clone(), sig, fs).unwrap();
+        let net = RtlSdrTcp::new(hostname, exit_req.clone(), fc, fs, rtl_tcp_cfg)
+            .map_err(|e| format!("Failed to connect to rtl_tcp {hostname}: {e}"))?;
 
         log::warn!("Using rtl_tcp backend: {}", hostname);
-        Some(Box::new(net))
+        Ok(Box::new(net))
+    } else if file.as_os_str().is_empty() {
+        Err("no IQ file, device or network source".into())
+    } else if is_stream(file) {
+        Ok(Box::new(IQStream::new(file, iq_file_type)?))
+    } else if is_multi_file(file) {
+        Ok(Box::new(IQMultiRecording::new(file, fs, iq_file_type)?))
     } else {
-        Some(Box::new(IQRecording::new(file, fs, iq_file_type)))
+        Ok(Box::new(IQRecording::new(file, fs, iq_file_type)?))
     }
 }
 
-impl Receiver {
+// Real IF samples of files and raw network sources are down-converted and
+// decimated: returns the feed, its sampling rate and IF.
+fn get_band_feed(
+    iq_feed: Box<dyn IQReader>,
+    device: &str,
+    hostname: &str,
+    iq_file_type: &IQFileType,
+    fs: f64,
+    fi: f64,
+    decim: usize,
+) -> (Box<dyn IQReader>, f64, f64) {
+    if device.is_empty()
+        && (hostname.is_empty() || is_net_source(hostname))
+        && iq_file_type.is_real()
+    {
+        let iq_feed = Box::new(RealIfFrontEnd::new(iq_feed, fs, fi, decim));
+        (iq_feed, fs / decim as f64, 0.0)
+    } else {
+        (iq_feed, fs, fi)
+    }
+}
+
+impl Band {
     #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        use_device: bool,
-        hostname: &str,
-        file: &Path,
-        iq_file_type: &IQFileType,
+    fn new(
+        iq_feed: Box<dyn IQReader>,
         fs: f64,
         fi: f64,
+        fc: f64,
         off_msec: usize,
         sig: &str,
         sats: &str,
-        exit_req: Arc<AtomicBool>,
         state: Arc<Mutex<GnssState>>,
-    ) -> Self {
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let period_sp = (PERIOD_RCV * fs) as usize;
+        let code_ms = (Code::get_code_period(sig) / PERIOD_RCV).round() as usize;
+        let window_sp = 2 * code_ms * period_sp;
         let mut channels = HashMap::<SV, Channel>::new();
-        let sat_vec = get_sat_list(sats);
+        let sat_vec = get_sat_list(sats, sig);
+        let num_sats = sat_vec.len();
+
+        // fc is the RF center frequency of the capture: a signal that is not
+        // at the center of a wideband capture is seen as an IF offset.
+        let sig_fc = Code::get_code_freq(sig);
+        let fc = if fc == 0.0 { sig_fc } else { fc };
+        let sig_fi = fi + sig_fc - fc;
+        if sig_fi.abs() > fs / 2.0 {
+            log::warn!(
+                "{sig}: IF offset {:.1} KHz outside of the {:.1} KHz band",
+                sig_fi / 1000.0,
+                fs / 1000.0
+            );
+        }
 
         for sv in sat_vec {
             let pub_state = state.clone();
-            channels.insert(sv, Channel::new(sig, sv, fs, fi, pub_state));
+            let Some(fo) = get_sat_freq_offset(sv) else {
+                log::warn!("{sv}: unknown carrier frequency");
+                continue;
+            };
+            match Channel::new(sig, sv, fs, sig_fi, fo, pub_state) {
+                Some(channel) => {
+                    channels.insert(sv, channel);
+                }
+                None => log::warn!("{sv}: no {sig} code available"),
+            }
+        }
+        // e.g. the code tables of memory codes are missing
+        if channels.is_empty() && num_sats > 0 {
+            return Err(format!("{sig}: no code available, cf resources/README.md").into());
         }
 
-        let iq_feed = get_iq_feed(
-            use_device,
-            hostname,
-            sig,
-            fs,
-            file,
-            iq_file_type,
-            exit_req.clone(),
-        )
-        .unwrap();
-
-        Self {
+        Ok(Self {
+            sig: sig.to_string(),
             iq_feed,
+            fs,
             period_sp,
+            window_sp,
             off_samples: off_msec * period_sp,
             cached_iq_vec: Vec::<Complex64>::new(),
             cached_ts_sec_tail: 0.0,
             channels,
-            solver: PositionSolver::new(state),
-            last_fix_sec: 0.0,
-            exit_req: exit_req.clone(),
-        }
+            state,
+        })
     }
 
-    fn fetch_samples_msec(&mut self) -> Result<(Vec<Complex64>, f64), Box<dyn std::error::Error>> {
-        let num_samples = if self.cached_iq_vec.is_empty() {
-            2 * self.period_sp
+    // Moves the sample window by one period, cf get_window(). Returns its
+    // timestamp and the time skipped over because of samples lost by the feed.
+    fn fetch_samples_msec(&mut self) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+        let mut num_samples = if self.cached_iq_vec.is_empty() {
+            self.window_sp
         } else {
             self.period_sp
         };
+        let mut num_skipped = 0;
 
-        let mut iq_vec = self.iq_feed.get_iq_data(self.off_samples, num_samples)?;
+        loop {
+            let mut iq_vec = self.iq_feed.get_iq_data(self.off_samples, num_samples)?;
+            self.off_samples += num_samples;
+
+            let num_lost = self.iq_feed.take_lost_samples();
+            if num_lost > 0 {
+                // the samples before the gap can't be correlated with the ones
+                // after it: the window is refilled
+                log::warn!("{}: {num_lost} samples lost", self.sig);
+                let mut state = self.state.lock().unwrap();
+                state.num_lost_samples += num_lost;
+                state.num_gaps += 1;
+                drop(state);
 
-        self.off_samples += num_samples;
-        self.cached_iq_vec.append(&mut iq_vec);
-        self.cached_ts_sec_tail += num_samples as f64 / (1000.0 * self.period_sp as f64);
+                self.off_samples += num_lost;
+                self.cached_ts_sec_tail += num_lost as f64 / self.fs;
+                num_skipped += num_lost;
+                self.cached_iq_vec.clear();
+            }
+            self.cached_iq_vec.append(&mut iq_vec);
+            self.cached_ts_sec_tail += num_samples as f64 / self.fs;
+            if self.cached_iq_vec.len() >= self.window_sp {
+                break;
+            }
+            num_samples = self.window_sp - self.cached_iq_vec.len();
+            num_skipped += num_samples;
+        }
 
-        if self.cached_iq_vec.len() > 2 * self.period_sp {
-            let num_samples = self.period_sp;
+        // the older samples are dropped once in a while rather than every
+        // period: a shift moves the whole window
+        if self.cached_iq_vec.len() > self.window_sp + CACHE_PERIODS * self.period_sp {
+            let num_samples = self.cached_iq_vec.len() - self.window_sp;
             let _ = self.cached_iq_vec.drain(0..num_samples);
         }
-        let len = self.cached_iq_vec.len();
 
-        // we pass 2 code worth of iq data back
-        // the timestamp given corresponds to the beginning of the last code
+        // the timestamp given corresponds to the beginning of the last period
         // [...code...][...code...]
-        //             ^
-
+        //                     ^
         Ok((
-            self.cached_iq_vec[len - 2 * self.period_sp..].to_vec(),
-            self.cached_ts_sec_tail - 0.001,
+            self.cached_ts_sec_tail - self.period_sp as f64 / self.fs,
+            num_skipped as f64 / self.fs,
         ))
     }
 
+    // 2 codes worth of iq data, ending with the last period fetched
+    fn get_window(cached_iq_vec: &[Complex64], window_sp: usize) -> &[Complex64] {
+        &cached_iq_vec[cached_iq_vec.len() - window_sp..]
+    }
+}
+
+impl Receiver {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &str,
+        hostname: &str,
+        rtl_tcp_cfg: &RtlSdrTcpConfig,
+        file: &Path,
+        iq_file_type: &IQFileType,
+        record: &Path,
+        speed: f64,
+        fs: f64,
+        fi: f64,
+        decim: usize,
+        fc: f64,
+        off_msec: usize,
+        sig: &str,
+        sats: &str,
+        exit_req: Arc<AtomicBool>,
+        state: Arc<Mutex<GnssState>>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (mut fs, mut fc) = (fs, fc);
+        let mut iq_file_type = iq_file_type.clone();
+
+        // SigMF metadata takes precedence over the command line
+        let mut sigmf_rec = None;
+        if device.is_empty() && hostname.is_empty() && is_sigmf_file(file) {
+            let rec =
+                SigMFRecording::new(file).map_err(|e| format!("SigMF: {}: {e}", file.display()))?;
+            let fs_meta = rec
+                .meta
+                .get_sample_rate()
+                .ok_or("SigMF: core:sample_rate missing from the metadata")?;
+            if fs_meta != fs {
+                log::warn!("SigMF: fs={:.1} KHz", fs_meta / 1000.0);
+                fs = fs_meta;
+            }
+            if let Some(fc_meta) = rec.meta.get_frequency()
+                && fc_meta != fc
+            {
+                log::warn!("SigMF: fc={:.3} MHz", fc_meta / 1_000_000.0);
+                fc = fc_meta;
+            }
+            iq_file_type = rec.file_type.clone();
+            sigmf_rec = Some(rec);
+        }
+        let sigmf = sigmf_rec.as_ref().map(|rec| SigMFInput {
+            meta: rec.meta.clone(),
+            meta_path: rec.meta_path.clone(),
+            fs,
+            off_samples: off_msec * (PERIOD_RCV * fs) as usize,
+        });
+
+        let fc_feed = if fc == 0.0 {
+            Code::get_code_freq(sig)
+        } else {
+            fc
+        };
+
+        let mut iq_feed: Box<dyn IQReader> = match sigmf_rec {
+            Some(rec) => Box::new(rec),
+            None => get_iq_feed(
+                device,
+                hostname,
+                rtl_tcp_cfg,
+                fc_feed,
+                fs,
+                file,
+                &iq_file_type,
+                exit_req.clone(),
+            )?,
+        };
+        // real-time playback of recordings, 0: as fast as possible
+        if speed != 0.0 {
+            if device.is_empty() && hostname.is_empty() {
+                iq_feed = Box::new(IQThrottle::new(iq_feed, fs, speed));
+            } else {
+                log::warn!("real-time playback only applies to recordings");
+            }
+        }
+        if !record.as_os_str().is_empty() {
+            if !device.is_empty() || !hostname.is_empty() {
+                iq_feed = Box::new(IQRecorder::new(
+                    iq_feed,
+                    record,
+                    &iq_file_type,
+                    fs,
+                    fc_feed,
+                )?);
+            } else {
+                log::warn!("recording is only available with live sources");
+            }
+        }
+        let fs_in = fs;
+        let (iq_feed, fs, fi) =
+            get_band_feed(iq_feed, device, hostname, &iq_file_type, fs, fi, decim);
+        let band = Band::new(iq_feed, fs, fi, fc, off_msec, sig, sats, state.clone())?;
+
+        Ok(Self {
+            bands: vec![band],
+            fs_in,
+            off_msec,
+            solver: PositionSolver::new(state.clone()),
+            state,
+            last_fix_sec: 0.0,
+            exit_req: exit_req.clone(),
+            sigmf,
+        })
+    }
+
+    // Keeps the channel events, e.g. LOCK and LOST, to write them back as
+    // annotations once the run is over. Only applies to SigMF inputs.
+    pub fn enable_sigmf_annotations(&mut self) {
+        if self.sigmf.is_none() {
+            log::warn!("SigMF: annotations only apply to SigMF recordings");
+            return;
+        }
+        self.state.lock().unwrap().record_events = true;
+    }
+
+    // Writes the channel events as annotations to the metadata of a SigMF
+    // input. Nothing to do for other inputs.
+    pub fn write_sigmf_annotations(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(sigmf) = &mut self.sigmf else {
+            return Ok(());
+        };
+        let events = std::mem::take(&mut self.state.lock().unwrap().events);
+        for ev in &events {
+            let sample = sigmf.off_samples + (ev.ts_sec * sigmf.fs).round() as usize;
+            let comment = format!("{} {} cn0={:.1}", ev.id.0, ev.id.1, ev.cn0);
+            sigmf
+                .meta
+                .add_annotation(sample as u64, &ev.label, &comment);
+        }
+        sigmf.meta.save(&sigmf.meta_path)?;
+        log::warn!(
+            "SigMF: {} annotations written to {}",
+            events.len(),
+            sigmf.meta_path.display().to_string().green()
+        );
+        Ok(())
+    }
+
+    // Adds a second band, e.g. L5Q next to L1CA, from an SDR device, a
+    // network source or a file that starts at the same time as the first
+    // band. fs is the sampling rate of its input, 0 for the one of the first
+    // band, fi and decim apply to real IF input. Channels of the same SV on both bands are combined into
+    // ionosphere-free pseudoranges.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_band(
+        &mut self,
+        device: &str,
+        hostname: &str,
+        rtl_tcp_cfg: &RtlSdrTcpConfig,
+        file: &Path,
+        iq_file_type: &IQFileType,
+        fs: f64,
+        fi: f64,
+        decim: usize,
+        fc: f64,
+        sig: &str,
+        sats: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if Code::get_code_freq(sig) == 0.0 {
+            return Err(format!("unknown signal {sig}").into());
+        }
+        let fs = if fs == 0.0 { self.fs_in } else { fs };
+        let fc_feed = if fc == 0.0 {
+            Code::get_code_freq(sig)
+        } else {
+            fc
+        };
+        let iq_feed = get_iq_feed(
+            device,
+            hostname,
+            rtl_tcp_cfg,
+            fc_feed,
+            fs,
+            file,
+            iq_file_type,
+            self.exit_req.clone(),
+        )?;
+        let (iq_feed, fs, fi) =
+            get_band_feed(iq_feed, device, hostname, iq_file_type, fs, fi, decim);
+        let band = Band::new(
+            iq_feed,
+            fs,
+            fi,
+            fc,
+            self.off_msec,
+            sig,
+            sats,
+            self.state.clone(),
+        )?;
+        self.bands.push(band);
+        Ok(())
+    }
+
+    // Ionosphere-free combination of the code offsets of an SV tracked on two
+    // bands. Both signals are received at the same time to within a small
+    // fraction of a code period: only the fractional difference matters.
+    fn get_iono_free_code_off(&self, sv: SV, code_off_sec: f64) -> Option<f64> {
+        let band = self.bands.get(1)?;
+        let ch = band.channels.get(&sv)?;
+        if !ch.is_state_tracking() {
+            return None;
+        }
+        let f1 = Code::get_code_freq(&self.bands[0].sig);
+        let f2 = Code::get_code_freq(&band.sig);
+        let mut diff = ch.get_code_off_sec() - code_off_sec;
+        diff -= (diff / PERIOD_RCV).round() * PERIOD_RCV;
+
+        // (f1^2 P1 - f2^2 P2) / (f1^2 - f2^2) with P2 = P1 + diff
+        Some(code_off_sec - f2 * f2 / (f1 * f1 - f2 * f2) * diff)
+    }
+
     fn compute_fix(&mut self, ts_sec: f64) {
         if ts_sec - self.last_fix_sec < 2.0 {
             return;
         }
 
-        let ephs: Vec<_> = self
+        let mut ephs: Vec<_> = self.bands[0]
             .channels
             .values()
             .filter(|&ch| ch.is_state_tracking())
@@ -179,6 +613,17 @@ impl Receiver {
             .map(|ch| ch.nav.eph)
             .collect();
 
+        for eph in ephs.iter_mut() {
+            if let Some(code_off_sec) = self.get_iono_free_code_off(eph.sv, eph.code_off_sec) {
+                log::info!(
+                    "{}: iono-free code offset: {:+e} -> {code_off_sec:+e}",
+                    eph.sv,
+                    eph.code_off_sec
+                );
+                eph.code_off_sec = code_off_sec;
+            }
+        }
+
         if ephs.len() < 4 {
             return;
         }
@@ -193,11 +638,20 @@ impl Receiver {
     }
 
     fn process_step(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let (iq_vec, ts_sec) = self.fetch_samples_msec()?;
+        let mut ts_sec = 0.0;
+        for band in self.bands.iter_mut() {
+            let (ts, skip_sec) = band.fetch_samples_msec()?;
 
-        self.channels
-            .par_iter_mut()
-            .for_each(|(_id, channel)| channel.process_samples(&iq_vec, ts_sec));
+            // the window stays in the cache of the band, the channels borrow it
+            let iq_vec = Band::get_window(&band.cached_iq_vec, band.window_sp);
+            band.channels.par_iter_mut().for_each(|(_id, channel)| {
+                if skip_sec > 0.0 {
+                    channel.skip_samples(skip_sec);
+                }
+                channel.process_samples(iq_vec, ts)
+            });
+            ts_sec = ts;
+        }
 
         self.compute_fix(ts_sec);
 
@@ -220,5 +674,41 @@ impl Receiver {
                 break;
             }
         }
+        for band in &self.bands {
+            if let Some(stats) = band.iq_feed.get_stats() {
+                log::warn!("{}: {stats}", band.sig);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sat_freq_offset() {
+        for (slot, k) in [(1, 1), (2, -4), (10, -7), (11, 0), (17, 4), (24, 2)] {
+            let sv = SV::new(Constellation::Glonass, slot);
+            assert_eq!(get_sat_freq_offset(sv), Some(k as f64 * 562.5e3));
+        }
+        // antipodal slots of a plane share the frequency channel
+        for slot in (0..3).flat_map(|plane| 8 * plane + 1..8 * plane + 5) {
+            let sv = SV::new(Constellation::Glonass, slot);
+            let sv_anti = SV::new(Constellation::Glonass, slot + 4);
+            assert_eq!(get_sat_freq_offset(sv), get_sat_freq_offset(sv_anti));
+        }
+        assert_eq!(
+            get_sat_freq_offset(SV::new(Constellation::Glonass, 0)),
+            None
+        );
+        assert_eq!(
+            get_sat_freq_offset(SV::new(Constellation::Glonass, 25)),
+            None
+        );
+        assert_eq!(
+            get_sat_freq_offset(SV::new(Constellation::GPS, 5)),
+            Some(0.0)
+        );
     }
 }
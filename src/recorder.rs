@@ -0,0 +1,182 @@
+use chrono::{SecondsFormat, Utc};
+use colored::Colorize;
+use rustfft::num_complex::Complex64;
+use serde_json::json;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording, ReadStats};
+use crate::sigmf::{SigMFCapture, SigMFGlobal, SigMFMeta, get_meta_path, get_sigmf_datatype};
+
+const WRITE_BUF_SIZE: usize = 1 << 20;
+const WRITE_QUEUE_LEN: usize = 64; // reads waiting for the disk
+
+// Tees the samples of a live source into a recording, along with its SigMF
+// metadata, e.g. rec.sigmf-data and rec.sigmf-meta. The file is written by
+// a separate thread so that a slow disk doesn't hold up the receiver: when it
+// falls behind, reads are dropped from the recording. Samples lost by the
+// source or dropped show up as GAP annotations.
+pub struct IQRecorder {
+    iq_feed: Box<dyn IQReader>,
+    file_type: IQFileType,
+    path: PathBuf,
+    fs: f64,
+    meta: SigMFMeta,
+    tx: Option<SyncSender<Vec<u8>>>,
+    write_th: Option<JoinHandle<()>>,
+    num_samples: usize,
+    num_lost: usize,    // before the samples of the last read
+    num_dropped: usize, // not yet annotated
+}
+
+impl Drop for IQRecorder {
+    fn drop(&mut self) {
+        self.annotate_dropped();
+        // closing the channel ends the write thread
+        self.tx = None;
+        if let Some(th) = self.write_th.take() {
+            let _ = th.join();
+        }
+        // with the gaps of the recording as annotations
+        if let Err(e) = self.meta.save(&get_meta_path(&self.path)) {
+            log::warn!("recording: failed to write metadata: {e}");
+        }
+        log::warn!(
+            "recorded {:.1} secs to {}",
+            self.num_samples as f64 / self.fs,
+            self.path.display().to_string().green()
+        );
+    }
+}
+
+impl IQRecorder {
+    pub fn new(
+        iq_feed: Box<dyn IQReader>,
+        path: &Path,
+        file_type: &IQFileType,
+        fs: f64,
+        fc: f64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let datatype = get_sigmf_datatype(file_type)
+            .ok_or(format!("{file_type}: not supported for recording"))?;
+
+        let mut capture = SigMFCapture {
+            sample_start: 0,
+            frequency: Some(fc),
+            datetime: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+            ..Default::default()
+        };
+        match iq_feed.get_gain_db() {
+            Some(gain_db) => capture
+                .other
+                .insert("gnss-rcv:gain_db".into(), json!(gain_db)),
+            None => capture.other.insert("gnss-rcv:agc".into(), json!(true)),
+        };
+        let meta = SigMFMeta {
+            global: SigMFGlobal {
+                datatype: datatype.to_string(),
+                sample_rate: Some(fs),
+                version: Some("1.0.0".to_string()),
+                description: Some("gnss-rcv recording".to_string()),
+                ..Default::default()
+            },
+            captures: vec![capture],
+            annotations: vec![],
+            ..Default::default()
+        };
+        meta.save(&get_meta_path(path))?;
+
+        let mut writer = BufWriter::with_capacity(WRITE_BUF_SIZE, File::create(path)?);
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(WRITE_QUEUE_LEN);
+        let write_th = thread::spawn(move || {
+            for buf in rx {
+                if let Err(e) = writer.write_all(&buf) {
+                    log::warn!("recording: write failed: {e}");
+                    return;
+                }
+            }
+            if let Err(e) = writer.flush() {
+                log::warn!("recording: write failed: {e}");
+            }
+        });
+
+        log::warn!(
+            "recording {file_type} to {}",
+            path.display().to_string().green()
+        );
+        Ok(Self {
+            iq_feed,
+            file_type: file_type.clone(),
+            path: path.to_path_buf(),
+            fs,
+            meta,
+            tx: Some(tx),
+            write_th: Some(write_th),
+            num_samples: 0,
+            num_lost: 0,
+            num_dropped: 0,
+        })
+    }
+
+    fn annotate_dropped(&mut self) {
+        if self.num_dropped > 0 {
+            let comment = format!("{} samples dropped: disk too slow", self.num_dropped);
+            self.meta
+                .add_annotation(self.num_samples as u64, "GAP", &comment);
+            self.num_dropped = 0;
+        }
+    }
+}
+
+impl IQReader for IQRecorder {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        let iq_vec = self.iq_feed.get_iq_data(off_samples, num_samples)?;
+        let num_lost = self.iq_feed.take_lost_samples();
+        if num_lost > 0 {
+            let comment = format!("{num_lost} samples lost");
+            self.meta
+                .add_annotation(self.num_samples as u64, "GAP", &comment);
+            self.num_lost += num_lost;
+        }
+        let buf = IQRecording::encode(&iq_vec, &self.file_type)?;
+        // the write thread is gone after an error, already reported
+        let Some(tx) = &self.tx else {
+            return Ok(iq_vec);
+        };
+        match tx.try_send(buf) {
+            Ok(()) => {
+                self.annotate_dropped();
+                self.num_samples += iq_vec.len();
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.num_dropped == 0 {
+                    log::warn!("recording: disk too slow, dropping samples");
+                }
+                self.num_dropped += iq_vec.len();
+            }
+            Err(TrySendError::Disconnected(_)) => self.tx = None,
+        }
+        Ok(iq_vec)
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        self.iq_feed.get_stats()
+    }
+
+    fn get_gain_db(&self) -> Option<f64> {
+        self.iq_feed.get_gain_db()
+    }
+
+    fn take_lost_samples(&mut self) -> usize {
+        std::mem::take(&mut self.num_lost)
+    }
+}
@@ -1,25 +1,34 @@
 use bytesize::ByteSize;
 use colored::Colorize;
+use flate2::read::MultiGzDecoder;
 use rustfft::num_complex::Complex64;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
 use std::io::Seek;
 use std::io::SeekFrom;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufReader, Read};
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use xz2::read::XzDecoder;
 
 use crate::receiver::IQReader;
 
 #[derive(Clone)]
 pub enum IQFileType {
     TypePairFloat32,
+    TypePairFloat32Be,
+    TypePairFloat16,
     TypePairInt16,
+    TypePairInt16Be,
+    TypePairInt12,
+    TypePairInt8,
     TypeRtlSdrFile,
     TypeOneInt8,
+    TypeOneInt4,
+    TypeOneInt2,
 }
 
 impl FromStr for IQFileType {
@@ -27,28 +36,371 @@ impl FromStr for IQFileType {
     fn from_str(input: &str) -> Result<IQFileType, Self::Err> {
         match input {
             "2xf32" => Ok(IQFileType::TypePairFloat32),
+            "2xf32be" => Ok(IQFileType::TypePairFloat32Be),
+            "2xf16" => Ok(IQFileType::TypePairFloat16),
             "2xi16" => Ok(IQFileType::TypePairInt16),
+            "2xi16be" => Ok(IQFileType::TypePairInt16Be),
+            "2xi12" => Ok(IQFileType::TypePairInt12),
+            "2xi8" => Ok(IQFileType::TypePairInt8),
             "rtlsdr-file" => Ok(IQFileType::TypeRtlSdrFile),
             "i8" => Ok(IQFileType::TypeOneInt8),
+            "i4" => Ok(IQFileType::TypeOneInt4),
+            "i2" => Ok(IQFileType::TypeOneInt2),
             _ => Err(format!("Failed to parse {}", input).into()),
         }
     }
 }
 
+impl IQFileType {
+    // real samples, at an intermediate frequency
+    pub fn is_real(&self) -> bool {
+        matches!(
+            self,
+            IQFileType::TypeOneInt8 | IQFileType::TypeOneInt4 | IQFileType::TypeOneInt2
+        )
+    }
+}
+
 impl fmt::Display for IQFileType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             IQFileType::TypePairFloat32 => write!(f, "2xf32"),
+            IQFileType::TypePairFloat32Be => write!(f, "2xf32be"),
+            IQFileType::TypePairFloat16 => write!(f, "2xf16"),
             IQFileType::TypePairInt16 => write!(f, "2xi16"),
+            IQFileType::TypePairInt16Be => write!(f, "2xi16be"),
+            IQFileType::TypePairInt12 => write!(f, "2xi12"),
+            IQFileType::TypePairInt8 => write!(f, "2xi8"),
             IQFileType::TypeRtlSdrFile => write!(f, "rtlsdr-file"),
             IQFileType::TypeOneInt8 => write!(f, "i8"),
+            IQFileType::TypeOneInt4 => write!(f, "i4"),
+            IQFileType::TypeOneInt2 => write!(f, "i2"),
         }
     }
 }
 
+// IEEE 754 half precision
+fn f16_to_f64(h: u16) -> f64 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let man = (h & 0x3ff) as f64;
+    match exp {
+        0 => sign * man * 2f64.powi(-24),
+        0x1f if man == 0.0 => sign * f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => sign * (1.0 + man / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+fn f64_to_f16(v: f64) -> u16 {
+    let sign = if v.is_sign_negative() { 0x8000 } else { 0 };
+    let a = v.abs();
+    if a.is_nan() {
+        return 0x7e00;
+    }
+    if a >= 65520.0 {
+        return sign | 0x7c00;
+    }
+    if a < 2f64.powi(-14) {
+        // subnormal, rounding up to the smallest normal if need be
+        return sign | (a * 2f64.powi(24)).round() as u16;
+    }
+    let mut exp = a.log2().floor() as i32;
+    let mut man = ((a / 2f64.powi(exp) - 1.0) * 1024.0).round() as u16;
+    if man == 1024 {
+        man = 0;
+        exp += 1;
+    }
+    sign | (((exp + 15) as u16) << 10) | man
+}
+
+// 12-bit I and Q packed in 3 bytes, little-endian: I in the low 12 bits
+fn i12_pair(b: &[u8]) -> (i16, i16) {
+    let i = (b[0] as u16 | ((b[1] as u16) << 8)) << 4;
+    let q = (((b[1] as u16) >> 4) | ((b[2] as u16) << 4)) << 4;
+    // sign extension through the arithmetic shift
+    (i as i16 >> 4, q as i16 >> 4)
+}
+
+fn i12_pack(i: i16, q: i16) -> [u8; 3] {
+    let (i, q) = (i as u16 & 0xfff, q as u16 & 0xfff);
+    [i as u8, ((i >> 8) | (q << 4)) as u8, (q >> 4) as u8]
+}
+
+// Sign-magnitude sample of 2 or 4 bits, as output by the quantizer of GNSS
+// front ends (MAX2769, NT1065): sign in the msb, levels at odd values, e.g.
+// -3, -1, 1, 3 for 2 bits. Normalized to [-1, 1].
+fn sign_mag_to_f64(v: u8, bits: u32) -> f64 {
+    let mag_mask = (1 << (bits - 1)) - 1;
+    let level = (2 * (v & mag_mask) + 1) as f64 / (2 * mag_mask + 1) as f64;
+    if (v >> (bits - 1)) & 1 != 0 {
+        -level
+    } else {
+        level
+    }
+}
+
+// the closest level of a sign-magnitude sample of 2 or 4 bits
+fn f64_to_sign_mag(v: f64, bits: u32) -> u8 {
+    let mag_mask = (1 << (bits - 1)) - 1;
+    let mag = ((v.abs() * (2 * mag_mask + 1) as f64 / 2.0) as u8).min(mag_mask);
+    if v < 0.0 {
+        (1 << (bits - 1)) | mag
+    } else {
+        mag
+    }
+}
+
+// Read statistics of an IQ feed
+#[derive(Clone, Default)]
+pub struct ReadStats {
+    pub num_reads: usize,
+    pub num_seeks: usize,
+    pub num_bytes: u64,
+    pub elapsed: Duration, // time spent reading and decoding
+}
+
+impl ReadStats {
+    pub fn add(&mut self, other: &ReadStats) {
+        self.num_reads += other.num_reads;
+        self.num_seeks += other.num_seeks;
+        self.num_bytes += other.num_bytes;
+        self.elapsed += other.elapsed;
+    }
+
+    pub fn get_bandwidth_mbs(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.num_bytes as f64 / 1024.0 / 1024.0 / secs
+    }
+}
+
+impl fmt::Display for ReadStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} read in {:.1} sec: {:.1} MB/sec -- num_reads={} num_seeks={}",
+            ByteSize::b(self.num_bytes),
+            self.elapsed.as_secs_f64(),
+            self.get_bandwidth_mbs(),
+            self.num_reads,
+            self.num_seeks
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Compression::None => write!(f, "raw"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Xz => write!(f, "xz"),
+        }
+    }
+}
+
+impl Compression {
+    // from the magic bytes of the file, whatever its extension
+    fn detect(file_path: &Path) -> io::Result<Self> {
+        let mut magic = [0u8; 6];
+        let mut file = File::open(file_path)?;
+        let mut len = 0;
+        while len < magic.len() {
+            let n = file.read(&mut magic[len..])?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        let compression = match &magic[..len] {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00] => Compression::Xz,
+            _ => Compression::None,
+        };
+        Ok(compression)
+    }
+}
+
+fn read_at(file: &mut File, off: u64, buf: &mut [u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(off))?;
+    file.read_exact(buf)
+}
+
+// Sum of the content sizes of the frames of a zstd file, None if a frame
+// doesn't record it. The frames are walked through their block headers.
+fn get_zstd_content_size(file_path: &Path) -> io::Result<Option<u64>> {
+    let mut file = File::open(file_path)?;
+    let file_size = file.metadata()?.len();
+    let mut off = 0;
+    let mut size = 0;
+    while off < file_size {
+        let mut b = [0u8; 4];
+        read_at(&mut file, off, &mut b)?;
+        let magic = u32::from_le_bytes(b);
+        if magic & 0xfffffff0 == 0x184d2a50 {
+            // skippable frame
+            read_at(&mut file, off + 4, &mut b)?;
+            off += 8 + u32::from_le_bytes(b) as u64;
+            continue;
+        }
+        if magic != 0xfd2fb528 {
+            return Ok(None);
+        }
+        let mut fhd = [0u8; 1];
+        read_at(&mut file, off + 4, &mut fhd)?;
+        let single_segment = fhd[0] & 0x20 != 0;
+        let has_checksum = fhd[0] & 0x04 != 0;
+        let did_len = [0, 1, 2, 4][(fhd[0] & 3) as usize];
+        let fcs_len = match fhd[0] >> 6 {
+            0 if single_segment => 1,
+            0 => return Ok(None),
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        let fcs_off = off + 5 + !single_segment as u64 + did_len;
+        let mut fcs = [0u8; 8];
+        read_at(&mut file, fcs_off, &mut fcs[..fcs_len])?;
+        size += u64::from_le_bytes(fcs) + if fcs_len == 2 { 256 } else { 0 };
+
+        off = fcs_off + fcs_len as u64;
+        loop {
+            let mut h = [0u8; 4];
+            read_at(&mut file, off, &mut h[..3])?;
+            let h = u32::from_le_bytes(h);
+            // RLE blocks hold a single byte
+            let len = if (h >> 1) & 3 == 1 { 1 } else { h >> 3 };
+            off += 3 + len as u64;
+            if h & 1 != 0 {
+                break;
+            }
+        }
+        if has_checksum {
+            off += 4;
+        }
+    }
+    Ok(Some(size))
+}
+
+// xz variable-length integer, 7 bits per byte, least significant first
+fn read_xz_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut v = 0;
+    for i in 0..9 {
+        let b = *buf.get(*pos)?;
+        *pos += 1;
+        v |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+// Sum of the uncompressed sizes listed by the index of each stream of an xz
+// file, walking the streams backward from their footer.
+fn get_xz_content_size(file_path: &Path) -> io::Result<Option<u64>> {
+    let mut file = File::open(file_path)?;
+    let mut end = file.metadata()?.len();
+    let mut size = 0;
+    while end > 0 {
+        if end < 24 {
+            return Ok(None);
+        }
+        // stream padding
+        let mut b = [0u8; 12];
+        read_at(&mut file, end - 4, &mut b[..4])?;
+        if b[..4] == [0; 4] {
+            end -= 4;
+            continue;
+        }
+        read_at(&mut file, end - 12, &mut b)?;
+        if b[10..] != *b"YZ" {
+            return Ok(None);
+        }
+        let index_len = (u32::from_le_bytes([b[4], b[5], b[6], b[7]]) as u64 + 1) * 4;
+        let Some(index_off) = (end - 12).checked_sub(index_len) else {
+            return Ok(None);
+        };
+        let mut index = vec![0u8; index_len as usize];
+        read_at(&mut file, index_off, &mut index)?;
+
+        let mut pos = 1;
+        let mut blocks_len = 0;
+        let Some(num_records) = read_xz_varint(&index, &mut pos) else {
+            return Ok(None);
+        };
+        for _ in 0..num_records {
+            let (Some(unpadded), Some(uncompressed)) = (
+                read_xz_varint(&index, &mut pos),
+                read_xz_varint(&index, &mut pos),
+            ) else {
+                return Ok(None);
+            };
+            blocks_len += unpadded.div_ceil(4) * 4;
+            size += uncompressed;
+        }
+        let Some(start) = index_off.checked_sub(blocks_len + 12) else {
+            return Ok(None);
+        };
+        end = start;
+    }
+    Ok(Some(size))
+}
+
+// Source of raw samples: the file itself, or a decoder streaming the
+// decompressed data. The latter can only seek by reading forward.
+enum Source {
+    Raw(File),
+    Decoder(Box<dyn Read + Send>),
+}
+
+impl Source {
+    fn open(file_path: &Path, compression: Compression) -> io::Result<Self> {
+        let file = BufReader::new(File::open(file_path)?);
+        let source = match compression {
+            Compression::None => Source::Raw(file.into_inner()),
+            Compression::Gzip => Source::Decoder(Box::new(MultiGzDecoder::new(file))),
+            Compression::Zstd => Source::Decoder(Box::new(zstd::Decoder::with_buffer(file)?)),
+            Compression::Xz => Source::Decoder(Box::new(XzDecoder::new_multi_decoder(file))),
+        };
+        Ok(source)
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Raw(file) => file.read(buf),
+            Source::Decoder(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+// The file stays open for the whole run: consecutive reads stream forward
+// from the current position, any other offset costs a seek. Compressed
+// files (gzip, zstd, xz) are decompressed on the fly: seeking backward
+// means decompressing again from the start.
 pub struct IQRecording {
     file_path: PathBuf,
+    compression: Compression,
+    source: Source,
     file_type: IQFileType,
+    num_samples: Option<usize>, // None until the end of a gzip file is reached
+    pos: u64,                   // file offset of the next read
+    buf: Vec<u8>,               // raw samples, reused across reads
+    stats: ReadStats,
 }
 
 impl IQReader for IQRecording {
@@ -57,148 +409,542 @@ impl IQReader for IQRecording {
         off_samples: usize,
         num_samples: usize,
     ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
-        let file = File::open(self.file_path.clone())?;
-        let sample_size = Self::get_sample_size_bytes(&self.file_type);
-        let buf_size = sample_size * num_samples;
-        let mut reader = BufReader::with_capacity(buf_size, &file);
-        let mut n: usize = 0;
-        let ts = Instant::now();
-        let mut iq_vec = vec![];
+        let iq_vec = self.read_iq_data(off_samples, num_samples)?;
+        if iq_vec.len() < num_samples {
+            return Err("end of file".into());
+        }
+        Ok(iq_vec)
+    }
 
-        let off_file = off_samples * sample_size;
+    fn get_stats(&self) -> Option<ReadStats> {
+        Some(self.stats.clone())
+    }
+}
 
-        if false {
-            log::debug!(
-                "read_iq_file: off_samples={} num_samples={}",
-                off_samples,
-                num_samples
+impl IQRecording {
+    pub fn new(file_path: &Path, fs: f64, file_type: &IQFileType) -> Result<Self, Box<dyn Error>> {
+        let file_size = file_path.metadata()?.len();
+        let compression = Compression::detect(file_path)?;
+        // zstd frames and the xz index record the size of the data, gzip only
+        // keeps it modulo 4 GB: it is known once the end of the file is read
+        let data_size = match compression {
+            Compression::None => Some(file_size),
+            Compression::Gzip => None,
+            Compression::Zstd => get_zstd_content_size(file_path)?,
+            Compression::Xz => get_xz_content_size(file_path)?,
+        };
+        let sample_size_bits = Self::get_sample_size_bits(file_type) as u64;
+        if let Some(data_size) = data_size
+            && !(data_size * 8).is_multiple_of(sample_size_bits)
+        {
+            log::warn!(
+                "{}: ends with a partial {file_type} sample",
+                file_path.display()
             );
         }
+        let num_samples = data_size.map(|size| (size * 8 / sample_size_bits) as usize);
+        let duration = match num_samples {
+            Some(n) => format!("{:.1} secs", n as f64 / fs),
+            None => "unknown".to_string(),
+        };
+
+        println!(
+            "file: {} -- {file_type} {} {compression} duration: {duration}",
+            file_path.display().to_string().green(),
+            ByteSize::b(file_size).to_string().bold(),
+        );
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            compression,
+            source: Source::open(file_path, compression)?,
+            file_type: file_type.clone(),
+            num_samples,
+            pos: 0,
+            buf: vec![],
+            stats: ReadStats::default(),
+        })
+    }
 
-        let _ = reader.seek(SeekFrom::Current(off_file as i64)).unwrap();
+    // None until the end of the data is reached, for gzip recordings
+    pub fn get_num_samples(&self) -> Option<usize> {
+        self.num_samples
+    }
 
-        loop {
-            let buf = reader.fill_buf()?;
-            let len = buf.len();
+    // Reads up to num_samples samples: fewer at the end of the file, whose
+    // number of samples is then known.
+    pub fn read_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn Error>> {
+        let ts = Instant::now();
+        // sub-byte samples may start in the middle of a byte
+        let bits = Self::get_sample_size_bits(&self.file_type);
+        let off_bits = off_samples * bits;
+        let off_file = (off_bits / 8) as u64;
+        let skip = off_bits % 8 / bits;
 
-            if len == 0 {
-                break;
+        if off_file != self.pos {
+            self.seek(off_file)?;
+            self.stats.num_seeks += 1;
+        }
+
+        self.buf
+            .resize(((skip + num_samples) * bits).div_ceil(8), 0);
+        let mut len = 0;
+        if self.pos == off_file {
+            while len < self.buf.len() {
+                let n = self.source.read(&mut self.buf[len..])?;
+                if n == 0 {
+                    break;
+                }
+                len += n;
             }
+        }
+        self.pos += len as u64;
+        self.stats.num_reads += 1;
+        self.stats.num_bytes += len as u64;
 
-            match self.file_type {
-                IQFileType::TypeRtlSdrFile => {
-                    for off in (0..len).step_by(2) {
-                        iq_vec.push(Complex64 {
-                            re: (buf[off] as f64 - 127.3) / 128.0,
-                            im: (buf[off + 1] as f64 - 127.3) / 128.0,
-                        });
-                        n += 1;
-                        if n >= num_samples {
-                            break;
-                        }
-                    }
+        if len < self.buf.len() && self.num_samples.is_none() {
+            self.num_samples = Some((self.pos * 8 / bits as u64) as usize);
+        }
+        let num_avail = self.num_samples.map_or(num_samples, |n| {
+            usize::min(num_samples, n.saturating_sub(off_samples))
+        });
+        let mut iq_vec = Self::decode(&self.buf[..len], &self.file_type);
+        iq_vec.drain(..usize::min(skip, iq_vec.len()));
+        iq_vec.truncate(num_avail);
+        self.stats.elapsed += ts.elapsed();
+
+        Ok(iq_vec)
+    }
+
+    // moves to the given offset of the uncompressed data, or to its end
+    fn seek(&mut self, off: u64) -> Result<(), Box<dyn Error>> {
+        if let Source::Raw(file) = &mut self.source {
+            file.seek(SeekFrom::Start(off))?;
+            self.pos = off;
+            return Ok(());
+        }
+        if off < self.pos {
+            self.source = Source::open(&self.file_path, self.compression)?;
+            self.pos = 0;
+        }
+        let n = io::copy(
+            &mut (&mut self.source).take(off - self.pos),
+            &mut io::sink(),
+        )?;
+        // short of off at the end of the data
+        self.pos += n;
+        Ok(())
+    }
+
+    // Complex samples to raw ones, the re part only for real formats. For
+    // i4 and i2, the last byte is padded when the samples don't fill it.
+    pub fn encode(iq_vec: &[Complex64], file_type: &IQFileType) -> Result<Vec<u8>, Box<dyn Error>> {
+        let bits = Self::get_sample_size_bits(file_type);
+        let mut buf = Vec::with_capacity(iq_vec.len() * bits / 8);
+        let to_i16 = |v: f64| {
+            (v * i16::MAX as f64)
+                .round()
+                .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+        };
+        let to_i8 = |v: f64| {
+            (v * i8::MAX as f64)
+                .round()
+                .clamp(i8::MIN as f64, i8::MAX as f64) as i8
+        };
+        let to_u8 = |v: f64| (v * 128.0 + 127.3).round().clamp(0.0, 255.0) as u8;
+        let to_i12 = |v: f64| (v * 2047.0).round().clamp(-2048.0, 2047.0) as i16;
+        // float samples are expected in [-1, 1] too
+        let to_f32 = |v: f64| v.clamp(-1.0, 1.0) as f32;
+
+        match file_type {
+            IQFileType::TypeOneInt4 => {
+                for pair in iq_vec.chunks(2) {
+                    let hi = f64_to_sign_mag(pair[0].re, 4);
+                    let lo = pair.get(1).map_or(0, |v| f64_to_sign_mag(v.re, 4));
+                    buf.push(hi << 4 | lo);
                 }
-                IQFileType::TypeOneInt8 => {
-                    for v in buf.iter().take(len) {
-                        iq_vec.push(Complex64 {
-                            re: *v as i8 as f64 / i8::MAX as f64,
-                            im: 0.0,
-                        });
-                        n += 1;
-                        if n >= num_samples {
-                            break;
-                        }
+                return Ok(buf);
+            }
+            IQFileType::TypeOneInt2 => {
+                for quad in iq_vec.chunks(4) {
+                    let mut b = 0;
+                    for k in 0..4 {
+                        let v = quad.get(k).map_or(0, |v| f64_to_sign_mag(v.re, 2));
+                        b |= v << (6 - 2 * k);
                     }
+                    buf.push(b);
+                }
+                return Ok(buf);
+            }
+            _ => {}
+        }
+
+        for v in iq_vec {
+            match file_type {
+                IQFileType::TypePairFloat32 => {
+                    buf.extend_from_slice(&to_f32(v.re).to_le_bytes());
+                    buf.extend_from_slice(&to_f32(v.im).to_le_bytes());
+                }
+                IQFileType::TypePairFloat32Be => {
+                    buf.extend_from_slice(&to_f32(v.re).to_be_bytes());
+                    buf.extend_from_slice(&to_f32(v.im).to_be_bytes());
                 }
                 IQFileType::TypePairInt16 => {
-                    for off in (0..len).step_by(4) {
-                        let i = i16::from_le_bytes([buf[off], buf[off + 1]]);
-                        let q = i16::from_le_bytes([buf[off + 2], buf[off + 3]]);
-                        iq_vec.push(Complex64 {
-                            re: i as f64 / i16::MAX as f64,
-                            im: q as f64 / i16::MAX as f64,
-                        });
-                        n += 1;
-                        if n >= num_samples {
-                            break;
-                        }
-                    }
+                    buf.extend_from_slice(&to_i16(v.re).to_le_bytes());
+                    buf.extend_from_slice(&to_i16(v.im).to_le_bytes());
                 }
-                IQFileType::TypePairFloat32 => {
-                    for off in (0..len).step_by(8) {
-                        let i = f32::from_le_bytes([
-                            buf[off],
-                            buf[off + 1],
-                            buf[off + 2],
-                            buf[off + 3],
-                        ]);
-                        let q = f32::from_le_bytes([
-                            buf[off + 4],
-                            buf[off + 5],
-                            buf[off + 6],
-                            buf[off + 7],
-                        ]);
-                        assert!((-1.0..=1.0).contains(&i));
-                        assert!((-1.0..=1.0).contains(&q));
-                        iq_vec.push(Complex64 {
-                            re: i as f64,
-                            im: q as f64,
-                        });
-                        n += 1;
-                        if n >= num_samples {
-                            break;
-                        }
-                    }
+                IQFileType::TypePairInt16Be => {
+                    buf.extend_from_slice(&to_i16(v.re).to_be_bytes());
+                    buf.extend_from_slice(&to_i16(v.im).to_be_bytes());
                 }
+                IQFileType::TypePairInt8 => {
+                    buf.extend_from_slice(&[to_i8(v.re) as u8, to_i8(v.im) as u8]);
+                }
+                IQFileType::TypeRtlSdrFile => {
+                    buf.extend_from_slice(&[to_u8(v.re), to_u8(v.im)]);
+                }
+                IQFileType::TypeOneInt8 => buf.push(to_i8(v.re) as u8),
+                IQFileType::TypePairInt12 => {
+                    buf.extend_from_slice(&i12_pack(to_i12(v.re), to_i12(v.im)))
+                }
+                IQFileType::TypePairFloat16 => {
+                    buf.extend_from_slice(&f64_to_f16(v.re.clamp(-1.0, 1.0)).to_le_bytes());
+                    buf.extend_from_slice(&f64_to_f16(v.im.clamp(-1.0, 1.0)).to_le_bytes());
+                }
+                IQFileType::TypeOneInt4 | IQFileType::TypeOneInt2 => unreachable!(),
             }
-            if n >= num_samples {
-                break;
-            }
-            reader.consume(len);
-        }
-        if n < num_samples {
-            return Err("end of file".into());
         }
-        assert_eq!(n, num_samples);
+        Ok(buf)
+    }
 
-        let bw = n as f64 * buf_size as f64 / 1024.0 / 1024.0 / ts.elapsed().as_secs_f64();
-        if false {
-            log::debug!(
-                "read_from_file: {} msec -- bandwidth: {:.1} MB/sec -- num_read_ops={}",
-                ts.elapsed().as_millis(),
-                bw,
-                n
-            );
+    // raw samples to complex ones, buf holding whole samples
+    pub fn decode(buf: &[u8], file_type: &IQFileType) -> Vec<Complex64> {
+        let sample_size = usize::max(1, Self::get_sample_size_bits(file_type) / 8);
+        let samples = buf.chunks_exact(sample_size);
+
+        match file_type {
+            IQFileType::TypeRtlSdrFile => samples
+                .map(|b| Complex64 {
+                    re: (b[0] as f64 - 127.3) / 128.0,
+                    im: (b[1] as f64 - 127.3) / 128.0,
+                })
+                .collect(),
+            IQFileType::TypePairInt8 => samples
+                .map(|b| Complex64 {
+                    re: b[0] as i8 as f64 / i8::MAX as f64,
+                    im: b[1] as i8 as f64 / i8::MAX as f64,
+                })
+                .collect(),
+            IQFileType::TypeOneInt8 => samples
+                .map(|b| Complex64 {
+                    re: b[0] as i8 as f64 / i8::MAX as f64,
+                    im: 0.0,
+                })
+                .collect(),
+            // msb first: the earliest sample in the high bits
+            IQFileType::TypeOneInt4 => buf
+                .iter()
+                .flat_map(|b| [b >> 4, b & 0xf])
+                .map(|v| Complex64::new(sign_mag_to_f64(v, 4), 0.0))
+                .collect(),
+            IQFileType::TypeOneInt2 => buf
+                .iter()
+                .flat_map(|b| [b >> 6, (b >> 4) & 3, (b >> 2) & 3, b & 3])
+                .map(|v| Complex64::new(sign_mag_to_f64(v, 2), 0.0))
+                .collect(),
+            IQFileType::TypePairInt12 => samples
+                .map(|b| {
+                    let (i, q) = i12_pair(b);
+                    Complex64 {
+                        re: i as f64 / 2047.0,
+                        im: q as f64 / 2047.0,
+                    }
+                })
+                .collect(),
+            IQFileType::TypePairInt16Be => samples
+                .map(|b| {
+                    let i = i16::from_be_bytes([b[0], b[1]]);
+                    let q = i16::from_be_bytes([b[2], b[3]]);
+                    Complex64 {
+                        re: i as f64 / i16::MAX as f64,
+                        im: q as f64 / i16::MAX as f64,
+                    }
+                })
+                .collect(),
+            IQFileType::TypePairFloat16 => samples
+                .map(|b| Complex64 {
+                    re: f16_to_f64(u16::from_le_bytes([b[0], b[1]])),
+                    im: f16_to_f64(u16::from_le_bytes([b[2], b[3]])),
+                })
+                .collect(),
+            IQFileType::TypePairFloat32Be => samples
+                .map(|b| Complex64 {
+                    re: f32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    im: f32::from_be_bytes([b[4], b[5], b[6], b[7]]) as f64,
+                })
+                .collect(),
+            IQFileType::TypePairInt16 => samples
+                .map(|b| {
+                    let i = i16::from_le_bytes([b[0], b[1]]);
+                    let q = i16::from_le_bytes([b[2], b[3]]);
+                    Complex64 {
+                        re: i as f64 / i16::MAX as f64,
+                        im: q as f64 / i16::MAX as f64,
+                    }
+                })
+                .collect(),
+            IQFileType::TypePairFloat32 => samples
+                .map(|b| {
+                    let i = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                    let q = f32::from_le_bytes([b[4], b[5], b[6], b[7]]);
+                    assert!((-1.0..=1.0).contains(&i));
+                    assert!((-1.0..=1.0).contains(&q));
+                    Complex64 {
+                        re: i as f64,
+                        im: q as f64,
+                    }
+                })
+                .collect(),
         }
+    }
 
-        Ok(iq_vec)
+    pub fn get_sample_size_bits(file_type: &IQFileType) -> usize {
+        match file_type {
+            IQFileType::TypeRtlSdrFile => 2 * 8,
+            IQFileType::TypePairInt8 => 2 * 8,
+            IQFileType::TypeOneInt8 => 8,
+            IQFileType::TypeOneInt4 => 4,
+            IQFileType::TypeOneInt2 => 2,
+            IQFileType::TypePairInt12 => 2 * 12,
+            IQFileType::TypePairInt16 | IQFileType::TypePairInt16Be => 2 * 16,
+            IQFileType::TypePairFloat16 => 2 * 16,
+            IQFileType::TypePairFloat32 | IQFileType::TypePairFloat32Be => 2 * 32,
+        }
     }
 }
 
-impl IQRecording {
-    pub fn new(file_path: &Path, fs: f64, file_type: &IQFileType) -> Self {
-        let file_size = file_path.metadata().unwrap().len();
-        let sample_size = Self::get_sample_size_bytes(file_type) as f64;
-        let recording_duration_sec = file_size as f64 / fs / sample_size;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        println!(
-            "file: {} -- {file_type} {} duration: {:.1} secs",
-            file_path.display().to_string().green(),
-            ByteSize::b(file_size).to_string().bold(),
-            recording_duration_sec
+    const ALL_TYPES: [&str; 11] = [
+        "2xf32",
+        "2xf32be",
+        "2xf16",
+        "2xi16",
+        "2xi16be",
+        "2xi12",
+        "2xi8",
+        "rtlsdr-file",
+        "i8",
+        "i4",
+        "i2",
+    ];
+
+    fn file_type(s: &str) -> IQFileType {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn f16_special_values() {
+        assert_eq!(f16_to_f64(0x3c00), 1.0);
+        assert_eq!(f16_to_f64(0xc000), -2.0);
+        assert_eq!(f16_to_f64(0x7bff), 65504.0);
+        // subnormals
+        assert_eq!(f16_to_f64(0x0001), 2f64.powi(-24));
+        assert_eq!(f16_to_f64(0x83ff), -1023.0 * 2f64.powi(-24));
+        assert_eq!(f16_to_f64(0x0400), 2f64.powi(-14));
+        assert_eq!(f16_to_f64(0x7c00), f64::INFINITY);
+        assert_eq!(f16_to_f64(0xfc00), f64::NEG_INFINITY);
+        assert!(f16_to_f64(0x7e00).is_nan());
+        assert!(f16_to_f64(0xfc01).is_nan());
+
+        assert_eq!(f64_to_f16(1.0), 0x3c00);
+        assert_eq!(f64_to_f16(-2.0), 0xc000);
+        assert_eq!(f64_to_f16(-0.0), 0x8000);
+        assert_eq!(f64_to_f16(2f64.powi(-24)), 0x0001);
+        assert_eq!(f64_to_f16(1023.0 * 2f64.powi(-24)), 0x03ff);
+        // rounded up to the smallest normal
+        assert_eq!(f64_to_f16(2f64.powi(-14) - 2f64.powi(-26)), 0x0400);
+        assert_eq!(f64_to_f16(65504.0), 0x7bff);
+        assert_eq!(f64_to_f16(65520.0), 0x7c00);
+        assert_eq!(f64_to_f16(f64::NEG_INFINITY), 0xfc00);
+        assert_eq!(f64_to_f16(f64::NAN), 0x7e00);
+    }
+
+    #[test]
+    fn i12_sign_extension() {
+        assert_eq!(i12_pair(&[0xff, 0xf7, 0x7f]), (2047, 2047));
+        assert_eq!(i12_pair(&[0x00, 0x08, 0x80]), (-2048, -2048));
+        assert_eq!(i12_pair(&[0xff, 0xff, 0xff]), (-1, -1));
+        // I in the low 12 bits
+        assert_eq!(i12_pair(&[0x01, 0xf0, 0xff]), (1, -1));
+        assert_eq!(i12_pack(1, -1), [0x01, 0xf0, 0xff]);
+        assert_eq!(i12_pack(-2048, 2047), [0x00, 0xf8, 0x7f]);
+
+        let iq = IQRecording::decode(&[0x00, 0x08, 0x80], &file_type("2xi12"));
+        assert_eq!(iq, vec![Complex64::new(-2048.0 / 2047.0, -2048.0 / 2047.0)]);
+    }
+
+    #[test]
+    fn sign_magnitude_levels() {
+        let levels: Vec<_> = (0..4).map(|v| sign_mag_to_f64(v, 2)).collect();
+        assert_eq!(levels, vec![1.0 / 3.0, 1.0, -1.0 / 3.0, -1.0]);
+        assert_eq!(sign_mag_to_f64(0x0, 4), 1.0 / 15.0);
+        assert_eq!(sign_mag_to_f64(0x7, 4), 1.0);
+        assert_eq!(sign_mag_to_f64(0x8, 4), -1.0 / 15.0);
+        assert_eq!(sign_mag_to_f64(0xf, 4), -1.0);
+        for bits in [2, 4] {
+            for v in 0..1 << bits {
+                assert_eq!(f64_to_sign_mag(sign_mag_to_f64(v, bits), bits), v);
+            }
+        }
+
+        // msb first: the earliest sample in the high bits
+        let re = |iq: Vec<Complex64>| iq.iter().map(|v| v.re).collect::<Vec<_>>();
+        let iq = IQRecording::decode(&[0x1b], &file_type("i2"));
+        assert_eq!(re(iq), vec![1.0 / 3.0, 1.0, -1.0 / 3.0, -1.0]);
+        let iq = IQRecording::decode(&[0x7f, 0x80], &file_type("i4"));
+        assert_eq!(re(iq), vec![1.0, -1.0, -1.0 / 15.0, 1.0 / 15.0]);
+
+        // the last byte is padded with level 0
+        let iq = [Complex64::new(-1.0, 0.0)];
+        assert_eq!(IQRecording::encode(&iq, &file_type("i2")).unwrap(), [0xc0]);
+        assert_eq!(IQRecording::encode(&iq, &file_type("i4")).unwrap(), [0xf0]);
+    }
+
+    #[test]
+    fn byte_order() {
+        let le = IQRecording::decode(&[0x02, 0x01, 0xff, 0x7f], &file_type("2xi16"));
+        let be = IQRecording::decode(&[0x01, 0x02, 0x7f, 0xff], &file_type("2xi16be"));
+        assert_eq!(le, vec![Complex64::new(258.0 / 32767.0, 1.0)]);
+        assert_eq!(le, be);
+
+        let le = IQRecording::decode(&[0, 0, 0, 0x3f, 0, 0, 0, 0xbf], &file_type("2xf32"));
+        let be = IQRecording::decode(&[0x3f, 0, 0, 0, 0xbf, 0, 0, 0], &file_type("2xf32be"));
+        assert_eq!(le, vec![Complex64::new(0.5, -0.5)]);
+        assert_eq!(le, be);
+
+        let iq = IQRecording::decode(&[0x00, 0x38, 0x00, 0xb8], &file_type("2xf16"));
+        assert_eq!(iq, vec![Complex64::new(0.5, -0.5)]);
+    }
+
+    #[test]
+    fn int8_formats() {
+        let iq = IQRecording::decode(&[0x81, 0x7f], &file_type("2xi8"));
+        assert_eq!(iq, vec![Complex64::new(-1.0, 1.0)]);
+        let iq = IQRecording::decode(&[0x81, 0x7f], &file_type("i8"));
+        assert_eq!(
+            iq,
+            vec![Complex64::new(-1.0, 0.0), Complex64::new(1.0, 0.0)]
         );
-        Self {
-            file_path: file_path.to_path_buf(),
-            file_type: file_type.clone(),
+        let iq = IQRecording::decode(&[0, 255], &file_type("rtlsdr-file"));
+        assert_eq!(iq, vec![Complex64::new(-127.3 / 128.0, 127.7 / 128.0)]);
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for s in ALL_TYPES {
+            let ft = file_type(s);
+            let iq: Vec<_> = (0..=100)
+                .map(|k| {
+                    let v = k as f64 / 50.0 - 1.0;
+                    Complex64::new(v, if ft.is_real() { 0.0 } else { -0.7 * v })
+                })
+                .collect();
+            let buf = IQRecording::encode(&iq, &ft).unwrap();
+            assert_eq!(
+                buf.len(),
+                (iq.len() * IQRecording::get_sample_size_bits(&ft)).div_ceil(8),
+                "{s}"
+            );
+            let res = IQRecording::decode(&buf, &ft);
+            // half a quantization step
+            let tol = match s {
+                "2xf32" | "2xf32be" => 1e-7,
+                "2xf16" => 2f64.powi(-11),
+                "2xi16" | "2xi16be" => 0.5 / 32767.0,
+                "2xi12" => 0.5 / 2047.0,
+                "2xi8" | "i8" => 0.5 / 127.0,
+                "rtlsdr-file" => 0.5 / 128.0,
+                "i4" => 1.0 / 15.0,
+                _ => 1.0 / 3.0,
+            };
+            for (a, b) in iq.iter().zip(res.iter()) {
+                assert!((a - b).norm() <= tol * 2f64.sqrt() + 1e-12, "{s}: {a} {b}");
+            }
         }
     }
 
-    fn get_sample_size_bytes(file_type: &IQFileType) -> usize {
-        match file_type {
-            IQFileType::TypeRtlSdrFile => 2,
-            IQFileType::TypeOneInt8 => 1,
-            IQFileType::TypePairInt16 => 2 * 2,
-            IQFileType::TypePairFloat32 => 2 * 4,
+    #[test]
+    fn encode_saturates() {
+        // e.g. simulated signal plus noise peaks: 2xf32 decoding rejects
+        // samples out of [-1, 1]
+        let iq = [Complex64::new(1.7, -3.2), Complex64::new(-1.0001, 0.5)];
+        for s in ALL_TYPES {
+            let ft = file_type(s);
+            let res = IQRecording::decode(&IQRecording::encode(&iq, &ft).unwrap(), &ft);
+            assert!(res[0].re > 0.9 && res[1].re < -0.9, "{s}");
+            if matches!(s, "2xf32" | "2xf32be" | "2xf16") {
+                for v in &res {
+                    assert!(v.re.abs() <= 1.0 && v.im.abs() <= 1.0, "{s}: {v}");
+                }
+            }
+        }
+    }
+
+    // the same 2xi16 samples, raw and compressed with each format
+    fn write_recordings(name: &str, iq: &[Complex64]) -> Vec<PathBuf> {
+        use std::io::Write;
+
+        let buf = IQRecording::encode(iq, &IQFileType::TypePairInt16).unwrap();
+        let (half0, half1) = buf.split_at(buf.len() / 2);
+        let dir = std::env::temp_dir();
+        let paths: Vec<PathBuf> = ["raw", "gz", "zst", "xz"]
+            .iter()
+            .map(|ext| dir.join(format!("gnss-rcv-{name}-{}.{ext}", std::process::id())))
+            .collect();
+
+        std::fs::write(&paths[0], &buf).unwrap();
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(&buf).unwrap();
+        std::fs::write(&paths[1], gz.finish().unwrap()).unwrap();
+        // two frames recording their size, then two streams
+        let mut zst = zstd::bulk::compress(half0, 3).unwrap();
+        zst.extend(zstd::bulk::compress(half1, 3).unwrap());
+        std::fs::write(&paths[2], zst).unwrap();
+        let mut xz = vec![];
+        for half in [half0, half1] {
+            let mut enc = xz2::write::XzEncoder::new(vec![], 6);
+            enc.write_all(half).unwrap();
+            xz.extend(enc.finish().unwrap());
+        }
+        std::fs::write(&paths[3], xz).unwrap();
+        paths
+    }
+
+    #[test]
+    fn compressed_num_samples() {
+        let iq: Vec<_> = (0..10000)
+            .map(|k| Complex64::new((k as f64 * 0.01).sin(), (k as f64 * 0.01).cos()))
+            .collect();
+        let ft = IQFileType::TypePairInt16;
+        for path in write_recordings("size", &iq) {
+            let mut rec = IQRecording::new(&path, 1e6, &ft).unwrap();
+            let is_gzip = path.extension().unwrap() == "gz";
+            let expected = if is_gzip { None } else { Some(iq.len()) };
+            assert_eq!(rec.get_num_samples(), expected, "{}", path.display());
+
+            // a read past the end returns what is left and the length
+            assert_eq!(rec.read_iq_data(9000, 2000).unwrap().len(), 1000);
+            assert_eq!(rec.get_num_samples(), Some(iq.len()));
+            assert!(rec.get_iq_data(9000, 2000).is_err());
+            let v = rec.get_iq_data(100, 10).unwrap();
+            assert!((v[0] - iq[100]).norm() < 1e-4);
+            std::fs::remove_file(&path).unwrap();
         }
     }
 }
@@ -0,0 +1,288 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use gnss_rs::constellation::Constellation;
+use gnss_rs::sv::SV;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::almanac::IonoUtc;
+use crate::constants::SECS_PER_WEEK;
+use crate::ephemeris::Ephemeris;
+
+const FIELD_LEN: usize = 19;
+// URA index: the upper bound of each accuracy range, in meters
+const URA_EPH: [f64; 15] = [
+    2.4, 3.4, 4.85, 6.85, 9.65, 13.65, 24.0, 48.0, 96.0, 192.0, 384.0, 768.0, 1536.0, 3072.0,
+    6144.0,
+];
+
+// GPS week and second of week of a GPS time, e.g. a RINEX epoch
+pub fn get_gps_week_sow(t: &NaiveDateTime) -> (u32, u32) {
+    let gps_epoch = NaiveDate::from_ymd_opt(1980, 1, 6)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let secs = (*t - gps_epoch).num_seconds();
+    let week_secs = SECS_PER_WEEK as i64;
+    ((secs / week_secs) as u32, (secs % week_secs) as u32)
+}
+
+// "-.839701388031D-03": Fortran exponents, blank for 0
+fn parse_field_len(line: &str, pos: usize, len: usize) -> Result<f64, Box<dyn Error>> {
+    let field = line.get(pos..line.len().min(pos + len)).unwrap_or("");
+    let field = field.trim().replace(['D', 'd'], "E");
+    if field.is_empty() {
+        return Ok(0.0);
+    }
+    Ok(field.parse()?)
+}
+
+fn parse_field(line: &str, pos: usize) -> Result<f64, Box<dyn Error>> {
+    parse_field_len(line, pos, FIELD_LEN)
+}
+
+fn get_ura_index(accuracy: f64) -> u32 {
+    URA_EPH
+        .iter()
+        .position(|&a| accuracy <= a)
+        .unwrap_or(URA_EPH.len()) as u32
+}
+
+// One record: the line of the SV/epoch/clock, then the 7 broadcast orbit
+// lines (RINEX 2.11 and 3.x, section 6 / table A4)
+fn parse_record(lines: &[&str], version: u32) -> Result<Ephemeris, Box<dyn Error>> {
+    if lines.len() < 8 {
+        return Err("truncated record".into());
+    }
+    let (epoch_end, orbit_pos) = if version >= 3 { (23, 4) } else { (22, 3) };
+    let first = lines[0];
+    let mut items = first
+        .get(..epoch_end)
+        .ok_or("truncated record")?
+        .split_whitespace();
+    let prn: u8 = items
+        .next()
+        .ok_or("no PRN")?
+        .trim_start_matches('G')
+        .parse()?;
+    let mut epoch = [0.0; 6];
+    for v in epoch.iter_mut() {
+        *v = items.next().ok_or("truncated epoch")?.parse()?;
+    }
+    let year = match epoch[0] as i32 {
+        y if y < 80 => y + 2000,
+        y if y < 100 => y + 1900,
+        y => y,
+    };
+    let toc = NaiveDate::from_ymd_opt(year, epoch[1] as u32, epoch[2] as u32)
+        .and_then(|d| d.and_hms_opt(epoch[3] as u32, epoch[4] as u32, epoch[5] as u32))
+        .ok_or("invalid epoch")?;
+
+    let mut orbit = [[0.0; 4]; 7];
+    for (i, row) in orbit.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = parse_field(lines[i + 1], orbit_pos + j * FIELD_LEN)?;
+        }
+    }
+    let mut eph = Ephemeris::new(SV::new(Constellation::GPS, prn));
+    eph.toc = get_gps_week_sow(&toc).1;
+    eph.f0 = parse_field(first, epoch_end)?;
+    eph.f1 = parse_field(first, epoch_end + FIELD_LEN)?;
+    eph.f2 = parse_field(first, epoch_end + 2 * FIELD_LEN)?;
+
+    eph.iode = orbit[0][0] as u32;
+    eph.crs = orbit[0][1];
+    eph.deln = orbit[0][2];
+    eph.m0 = orbit[0][3];
+    eph.cuc = orbit[1][0];
+    eph.ecc = orbit[1][1];
+    eph.cus = orbit[1][2];
+    eph.a = orbit[1][3].powi(2);
+    eph.toe = orbit[2][0] as u32;
+    eph.cic = orbit[2][1];
+    eph.omg0 = orbit[2][2];
+    eph.cis = orbit[2][3];
+    eph.i0 = orbit[3][0];
+    eph.crc = orbit[3][1];
+    eph.omg = orbit[3][2];
+    eph.omg_dot = orbit[3][3];
+    eph.i_dot = orbit[4][0];
+    eph.code = orbit[4][1] as u32;
+    eph.week = orbit[4][2] as u32;
+    eph.flag = orbit[4][3] as u32;
+    eph.sva = get_ura_index(orbit[5][0]);
+    eph.svh = orbit[5][1] as u32;
+    eph.tgd = orbit[5][2];
+    eph.iodc = orbit[5][3] as u32;
+    // fit interval in hours: the LNAV flag is set beyond 4 hours
+    eph.fit = (orbit[6][1] > 4.0) as u32;
+    Ok(eph)
+}
+
+// Klobuchar and GPS-UTC parameters of the header of a RINEX navigation file,
+// as in page 18 of LNAV subframe 4. Zero when the header doesn't have them.
+pub fn load_rinex_iono_utc(path: &Path) -> Result<IonoUtc, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut iono_utc = IonoUtc::default();
+    for line in content.lines() {
+        let label = line.get(60..).unwrap_or("").trim();
+        match label {
+            // RINEX 2
+            "ION ALPHA" | "ION BETA" => {
+                let off = if label == "ION ALPHA" { 0 } else { 4 };
+                for i in 0..4 {
+                    iono_utc.ion[off + i] = parse_field_len(line, 2 + 12 * i, 12)?;
+                }
+            }
+            "DELTA-UTC: A0,A1,T,W" => {
+                iono_utc.utc[0] = parse_field(line, 3)?;
+                iono_utc.utc[1] = parse_field(line, 22)?;
+                iono_utc.utc[2] = parse_field_len(line, 41, 9)?;
+                iono_utc.utc[3] = parse_field_len(line, 50, 9)?;
+            }
+            // RINEX 3
+            "IONOSPHERIC CORR" if line.starts_with("GPSA") || line.starts_with("GPSB") => {
+                let off = if line.starts_with("GPSA") { 0 } else { 4 };
+                for i in 0..4 {
+                    iono_utc.ion[off + i] = parse_field_len(line, 5 + 12 * i, 12)?;
+                }
+            }
+            "TIME SYSTEM CORR" if line.starts_with("GPUT") => {
+                iono_utc.utc[0] = parse_field_len(line, 5, 17)?;
+                iono_utc.utc[1] = parse_field_len(line, 22, 16)?;
+                iono_utc.utc[2] = parse_field_len(line, 38, 7)?;
+                iono_utc.utc[3] = parse_field_len(line, 45, 5)?;
+            }
+            // dt_LS, then dt_LSF, WN_LSF and DN in RINEX 3
+            "LEAP SECONDS" => {
+                iono_utc.utc[4] = parse_field_len(line, 0, 6)?;
+                iono_utc.utc[7] = parse_field_len(line, 6, 6)?;
+                iono_utc.utc[5] = parse_field_len(line, 12, 6)?;
+                iono_utc.utc[6] = parse_field_len(line, 18, 6)?;
+                if iono_utc.utc[7] == 0.0 {
+                    iono_utc.utc[7] = iono_utc.utc[4];
+                }
+            }
+            "END OF HEADER" => break,
+            _ => {}
+        }
+    }
+    Ok(iono_utc)
+}
+
+// The GPS ephemerides of a RINEX navigation file, versions 2 and 3. The
+// records of the other constellations of a mixed file are skipped.
+pub fn load_rinex_nav(path: &Path) -> Result<Vec<Ephemeris>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut lines = content.lines();
+    let mut version = 0;
+    for line in lines.by_ref() {
+        if line.contains("RINEX VERSION / TYPE") {
+            let v: f64 = line.get(..9).unwrap_or("").trim().parse()?;
+            version = v as u32;
+        }
+        if line.contains("END OF HEADER") {
+            break;
+        }
+    }
+    if version == 0 {
+        return Err(format!("{}: not a RINEX file", path.display()).into());
+    }
+
+    // records start with the SV, orbit lines with blanks
+    let lines: Vec<&str> = lines.filter(|l| !l.trim().is_empty()).collect();
+    let is_start = |l: &str| {
+        if version >= 3 {
+            !l.starts_with(' ')
+        } else {
+            !l.get(..2).unwrap_or("").trim().is_empty()
+        }
+    };
+    let mut ephs = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let len = 1 + lines[i + 1..].iter().take_while(|l| !is_start(l)).count();
+        let record = &lines[i..i + len];
+        i += len;
+        if version >= 3 && !record[0].starts_with('G') {
+            continue;
+        }
+        match parse_record(record, version) {
+            Ok(eph) => ephs.push(eph),
+            Err(e) => log::warn!("{}: skipping record: {e}", path.display()),
+        }
+    }
+    log::warn!(
+        "{}: RINEX {version}: {} GPS ephemerides",
+        path.display(),
+        ephs.len()
+    );
+    Ok(ephs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RINEX2_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/rinex/example.99n");
+    const RINEX3_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/rinex/example.rnx");
+
+    #[test]
+    fn rinex_nav() {
+        let ephs2 = load_rinex_nav(Path::new(RINEX2_FILE)).unwrap();
+        // the Galileo record of the mixed file is skipped
+        let ephs3 = load_rinex_nav(Path::new(RINEX3_FILE)).unwrap();
+        assert_eq!(ephs2.len(), 2);
+        assert_eq!(ephs3.len(), 2);
+
+        for (e2, e3) in ephs2.iter().zip(&ephs3) {
+            assert_eq!(e2.sv, e3.sv);
+            assert_eq!((e2.week, e2.toe, e2.toc), (1025, 409904, 409904));
+            assert_eq!((e2.week, e2.toe, e2.toc), (e3.week, e3.toe, e3.toc));
+            assert_eq!((e2.iode, e2.iodc, e2.sva), (e3.iode, e3.iodc, e3.sva));
+            for (v2, v3) in [
+                (e2.f0, e3.f0),
+                (e2.m0, e3.m0),
+                (e2.ecc, e3.ecc),
+                (e2.a, e3.a),
+                (e2.omg0, e3.omg0),
+                (e2.tgd, e3.tgd),
+            ] {
+                assert!((v2 - v3).abs() <= 1e-12 * v2.abs(), "{}: {v2} {v3}", e2.sv);
+            }
+        }
+        let eph = &ephs2[0];
+        assert_eq!(eph.sv, SV::new(Constellation::GPS, 6));
+        assert_eq!((eph.iode, eph.iodc, eph.sva, eph.svh), (91, 91, 0, 0));
+        assert_eq!(eph.f0, -0.839701388031e-03);
+        assert_eq!(eph.a, 5153.65489006f64.powi(2));
+        assert_eq!(eph.i0, 1.11541663136);
+        let eph = &ephs2[1];
+        assert_eq!(eph.sv, SV::new(Constellation::GPS, 9));
+        assert_eq!((eph.iode, eph.sva), (12, 0));
+        assert_eq!(eph.tgd, -0.698491930962e-08);
+    }
+
+    #[test]
+    fn rinex_iono_utc() {
+        for file in [RINEX2_FILE, RINEX3_FILE] {
+            let iono_utc = load_rinex_iono_utc(Path::new(file)).unwrap();
+            assert_eq!(
+                iono_utc.ion,
+                [
+                    1.676e-8, 2.235e-8, -1.192e-7, -1.192e-7, 1.208e5, 1.31e5, -1.31e5, -1.966e5
+                ],
+                "{file}"
+            );
+            // A1 only has 10 digits in RINEX 3
+            assert_eq!(iono_utc.utc[0], 1.33179128170e-7, "{file}");
+            assert!(
+                (iono_utc.utc[1] - 1.07469588780e-13).abs() < 1e-22,
+                "{file}"
+            );
+            assert_eq!(iono_utc.utc[2..4], [552960.0, 1025.0], "{file}");
+            // dt_LSF defaults to dt_LS
+            assert_eq!((iono_utc.utc[4], iono_utc.utc[7]), (13.0, 13.0));
+        }
+    }
+}
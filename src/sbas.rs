@@ -0,0 +1,789 @@
+use colored::Colorize;
+use gnss_rs::constellation::Constellation;
+use gnss_rs::sv::SV;
+
+use crate::{
+    code::QZSS_PRN_OFFSET,
+    constants::{P2_11, P2_31, P2_39},
+    util::{getbits, getbitu},
+};
+
+pub const SBAS_MSG_LEN: usize = 250; // bits, incl. 24-bit CRC
+pub const SBAS_PREAMBLES: [u32; 3] = [0x53, 0x9a, 0xc6];
+
+pub const UDREI_NOT_MONITORED: u32 = 14;
+pub const UDREI_DO_NOT_USE: u32 = 15;
+pub const GIVEI_NOT_MONITORED: u32 = 15;
+
+// fast correction time-out interval by degradation factor indicator, en route
+// through non-precision approach (DO-229, table A-8)
+const FCORR_TIMEOUT: [f64; 16] = [
+    180.0, 180.0, 153.0, 135.0, 135.0, 117.0, 99.0, 81.0, 63.0, 45.0, 45.0, 27.0, 27.0, 27.0, 18.0,
+    18.0,
+];
+const LCORR_TIMEOUT: f64 = 360.0;
+const IGP_TIMEOUT: f64 = 600.0;
+
+const IONO_HEIGHT: f64 = 350_000.0; // ionospheric pierce point height (m)
+const EARTH_RADIUS: f64 = 6_378_136.3; // (m)
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasFastCorr {
+    pub ts_sec: f64, // receiver time of reception
+    pub prc: f64,    // pseudorange correction (m)
+    pub udrei: u32,  // user differential range error indicator
+    pub iodf: u32,   // issue of data fast correction
+    pub ai: u32,     // degradation factor indicator
+    pub rrc: f64,    // range-rate correction (m/s)
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasLongCorr {
+    pub ts_sec: f64,
+    pub iode: u32,
+    pub dpos: [f64; 3], // delta position ecef (m)
+    pub dvel: [f64; 3], // delta velocity ecef (m/s)
+    pub daf0: f64,      // delta clock offset (s)
+    pub daf1: f64,      // delta clock drift (s/s)
+    pub t0: u32,        // time of applicability (s of day)
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasSat {
+    pub sv: Option<SV>, // satellite of the PRN mask slot, if supported
+    pub fcorr: SbasFastCorr,
+    pub lcorr: SbasLongCorr,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasGeoEph {
+    pub ts_sec: f64,
+    pub t0: u32, // s of day
+    pub sva: u32,
+    pub svh: u32,
+    pub pos: [f64; 3],
+    pub vel: [f64; 3],
+    pub acc: [f64; 3],
+    pub af0: f64,
+    pub af1: f64,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasGeoAlm {
+    pub data_id: u32,
+    pub prn: u32,
+    pub health: u32,
+    pub pos: [f64; 3],
+    pub vel: [f64; 3],
+    pub t0: u32,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SbasIgp {
+    pub lat: i16,
+    pub lon: i16,
+    pub ts_sec: f64,
+    pub delay: f64, // vertical delay (m), 0.0 if not monitored
+    pub givei: u32, // grid ionospheric vertical error indicator
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct SbasIonoBand {
+    pub iodi: u32,
+    pub igp: Vec<SbasIgp>,
+}
+
+// Corrections broadcast by one SBAS GEO satellite.
+#[derive(Clone, Debug)]
+pub struct Sbas {
+    pub iodp: u32, // issue of data PRN mask
+    pub tlat: u32, // system latency (s)
+    pub sats: Vec<SbasSat>,
+    pub geo_eph: SbasGeoEph,
+    pub geo_alm: Vec<SbasGeoAlm>,
+    pub iono: Vec<SbasIonoBand>, // indexed by IGP band number
+}
+
+impl Default for Sbas {
+    fn default() -> Self {
+        Self {
+            iodp: 0,
+            tlat: 0,
+            sats: vec![],
+            geo_eph: SbasGeoEph::default(),
+            geo_alm: vec![],
+            iono: vec![SbasIonoBand::default(); IGP_BAND.len()],
+        }
+    }
+}
+
+/* IGP latitudes for the different longitudes of bands 0-8 (DO-229 A.4.4.9) */
+const IGP_LAT_1: &[i16] = &[
+    -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35,
+    40, 45, 50, 55, 65, 75, 85,
+];
+const IGP_LAT_2: &[i16] = &[
+    -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50,
+    55,
+];
+const IGP_LAT_3: &[i16] = &[
+    -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35,
+    40, 45, 50, 55, 65, 75,
+];
+const IGP_LAT_4: &[i16] = &[
+    -85, -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30,
+    35, 40, 45, 50, 55, 65, 75,
+];
+
+type IgpColumn = (i16, &'static [i16], usize); // longitude, latitudes, first mask bit
+
+const IGP_BAND: [[IgpColumn; 8]; 9] = [
+    [
+        (-180, IGP_LAT_1, 1),
+        (-175, IGP_LAT_2, 29),
+        (-170, IGP_LAT_3, 52),
+        (-165, IGP_LAT_2, 79),
+        (-160, IGP_LAT_3, 102),
+        (-155, IGP_LAT_2, 129),
+        (-150, IGP_LAT_3, 152),
+        (-145, IGP_LAT_2, 179),
+    ],
+    [
+        (-140, IGP_LAT_4, 1),
+        (-135, IGP_LAT_2, 29),
+        (-130, IGP_LAT_3, 52),
+        (-125, IGP_LAT_2, 79),
+        (-120, IGP_LAT_3, 102),
+        (-115, IGP_LAT_2, 129),
+        (-110, IGP_LAT_3, 152),
+        (-105, IGP_LAT_2, 179),
+    ],
+    [
+        (-100, IGP_LAT_3, 1),
+        (-95, IGP_LAT_2, 28),
+        (-90, IGP_LAT_1, 51),
+        (-85, IGP_LAT_2, 79),
+        (-80, IGP_LAT_3, 102),
+        (-75, IGP_LAT_2, 129),
+        (-70, IGP_LAT_3, 152),
+        (-65, IGP_LAT_2, 179),
+    ],
+    [
+        (-60, IGP_LAT_3, 1),
+        (-55, IGP_LAT_2, 28),
+        (-50, IGP_LAT_4, 51),
+        (-45, IGP_LAT_2, 79),
+        (-40, IGP_LAT_3, 102),
+        (-35, IGP_LAT_2, 129),
+        (-30, IGP_LAT_3, 152),
+        (-25, IGP_LAT_2, 179),
+    ],
+    [
+        (-20, IGP_LAT_3, 1),
+        (-15, IGP_LAT_2, 28),
+        (-10, IGP_LAT_3, 51),
+        (-5, IGP_LAT_2, 78),
+        (0, IGP_LAT_1, 101),
+        (5, IGP_LAT_2, 129),
+        (10, IGP_LAT_3, 152),
+        (15, IGP_LAT_2, 179),
+    ],
+    [
+        (20, IGP_LAT_3, 1),
+        (25, IGP_LAT_2, 28),
+        (30, IGP_LAT_3, 51),
+        (35, IGP_LAT_2, 78),
+        (40, IGP_LAT_4, 101),
+        (45, IGP_LAT_2, 129),
+        (50, IGP_LAT_3, 152),
+        (55, IGP_LAT_2, 179),
+    ],
+    [
+        (60, IGP_LAT_3, 1),
+        (65, IGP_LAT_2, 28),
+        (70, IGP_LAT_3, 51),
+        (75, IGP_LAT_2, 78),
+        (80, IGP_LAT_3, 101),
+        (85, IGP_LAT_2, 128),
+        (90, IGP_LAT_1, 151),
+        (95, IGP_LAT_2, 179),
+    ],
+    [
+        (100, IGP_LAT_3, 1),
+        (105, IGP_LAT_2, 28),
+        (110, IGP_LAT_3, 51),
+        (115, IGP_LAT_2, 78),
+        (120, IGP_LAT_3, 101),
+        (125, IGP_LAT_2, 128),
+        (130, IGP_LAT_4, 151),
+        (135, IGP_LAT_2, 179),
+    ],
+    [
+        (140, IGP_LAT_3, 1),
+        (145, IGP_LAT_2, 28),
+        (150, IGP_LAT_3, 51),
+        (155, IGP_LAT_2, 78),
+        (160, IGP_LAT_3, 101),
+        (165, IGP_LAT_2, 128),
+        (170, IGP_LAT_3, 151),
+        (175, IGP_LAT_2, 178),
+    ],
+];
+
+fn get_igp_position(band: usize, bit: usize) -> Option<(i16, i16)> {
+    for (lon, lats, first) in IGP_BAND[band] {
+        if bit >= first && bit < first + lats.len() {
+            return Some((lats[bit - first], lon));
+        }
+    }
+    None
+}
+
+// satellite associated with a PRN mask slot
+fn get_mask_sv(slot: usize) -> Option<SV> {
+    match slot {
+        1..=37 => Some(SV::new(Constellation::GPS, slot as u8)),
+        38..=61 => Some(SV::new(Constellation::Glonass, (slot - 37) as u8)),
+        120..=158 => Some(SV::new(Constellation::GPS, slot as u8)),
+        193..=202 => Some(SV::new(Constellation::QZSS, slot as u8 - QZSS_PRN_OFFSET)),
+        _ => None,
+    }
+}
+
+impl SbasFastCorr {
+    // the range-rate correction is derived from two successive corrections
+    fn update(&mut self, ts_sec: f64, prc: f64, udrei: u32, iodf: u32) {
+        let dt = ts_sec - self.ts_sec;
+        self.rrc = if self.ts_sec != 0.0 && dt > 0.0 && dt <= self.get_timeout() {
+            (prc - self.prc) / dt
+        } else {
+            0.0
+        };
+        self.ts_sec = ts_sec;
+        self.prc = prc;
+        self.udrei = udrei;
+        self.iodf = iodf;
+    }
+
+    fn get_timeout(&self) -> f64 {
+        FCORR_TIMEOUT[self.ai as usize & 0xf]
+    }
+
+    // pseudorange correction at receiver time ts_sec, None once timed out
+    pub fn get_prc(&self, ts_sec: f64) -> Option<f64> {
+        let dt = ts_sec - self.ts_sec;
+        if self.udrei >= UDREI_NOT_MONITORED || dt > self.get_timeout() {
+            return None;
+        }
+        Some(self.prc + self.rrc * dt)
+    }
+}
+
+impl SbasLongCorr {
+    // satellite position (m) and clock (s) corrections at receiver time
+    // ts_sec and GPS time of day tod, None once timed out
+    pub fn get_corr(&self, ts_sec: f64, tod: f64) -> Option<([f64; 3], f64)> {
+        if ts_sec - self.ts_sec > LCORR_TIMEOUT {
+            return None;
+        }
+        let mut dt = tod - self.t0 as f64;
+        if dt > 43200.0 {
+            dt -= 86400.0;
+        } else if dt < -43200.0 {
+            dt += 86400.0;
+        }
+        // dvel and daf1 are zero with velocity code 0
+        let dpos = [
+            self.dpos[0] + self.dvel[0] * dt,
+            self.dpos[1] + self.dvel[1] * dt,
+            self.dpos[2] + self.dvel[2] * dt,
+        ];
+        Some((dpos, self.daf0 + self.daf1 * dt))
+    }
+}
+
+impl Sbas {
+    pub fn get_fast_corr(&self, sv: SV) -> Option<&SbasFastCorr> {
+        self.sats
+            .iter()
+            .find(|sat| sat.sv == Some(sv) && sat.fcorr.ts_sec != 0.0)
+            .map(|sat| &sat.fcorr)
+    }
+
+    pub fn get_long_corr(&self, sv: SV) -> Option<&SbasLongCorr> {
+        self.sats
+            .iter()
+            .find(|sat| sat.sv == Some(sv) && sat.lcorr.ts_sec != 0.0)
+            .map(|sat| &sat.lcorr)
+    }
+
+    // vertical delay of the IGP at lat/lon, if monitored
+    fn get_igp_delay(&self, lat: i16, lon: i16, ts_sec: f64) -> Option<f64> {
+        self.iono
+            .iter()
+            .flat_map(|band| band.igp.iter())
+            .find(|igp| igp.lat == lat && igp.lon == lon)
+            .filter(|igp| {
+                igp.ts_sec != 0.0
+                    && ts_sec - igp.ts_sec <= IGP_TIMEOUT
+                    && igp.givei < GIVEI_NOT_MONITORED
+            })
+            .map(|igp| igp.delay)
+    }
+
+    // vertical delay at the pierce point, interpolated over the cell of
+    // 4 IGPs whose south-west corner is lat0/lon0 (DO-229 A.4.4.10.3)
+    #[allow(clippy::too_many_arguments)]
+    fn get_cell_delay(
+        &self,
+        lat: f64,
+        lon: f64,
+        lat0: f64,
+        lon0: f64,
+        dlat: f64,
+        dlon: f64,
+        ts_sec: f64,
+    ) -> Option<f64> {
+        let wrap = |lon: f64| if lon >= 180.0 { lon - 360.0 } else { lon };
+        let x = (lon - lon0) / dlon;
+        let y = (lat - lat0) / dlat;
+        let cell = [
+            (lat0, lon0, (1.0 - x) * (1.0 - y)),
+            (lat0, wrap(lon0 + dlon), x * (1.0 - y)),
+            (lat0 + dlat, wrap(lon0 + dlon), x * y),
+            (lat0 + dlat, lon0, (1.0 - x) * y),
+        ];
+        let mut delay = 0.0;
+        for (lat, lon, w) in cell {
+            delay += w * self.get_igp_delay(lat as i16, lon as i16, ts_sec)?;
+        }
+        Some(delay)
+    }
+
+    // slant ionospheric delay (m) of a satellite seen at az/el (rad) from
+    // lat/lon (deg), at receiver time ts_sec
+    pub fn get_iono_delay(&self, lat: f64, lon: f64, az: f64, el: f64, ts_sec: f64) -> Option<f64> {
+        let (lat, lon) = (lat.to_radians(), lon.to_radians());
+        let rp = EARTH_RADIUS / (EARTH_RADIUS + IONO_HEIGHT) * el.cos();
+        let psi = std::f64::consts::FRAC_PI_2 - el - rp.asin();
+        let lat_pp = (lat.sin() * psi.cos() + lat.cos() * psi.sin() * az.cos()).asin();
+        let lon_pp = lon + (psi.sin() * az.sin() / lat_pp.cos()).asin();
+        let (lat_pp, lon_pp) = (lat_pp.to_degrees(), lon_pp.to_degrees());
+        let lon_pp = (lon_pp + 180.0).rem_euclid(360.0) - 180.0;
+
+        // 5 degree grid up to 55 degrees of latitude, falling back to
+        // 10 degrees; beyond, the IGPs are at 55, 65 and 75 degrees
+        let lon5 = (lon_pp / 5.0).floor() * 5.0;
+        let lon10 = (lon_pp / 10.0).floor() * 10.0;
+        let delay = if lat_pp.abs() < 55.0 {
+            let lat5 = (lat_pp / 5.0).floor() * 5.0;
+            let lat10 = (lat_pp / 10.0).floor() * 10.0;
+            self.get_cell_delay(lat_pp, lon_pp, lat5, lon5, 5.0, 5.0, ts_sec)
+                .or_else(|| self.get_cell_delay(lat_pp, lon_pp, lat10, lon10, 10.0, 10.0, ts_sec))
+        } else if lat_pp.abs() < 75.0 {
+            let lat10 = ((lat_pp - 5.0) / 10.0).floor() * 10.0 + 5.0;
+            self.get_cell_delay(lat_pp, lon_pp, lat10, lon10, 10.0, 10.0, ts_sec)
+        } else {
+            None
+        }?;
+        let fpp = 1.0 / (1.0 - rp * rp).sqrt();
+        Some(fpp * delay)
+    }
+
+    fn decode_type1(&mut self, buf: &[u8]) {
+        self.sats.clear();
+        for slot in 1..=210 {
+            if getbitu(buf, 13 + slot, 1) != 0 {
+                self.sats.push(SbasSat {
+                    sv: get_mask_sv(slot),
+                    ..Default::default()
+                });
+            }
+        }
+        self.iodp = getbitu(buf, 224, 2);
+        log::info!(
+            "sbas: prn mask: iodp={} nsat={}",
+            self.iodp,
+            self.sats.len()
+        );
+    }
+
+    fn decode_fast_corr(&mut self, buf: &[u8], msg_type: u32, ts_sec: f64) {
+        let iodf = getbitu(buf, 14, 2);
+        if self.iodp != getbitu(buf, 16, 2) {
+            return;
+        }
+        for i in 0..13 {
+            let j = 13 * (msg_type as usize - 2) + i;
+            let Some(sat) = self.sats.get_mut(j) else {
+                break;
+            };
+            let prc = getbits(buf, 18 + i * 12, 12) as f64 * 0.125;
+            let udrei = getbitu(buf, 174 + i * 4, 4);
+            sat.fcorr.update(ts_sec, prc, udrei, iodf);
+        }
+    }
+
+    fn decode_type6(&mut self, buf: &[u8]) {
+        let iodf: Vec<_> = (0..4).map(|i| getbitu(buf, 14 + i * 2, 2)).collect();
+        for (i, sat) in self.sats.iter_mut().enumerate().take(51) {
+            if sat.fcorr.iodf != iodf[i / 13] {
+                continue;
+            }
+            sat.fcorr.udrei = getbitu(buf, 22 + i * 4, 4);
+        }
+    }
+
+    fn decode_type7(&mut self, buf: &[u8]) {
+        if self.iodp != getbitu(buf, 18, 2) {
+            return;
+        }
+        self.tlat = getbitu(buf, 14, 4);
+        for (i, sat) in self.sats.iter_mut().enumerate().take(51) {
+            sat.fcorr.ai = getbitu(buf, 22 + i * 4, 4);
+        }
+    }
+
+    fn decode_type9(&mut self, buf: &[u8], ts_sec: f64) {
+        let eph = &mut self.geo_eph;
+        eph.ts_sec = ts_sec;
+        eph.t0 = getbitu(buf, 22, 13) * 16;
+        eph.sva = getbitu(buf, 35, 4);
+        eph.svh = if eph.sva == 15 { 1 } else { 0 };
+        eph.pos[0] = getbits(buf, 39, 30) as f64 * 0.08;
+        eph.pos[1] = getbits(buf, 69, 30) as f64 * 0.08;
+        eph.pos[2] = getbits(buf, 99, 25) as f64 * 0.4;
+        eph.vel[0] = getbits(buf, 124, 17) as f64 * 0.000625;
+        eph.vel[1] = getbits(buf, 141, 17) as f64 * 0.000625;
+        eph.vel[2] = getbits(buf, 158, 18) as f64 * 0.004;
+        eph.acc[0] = getbits(buf, 176, 10) as f64 * 0.0000125;
+        eph.acc[1] = getbits(buf, 186, 10) as f64 * 0.0000125;
+        eph.acc[2] = getbits(buf, 196, 10) as f64 * 0.0000625;
+        eph.af0 = getbits(buf, 206, 12) as f64 * P2_31;
+        eph.af1 = getbits(buf, 218, 8) as f64 * P2_39 / 2.0;
+    }
+
+    fn decode_type17(&mut self, buf: &[u8]) {
+        let t0 = getbitu(buf, 215, 11) * 64;
+        for i in 0..3 {
+            let p = 14 + i * 67;
+            let alm = SbasGeoAlm {
+                data_id: getbitu(buf, p, 2),
+                prn: getbitu(buf, p + 2, 8),
+                health: getbitu(buf, p + 10, 8),
+                pos: [
+                    getbits(buf, p + 18, 15) as f64 * 2600.0,
+                    getbits(buf, p + 33, 15) as f64 * 2600.0,
+                    getbits(buf, p + 48, 9) as f64 * 26000.0,
+                ],
+                vel: [
+                    getbits(buf, p + 57, 3) as f64 * 10.0,
+                    getbits(buf, p + 60, 3) as f64 * 10.0,
+                    getbits(buf, p + 63, 4) as f64 * 40.0,
+                ],
+                t0,
+            };
+            if alm.prn == 0 {
+                continue;
+            }
+            match self.geo_alm.iter_mut().find(|a| a.prn == alm.prn) {
+                Some(a) => *a = alm,
+                None => self.geo_alm.push(alm),
+            }
+        }
+    }
+
+    fn decode_type18(&mut self, buf: &[u8]) {
+        let band = getbitu(buf, 18, 4) as usize;
+        let iodi = getbitu(buf, 22, 2);
+        if band >= IGP_BAND.len() {
+            log::info!("sbas: igp band {band} not supported");
+            return;
+        }
+        let mut igp = vec![];
+        for bit in 1..=201 {
+            if getbitu(buf, 23 + bit, 1) == 0 {
+                continue;
+            }
+            if let Some((lat, lon)) = get_igp_position(band, bit) {
+                igp.push(SbasIgp {
+                    lat,
+                    lon,
+                    ..Default::default()
+                });
+            }
+        }
+        log::info!("sbas: igp mask: band={band} iodi={iodi} nigp={}", igp.len());
+        self.iono[band] = SbasIonoBand { iodi, igp };
+    }
+
+    fn decode_type26(&mut self, buf: &[u8], ts_sec: f64) {
+        let band = getbitu(buf, 14, 4) as usize;
+        let block = getbitu(buf, 18, 4) as usize;
+        if band >= IGP_BAND.len() || self.iono[band].iodi != getbitu(buf, 217, 2) {
+            return;
+        }
+        for i in 0..15 {
+            let Some(igp) = self.iono[band].igp.get_mut(block * 15 + i) else {
+                break;
+            };
+            let delay = getbitu(buf, 22 + i * 13, 9);
+            igp.ts_sec = ts_sec;
+            igp.delay = if delay == 0x1ff {
+                0.0
+            } else {
+                delay as f64 * 0.125
+            };
+            igp.givei = getbitu(buf, 22 + i * 13 + 9, 4);
+        }
+    }
+
+    // long term corrections, velocity code 0
+    fn decode_long_corr0(&mut self, buf: &[u8], p: usize, ts_sec: f64) {
+        let n = getbitu(buf, p, 6) as usize;
+        let Some(sat) = n.checked_sub(1).and_then(|i| self.sats.get_mut(i)) else {
+            return;
+        };
+        let lcorr = &mut sat.lcorr;
+        lcorr.ts_sec = ts_sec;
+        lcorr.iode = getbitu(buf, p + 6, 8);
+        for i in 0..3 {
+            lcorr.dpos[i] = getbits(buf, p + 14 + 9 * i, 9) as f64 * 0.125;
+            lcorr.dvel[i] = 0.0;
+        }
+        lcorr.daf0 = getbits(buf, p + 41, 10) as f64 * P2_31;
+        lcorr.daf1 = 0.0;
+    }
+
+    // long term corrections, velocity code 1
+    fn decode_long_corr1(&mut self, buf: &[u8], p: usize, ts_sec: f64) {
+        let n = getbitu(buf, p, 6) as usize;
+        let Some(sat) = n.checked_sub(1).and_then(|i| self.sats.get_mut(i)) else {
+            return;
+        };
+        let lcorr = &mut sat.lcorr;
+        lcorr.ts_sec = ts_sec;
+        lcorr.iode = getbitu(buf, p + 6, 8);
+        for i in 0..3 {
+            lcorr.dpos[i] = getbits(buf, p + 14 + i * 11, 11) as f64 * 0.125;
+            lcorr.dvel[i] = getbits(buf, p + 58 + i * 8, 8) as f64 * P2_11;
+        }
+        lcorr.daf0 = getbits(buf, p + 47, 11) as f64 * P2_31;
+        lcorr.daf1 = getbits(buf, p + 82, 8) as f64 * P2_39;
+        lcorr.t0 = getbitu(buf, p + 90, 13) * 16;
+    }
+
+    // half message of long term corrections (106 bits)
+    fn decode_long_corr_half(&mut self, buf: &[u8], p: usize, ts_sec: f64) {
+        if getbitu(buf, p, 1) == 0 {
+            if self.iodp == getbitu(buf, p + 103, 2) {
+                self.decode_long_corr0(buf, p + 1, ts_sec);
+                self.decode_long_corr0(buf, p + 52, ts_sec);
+            }
+        } else if self.iodp == getbitu(buf, p + 104, 2) {
+            self.decode_long_corr1(buf, p + 1, ts_sec);
+        }
+    }
+
+    fn decode_type24(&mut self, buf: &[u8], ts_sec: f64) {
+        if self.iodp != getbitu(buf, 110, 2) {
+            return;
+        }
+        let block = getbitu(buf, 112, 2) as usize;
+        let iodf = getbitu(buf, 114, 2);
+        for i in 0..6 {
+            let Some(sat) = self.sats.get_mut(13 * block + i) else {
+                break;
+            };
+            let prc = getbits(buf, 14 + i * 12, 12) as f64 * 0.125;
+            let udrei = getbitu(buf, 86 + 4 * i, 4);
+            sat.fcorr.update(ts_sec, prc, udrei, iodf);
+        }
+        self.decode_long_corr_half(buf, 120, ts_sec);
+    }
+
+    fn decode_type25(&mut self, buf: &[u8], ts_sec: f64) {
+        self.decode_long_corr_half(buf, 14, ts_sec);
+        self.decode_long_corr_half(buf, 120, ts_sec);
+    }
+
+    // buf holds a 250-bit message whose CRC has been checked
+    pub fn decode_msg(&mut self, buf: &[u8], sv: SV, ts_sec: f64) -> u32 {
+        let msg_type = getbitu(buf, 8, 6);
+
+        match msg_type {
+            1 => self.decode_type1(buf),
+            2..=5 => self.decode_fast_corr(buf, msg_type, ts_sec),
+            6 => self.decode_type6(buf),
+            7 => self.decode_type7(buf),
+            9 => self.decode_type9(buf, ts_sec),
+            17 => self.decode_type17(buf),
+            18 => self.decode_type18(buf),
+            24 => self.decode_type24(buf, ts_sec),
+            25 => self.decode_type25(buf, ts_sec),
+            26 => self.decode_type26(buf, ts_sec),
+            _ => {}
+        }
+        log::info!("{sv}: {}: type={msg_type}", "SBAS".blue());
+
+        msg_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::setbitu;
+
+    fn gen_msg(msg_type: u32, fields: &[(usize, usize, i32)]) -> Vec<u8> {
+        let mut buf = vec![0u8; 32];
+        setbitu(&mut buf, 0, 8, SBAS_PREAMBLES[0]);
+        setbitu(&mut buf, 8, 6, msg_type);
+        for &(pos, len, v) in fields {
+            setbitu(&mut buf, pos, len, v as u32);
+        }
+        buf
+    }
+
+    #[test]
+    fn decode_messages() {
+        let geo = SV::new(Constellation::GPS, 131);
+        let gps = |prn| SV::new(Constellation::GPS, prn);
+        let mut sbas = Sbas::default();
+
+        // PRN mask: GPS 1, GPS 5 and PRN 122, IODP 2
+        let msg = gen_msg(1, &[(14, 1, 1), (18, 1, 1), (135, 1, 1), (224, 2, 2)]);
+        assert_eq!(sbas.decode_msg(&msg, geo, 10.0), 1);
+        assert_eq!(sbas.iodp, 2);
+        let svs: Vec<_> = sbas.sats.iter().map(|sat| sat.sv).collect();
+        assert_eq!(svs, [Some(gps(1)), Some(gps(5)), Some(gps(122))]);
+
+        // fast corrections: IODF 1, IODP 2
+        let msg = gen_msg(
+            2,
+            &[
+                (14, 2, 1),
+                (16, 2, 2),
+                (18, 12, -12),
+                (30, 12, 40),
+                (174, 4, 3),
+                (178, 4, 5),
+            ],
+        );
+        sbas.decode_msg(&msg, geo, 10.0);
+        let fcorr = sbas.get_fast_corr(gps(1)).unwrap();
+        assert_eq!((fcorr.prc, fcorr.udrei, fcorr.iodf), (-1.5, 3, 1));
+        assert_eq!(sbas.get_fast_corr(gps(5)).unwrap().prc, 5.0);
+        // IODP mismatch
+        let msg = gen_msg(2, &[(14, 2, 1), (16, 2, 3), (18, 12, 80)]);
+        sbas.decode_msg(&msg, geo, 11.0);
+        assert_eq!(sbas.get_fast_corr(gps(1)).unwrap().prc, -1.5);
+
+        // integrity: UDREI of IODF 1
+        let msg = gen_msg(6, &[(14, 2, 1), (22, 4, 7)]);
+        sbas.decode_msg(&msg, geo, 10.0);
+        assert_eq!(sbas.get_fast_corr(gps(1)).unwrap().udrei, 7);
+
+        // degradation factors
+        let msg = gen_msg(7, &[(14, 4, 4), (18, 2, 2), (22, 4, 2)]);
+        sbas.decode_msg(&msg, geo, 10.0);
+        assert_eq!(sbas.tlat, 4);
+        assert_eq!(sbas.get_fast_corr(gps(1)).unwrap().ai, 2);
+
+        // GEO navigation message
+        let msg = gen_msg(
+            9,
+            &[(22, 13, 100), (39, 30, 1000), (99, 25, -10), (158, 18, 4)],
+        );
+        sbas.decode_msg(&msg, geo, 10.0);
+        assert_eq!(sbas.geo_eph.t0, 1600);
+        assert_eq!(sbas.geo_eph.pos, [80.0, 0.0, -4.0]);
+        assert_eq!(sbas.geo_eph.vel[2], 0.016);
+
+        // GEO almanac
+        let msg = gen_msg(
+            17,
+            &[
+                (14, 2, 1),
+                (16, 8, 131),
+                (32, 15, 10),
+                (62, 9, -2),
+                (71, 3, 3),
+                (77, 4, -2),
+                (215, 11, 5),
+            ],
+        );
+        sbas.decode_msg(&msg, geo, 10.0);
+        let alm = &sbas.geo_alm[0];
+        assert_eq!((alm.data_id, alm.prn, alm.t0), (1, 131, 320));
+        assert_eq!(alm.pos, [26000.0, 0.0, -52000.0]);
+        assert_eq!(alm.vel, [30.0, 0.0, -80.0]);
+
+        // IGP mask: band 4, IODI 1, bits 101 and 102
+        let msg = gen_msg(18, &[(18, 4, 4), (22, 2, 1), (124, 1, 1), (125, 1, 1)]);
+        sbas.decode_msg(&msg, geo, 10.0);
+        let band = &sbas.iono[4];
+        assert_eq!(band.iodi, 1);
+        let pos: Vec<_> = band.igp.iter().map(|igp| (igp.lat, igp.lon)).collect();
+        assert_eq!(pos, [(-75, 0), (-65, 0)]);
+
+        // ionospheric delays: band 4, block 0, the second IGP not monitored
+        let msg = gen_msg(
+            26,
+            &[
+                (14, 4, 4),
+                (22, 9, 16),
+                (31, 4, 3),
+                (35, 9, 0x1ff),
+                (44, 4, 15),
+                (217, 2, 1),
+            ],
+        );
+        sbas.decode_msg(&msg, geo, 10.0);
+        let igp = &sbas.iono[4].igp;
+        assert_eq!((igp[0].delay, igp[0].givei), (2.0, 3));
+        assert_eq!((igp[1].delay, igp[1].givei), (0.0, 15));
+        assert_eq!(sbas.get_igp_delay(-75, 0, 10.0), Some(2.0));
+        assert_eq!(sbas.get_igp_delay(-65, 0, 10.0), None);
+
+        // mixed corrections: GPS 1 fast correction, GPS 5 long term
+        // correction with velocity code 0
+        let msg = gen_msg(
+            24,
+            &[
+                (14, 12, 8),
+                (86, 4, 3),
+                (110, 2, 2),
+                (114, 2, 2),
+                (121, 6, 2),
+                (127, 8, 33),
+                (135, 9, -8),
+                (223, 2, 2),
+            ],
+        );
+        sbas.decode_msg(&msg, geo, 12.0);
+        let fcorr = sbas.get_fast_corr(gps(1)).unwrap();
+        assert_eq!((fcorr.prc, fcorr.rrc, fcorr.iodf), (1.0, 1.25, 2));
+        let lcorr = sbas.get_long_corr(gps(5)).unwrap();
+        assert_eq!((lcorr.iode, lcorr.dpos), (33, [-1.0, 0.0, 0.0]));
+
+        // long term corrections: GPS 1 with velocity code 1
+        let msg = gen_msg(
+            25,
+            &[
+                (14, 1, 1),
+                (15, 6, 1),
+                (21, 8, 7),
+                (29, 11, 16),
+                (73, 8, 4),
+                (105, 13, 10),
+                (118, 2, 2),
+            ],
+        );
+        sbas.decode_msg(&msg, geo, 12.0);
+        let lcorr = sbas.get_long_corr(gps(1)).unwrap();
+        assert_eq!((lcorr.iode, lcorr.t0), (7, 160));
+        assert_eq!(lcorr.dpos, [2.0, 0.0, 0.0]);
+        assert_eq!(lcorr.dvel, [4.0 * P2_11, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,297 @@
+use colored::Colorize;
+use rustfft::num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording, ReadStats};
+
+// SigMF recordings (https://sigmf.org): a binary dataset (.sigmf-data)
+// described by a JSON metadata file (.sigmf-meta). Only the core namespace
+// is interpreted, other fields are kept as is when the metadata is written
+// back.
+
+const SIGMF_DATA_EXT: &str = "sigmf-data";
+const SIGMF_META_EXT: &str = "sigmf-meta";
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SigMFGlobal {
+    #[serde(rename = "core:datatype")]
+    pub datatype: String,
+    #[serde(rename = "core:sample_rate", skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    #[serde(rename = "core:version", skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(rename = "core:description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SigMFCapture {
+    #[serde(rename = "core:sample_start")]
+    pub sample_start: u64,
+    #[serde(rename = "core:frequency", skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f64>,
+    #[serde(rename = "core:datetime", skip_serializing_if = "Option::is_none")]
+    pub datetime: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SigMFAnnotation {
+    #[serde(rename = "core:sample_start")]
+    pub sample_start: u64,
+    #[serde(rename = "core:sample_count", skip_serializing_if = "Option::is_none")]
+    pub sample_count: Option<u64>,
+    #[serde(rename = "core:label", skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(rename = "core:comment", skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SigMFMeta {
+    pub global: SigMFGlobal,
+    #[serde(default)]
+    pub captures: Vec<SigMFCapture>,
+    #[serde(default)]
+    pub annotations: Vec<SigMFAnnotation>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+// true for foo.sigmf-data or foo.sigmf-meta
+pub fn is_sigmf_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some(SIGMF_DATA_EXT) | Some(SIGMF_META_EXT)
+    )
+}
+
+pub fn get_meta_path(path: &Path) -> PathBuf {
+    path.with_extension(SIGMF_META_EXT)
+}
+
+pub fn get_data_path(path: &Path) -> PathBuf {
+    path.with_extension(SIGMF_DATA_EXT)
+}
+
+// datatype of the formats a recording can be made in
+pub fn get_sigmf_datatype(file_type: &IQFileType) -> Option<&'static str> {
+    match file_type {
+        IQFileType::TypePairFloat32 => Some("cf32_le"),
+        IQFileType::TypePairFloat32Be => Some("cf32_be"),
+        IQFileType::TypePairFloat16 => Some("cf16_le"),
+        IQFileType::TypePairInt16 => Some("ci16_le"),
+        IQFileType::TypePairInt16Be => Some("ci16_be"),
+        IQFileType::TypePairInt8 => Some("ci8"),
+        IQFileType::TypeRtlSdrFile => Some("cu8"),
+        IQFileType::TypeOneInt8 => Some("ri8"),
+        _ => None,
+    }
+}
+
+impl SigMFMeta {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let meta: Self = serde_json::from_str(&content)?;
+        Ok(meta)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get_file_type(&self) -> Result<IQFileType, Box<dyn Error>> {
+        match self.global.datatype.as_str() {
+            "cf32_le" => Ok(IQFileType::TypePairFloat32),
+            "cf32_be" => Ok(IQFileType::TypePairFloat32Be),
+            "cf16_le" => Ok(IQFileType::TypePairFloat16),
+            "ci16_le" => Ok(IQFileType::TypePairInt16),
+            "ci16_be" => Ok(IQFileType::TypePairInt16Be),
+            "ci8" => Ok(IQFileType::TypePairInt8),
+            "cu8" => Ok(IQFileType::TypeRtlSdrFile),
+            "ri8" => Ok(IQFileType::TypeOneInt8),
+            t => Err(format!("unsupported SigMF datatype: {t}").into()),
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> Option<f64> {
+        self.global.sample_rate
+    }
+
+    // RF center frequency of the first capture
+    pub fn get_frequency(&self) -> Option<f64> {
+        self.captures.first().and_then(|c| c.frequency)
+    }
+
+    pub fn add_annotation(&mut self, sample_start: u64, label: &str, comment: &str) {
+        self.annotations.push(SigMFAnnotation {
+            sample_start,
+            label: Some(label.to_string()),
+            comment: Some(comment.to_string()),
+            ..Default::default()
+        });
+        // annotations are sorted by core:sample_start
+        self.annotations.sort_by_key(|a| a.sample_start);
+    }
+}
+
+pub struct SigMFRecording {
+    rec: IQRecording,
+    pub meta: SigMFMeta,
+    pub meta_path: PathBuf,
+    pub file_type: IQFileType,
+}
+
+impl SigMFRecording {
+    // path may point to either the dataset or the metadata file
+    pub fn new(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let meta_path = get_meta_path(path);
+        let meta = SigMFMeta::load(&meta_path)?;
+        let file_type = meta.get_file_type()?;
+        let fs = meta
+            .get_sample_rate()
+            .ok_or("SigMF: core:sample_rate missing")?;
+
+        if meta
+            .captures
+            .iter()
+            .any(|c| c.frequency != meta.get_frequency())
+        {
+            log::warn!("SigMF: captures at several frequencies: using the first one");
+        }
+        log::warn!(
+            "SigMF: {} datatype={} fs={:.1} KHz fc={:.3} MHz",
+            meta_path.display().to_string().green(),
+            meta.global.datatype,
+            fs / 1000.0,
+            meta.get_frequency().unwrap_or(0.0) / 1_000_000.0
+        );
+        let rec = IQRecording::new(&get_data_path(path), fs, &file_type)?;
+
+        Ok(Self {
+            rec,
+            meta,
+            meta_path,
+            file_type,
+        })
+    }
+}
+
+impl IQReader for SigMFRecording {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        self.rec.get_iq_data(off_samples, num_samples)
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        self.rec.get_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META: &str = r#"{
+        "global": {
+            "core:datatype": "ci16_le",
+            "core:sample_rate": 2046000.0,
+            "core:version": "1.0.0",
+            "core:hw": "rtl-sdr v3"
+        },
+        "captures": [
+            {
+                "core:sample_start": 0,
+                "core:frequency": 1575420000.0,
+                "antenna:gain": 28.0
+            }
+        ],
+        "annotations": [],
+        "extra": { "note": 1 }
+    }"#;
+
+    #[test]
+    fn round_trip() {
+        let meta: SigMFMeta = serde_json::from_str(META).unwrap();
+        assert_eq!(meta.get_sample_rate(), Some(2046000.0));
+        assert_eq!(meta.get_frequency(), Some(1575420000.0));
+        assert_eq!(meta.global.other["core:hw"], "rtl-sdr v3");
+        assert_eq!(meta.captures[0].other["antenna:gain"], 28.0);
+
+        let path = std::env::temp_dir().join(format!(
+            "gnss-rcv-sigmf-{}.{SIGMF_META_EXT}",
+            std::process::id()
+        ));
+        meta.save(&path).unwrap();
+        let loaded = SigMFMeta::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        // fields outside the core namespace are written back as they were
+        assert_eq!(loaded.global.datatype, "ci16_le");
+        assert_eq!(loaded.global.version.as_deref(), Some("1.0.0"));
+        assert_eq!(loaded.global.other["core:hw"], "rtl-sdr v3");
+        assert_eq!(loaded.captures[0].other["antenna:gain"], 28.0);
+        assert_eq!(loaded.get_frequency(), Some(1575420000.0));
+        assert_eq!(loaded.other["extra"]["note"], 1);
+    }
+
+    #[test]
+    fn datatype() {
+        for file_type in [
+            IQFileType::TypePairFloat32,
+            IQFileType::TypePairFloat32Be,
+            IQFileType::TypePairFloat16,
+            IQFileType::TypePairInt16,
+            IQFileType::TypePairInt16Be,
+            IQFileType::TypePairInt8,
+            IQFileType::TypeRtlSdrFile,
+            IQFileType::TypeOneInt8,
+        ] {
+            let datatype = get_sigmf_datatype(&file_type).unwrap();
+            let mut meta = SigMFMeta::default();
+            meta.global.datatype = datatype.to_string();
+            let t = meta.get_file_type().unwrap();
+            assert_eq!(get_sigmf_datatype(&t), Some(datatype));
+        }
+        assert_eq!(get_sigmf_datatype(&IQFileType::TypeOneInt2), None);
+        let mut meta = SigMFMeta::default();
+        meta.global.datatype = "cu16_le".to_string();
+        assert!(meta.get_file_type().is_err());
+    }
+
+    #[test]
+    fn annotations_sorted() {
+        let mut meta = SigMFMeta::default();
+        meta.add_annotation(3000, "fix", "3D");
+        meta.add_annotation(1000, "lock", "GPS05");
+        meta.add_annotation(2000, "lock", "GPS12");
+        let starts: Vec<u64> = meta.annotations.iter().map(|a| a.sample_start).collect();
+        assert_eq!(starts, [1000, 2000, 3000]);
+        assert_eq!(meta.annotations[0].comment.as_deref(), Some("GPS05"));
+    }
+
+    #[test]
+    fn paths() {
+        let path = Path::new("/tmp/rec.sigmf-data");
+        assert!(is_sigmf_file(path));
+        assert!(!is_sigmf_file(Path::new("/tmp/rec.bin")));
+        assert_eq!(get_meta_path(path), Path::new("/tmp/rec.sigmf-meta"));
+        assert_eq!(
+            get_data_path(&get_meta_path(path)),
+            Path::new("/tmp/rec.sigmf-data")
+        );
+    }
+}
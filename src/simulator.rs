@@ -0,0 +1,493 @@
+use chrono::{Duration, NaiveDate, SecondsFormat};
+use colored::Colorize;
+use map_3d::{Ellipsoid, geodetic2ecef};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Normal};
+use rayon::prelude::*;
+use rustfft::num_complex::Complex64;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::almanac::{ALM_TOA_UNIT, Almanac, IonoUtc};
+use crate::code::Code;
+use crate::constants::{EARTH_ROTATION_RATE, SECS_PER_WEEK, SPEED_OF_LIGHT};
+use crate::ephemeris::Ephemeris;
+use crate::navigation::{LNAV_SUBFRAME_BITS, LNAV_SUBFRAME_SEC, nav_encode_lnav_subframe};
+use crate::receiver::get_sat_list;
+use crate::recording::{IQFileType, IQRecording};
+use crate::sigmf::{SigMFCapture, SigMFGlobal, SigMFMeta, get_meta_path, get_sigmf_datatype};
+
+const PI: f64 = std::f64::consts::PI;
+const CODE_PERIOD: f64 = 1e-3;
+const BIT_SEC: f64 = 0.02;
+const BLOCK_LEN: usize = 4096; // samples between two updates of the geometry
+const REPORT_SEC: f64 = 10.0;
+// noise per component, relative to the full scale of the integer formats
+const NOISE_RMS: f64 = 0.25;
+const NOISE_RMS_I2: f64 = 0.6; // around the threshold of the 2-bit quantizer
+const CN0_ELEV_LOSS: f64 = 6.0; // dB, from zenith to horizon
+
+// receiver ECEF positions, at t seconds from the start
+pub type Trajectory = Vec<(f64, [f64; 3])>;
+
+// "lat,lon,h", e.g. "35.681298,139.766247,10.0"
+pub fn parse_position(s: &str) -> Result<[f64; 3], Box<dyn Error>> {
+    let v: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<_, _>>()?;
+    if v.len() != 3 {
+        return Err(format!("Failed to parse {s}: lat,lon,h expected").into());
+    }
+    let (x, y, z) = geodetic2ecef(v[0].to_radians(), v[1].to_radians(), v[2], Ellipsoid::WGS84);
+    Ok([x, y, z])
+}
+
+// Receiver trajectory: "t,lat,lon,h" lines, t in seconds from the start of
+// the simulation. Positions are interpolated linearly in between.
+pub fn load_trajectory(path: &Path) -> Result<Trajectory, Box<dyn Error>> {
+    let mut traj = vec![];
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (t, pos) = line
+            .split_once(',')
+            .ok_or(format!("Failed to parse {line}: t,lat,lon,h expected"))?;
+        traj.push((t.trim().parse()?, parse_position(pos)?));
+    }
+    if traj.is_empty() {
+        return Err(format!("{}: empty trajectory", path.display()).into());
+    }
+    Ok(traj)
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+// seconds since the GPS epoch
+fn get_gps_secs(week: u32, sow: u32) -> i64 {
+    week as i64 * SECS_PER_WEEK as i64 + sow as i64
+}
+
+// seconds between two times of the week, across a week boundary if closer
+fn get_week_delta(t: f64) -> f64 {
+    let half_week = SECS_PER_WEEK as f64 / 2.0;
+    if t > half_week {
+        t - 2.0 * half_week
+    } else if t < -half_week {
+        t + 2.0 * half_week
+    } else {
+        t
+    }
+}
+
+// A GPS satellite to simulate, with its C/N0 in dB-Hz
+pub struct SimSat {
+    pub eph: Ephemeris,
+    pub cn0: f64,
+}
+
+pub struct SimConfig {
+    pub fs: f64,
+    pub fi: f64,
+    pub file_type: IQFileType,
+    pub week: u32, // GPS time of the first sample
+    pub sow: u32,
+    pub duration_sec: f64,
+    pub trajectory: Trajectory,
+    pub seed: u64,
+    pub almanac: Vec<Almanac>, // of SVs 1..32, sat 0 when unknown
+    pub iono_utc: IonoUtc,
+}
+
+impl SimConfig {
+    // position of the receiver t seconds from the start
+    fn get_rx_position(&self, t: f64) -> [f64; 3] {
+        let traj = &self.trajectory;
+        let i = traj.partition_point(|(ts, _)| *ts <= t);
+        if i == 0 {
+            return traj[0].1;
+        }
+        if i == traj.len() {
+            return traj[i - 1].1;
+        }
+        let ((t0, p0), (t1, p1)) = (traj[i - 1], traj[i]);
+        let r = (t - t0) / (t1 - t0);
+        [0, 1, 2].map(|k| p0[k] + r * (p1[k] - p0[k]))
+    }
+
+    fn get_noise_rms(&self) -> f64 {
+        match self.file_type {
+            IQFileType::TypeOneInt2 => NOISE_RMS_I2,
+            _ => NOISE_RMS,
+        }
+    }
+}
+
+struct SimChannel {
+    eph: Ephemeris,
+    code: Vec<i8>,
+    amp: f64,
+    delay: f64,                       // at the start of the next block
+    subframe: Option<(i64, Vec<u8>)>, // index since the start of the week, bits sent
+}
+
+impl SimChannel {
+    // Delay between the SV clock time of the signal and the receiver time t,
+    // in seconds from the start: the signal received at t left the SV when
+    // its clock read t - delay. Light time with the rotation of the earth
+    // during the transit, minus the SV clock offset.
+    fn get_delay(&self, cfg: &SimConfig, t: f64, rx: &[f64; 3]) -> f64 {
+        let start = get_gps_secs(cfg.week, cfg.sow);
+        let off_toe = get_week_delta((start - get_gps_secs(self.eph.week, self.eph.toe)) as f64);
+        let off_toc = get_week_delta((start - get_gps_secs(self.eph.week, self.eph.toc)) as f64);
+
+        let mut tau = 0.075;
+        let mut dte = 0.0;
+        for _ in 0..3 {
+            dte = off_toe + t - tau;
+            let (x, y, z) = self.eph.get_sv_position_ecef(dte);
+            let theta = EARTH_ROTATION_RATE * tau;
+            let sv = [
+                x * theta.cos() + y * theta.sin(),
+                -x * theta.sin() + y * theta.cos(),
+                z,
+            ];
+            tau = distance(&sv, rx) / SPEED_OF_LIGHT;
+        }
+        let dtc = off_toc + t - tau;
+        tau - self.eph.get_clock_bias(dtc, dte)
+    }
+
+    fn get_elevation(&self, cfg: &SimConfig, rx: &[f64; 3]) -> f64 {
+        let tau = self.get_delay(cfg, 0.0, rx);
+        let start = get_gps_secs(cfg.week, cfg.sow);
+        let off_toe = (start - get_gps_secs(self.eph.week, self.eph.toe)) as f64;
+        let (x, y, z) = self.eph.get_sv_position_ecef(get_week_delta(off_toe) - tau);
+        let los = [x - rx[0], y - rx[1], z - rx[2]];
+        // geocentric up: close enough for an elevation mask
+        let r = distance(rx, &[0.0; 3]);
+        let up = (los[0] * rx[0] + los[1] * rx[1] + los[2] * rx[2]) / r;
+        (up / distance(&los, &[0.0; 3])).asin()
+    }
+
+    // bits of an LNAV subframe: 1 to 3 from the ephemeris, 4 and 5 with the
+    // page of the 12.5 minute cycle, counted from the start of the week
+    fn get_subframe_bits(&mut self, cfg: &SimConfig, idx: i64) -> &[u8] {
+        if self.subframe.as_ref().is_none_or(|(i, _)| *i != idx) {
+            let id = (idx.rem_euclid(5) + 1) as u32;
+            let tow = ((idx + 1) * LNAV_SUBFRAME_SEC as i64).rem_euclid(SECS_PER_WEEK as i64);
+            let page = ((idx / 5).rem_euclid(25) + 1) as u32;
+            let bits = nav_encode_lnav_subframe(
+                id,
+                tow as u32,
+                page,
+                &self.eph,
+                &cfg.almanac,
+                &cfg.iono_utc,
+            );
+            self.subframe = Some((idx, bits));
+        }
+        &self.subframe.as_ref().unwrap().1
+    }
+
+    // n samples from t0 to t1, the delay and the carrier phase going
+    // linearly from one end to the other
+    fn gen_block(&mut self, cfg: &SimConfig, t0: f64, t1: f64, n: usize) -> Vec<Complex64> {
+        let rx = cfg.get_rx_position(t1);
+        let (delay0, delay1) = (self.delay, self.get_delay(cfg, t1, &rx));
+        self.delay = delay1;
+
+        // SV clock time of the signal, from the start
+        let s0 = t0 - delay0;
+        let ds = ((t1 - delay1) - s0) / n as f64;
+        // carrier: -L1 * delay, on top of fi
+        let fc = Code::get_code_freq("L1CA");
+        let cycles0 = (-fc * delay0 + cfg.fi * t0).rem_euclid(1.0);
+        let dcycles = (-fc * (delay1 - delay0) + cfg.fi * (t1 - t0)) / n as f64;
+        let mut phasor = Complex64::from_polar(self.amp, 2.0 * PI * cycles0);
+        let rot = Complex64::from_polar(1.0, 2.0 * PI * dcycles);
+        let code_len = self.code.len() as f64;
+
+        let mut v = Vec::with_capacity(n);
+        for k in 0..n {
+            let s = s0 + ds * k as f64;
+            let chip = ((s / CODE_PERIOD).rem_euclid(1.0) * code_len) as usize;
+            let tow = cfg.sow as f64 + s;
+            let idx = (tow / LNAV_SUBFRAME_SEC as f64).floor() as i64;
+            let bit_idx = ((tow - (idx * LNAV_SUBFRAME_SEC as i64) as f64) / BIT_SEC) as usize;
+            let bit = self.get_subframe_bits(cfg, idx)[bit_idx.min(LNAV_SUBFRAME_BITS - 1)];
+            let sym = (self.code[chip] * (1 - 2 * bit as i8)) as f64;
+            v.push(phasor * sym);
+            phasor *= rot;
+        }
+        v
+    }
+}
+
+// Almanac of the GPS SVs, from the ephemerides closest to the start: its
+// toa is the last multiple of 4096 s before the start.
+pub fn get_almanac(ephs: &[Ephemeris], week: u32, sow: u32) -> Vec<Almanac> {
+    let start = get_gps_secs(week, sow);
+    let toas = sow / ALM_TOA_UNIT * ALM_TOA_UNIT;
+    (1..=32)
+        .map(|prn| {
+            ephs.iter()
+                .filter(|e| e.sv.prn == prn)
+                .min_by_key(|e| (start - get_gps_secs(e.week, e.toe)).abs())
+                .map_or(Almanac::default(), |eph| {
+                    Almanac::from_ephemeris(eph, week, toas)
+                })
+        })
+        .collect()
+}
+
+// Picks, for each SV of sats (all GPS SVs if empty), the ephemeris closest
+// to the start, and keeps the healthy SVs above the elevation mask. C/N0 is
+// cn0 at zenith, a few dB lower toward the horizon.
+pub fn get_visible_sats(
+    ephs: &[Ephemeris],
+    cfg: &SimConfig,
+    sats: &str,
+    elev_mask_deg: f64,
+    cn0: f64,
+) -> Vec<SimSat> {
+    let sat_list = get_sat_list(sats, "L1CA");
+    let start = get_gps_secs(cfg.week, cfg.sow);
+    let rx = cfg.get_rx_position(0.0);
+    let mut sim_sats = vec![];
+
+    for sv in sat_list {
+        let Some(eph) = ephs
+            .iter()
+            .filter(|e| e.sv == sv && e.svh == 0)
+            .min_by_key(|e| (start - get_gps_secs(e.week, e.toe)).abs())
+        else {
+            continue;
+        };
+        let ch = SimChannel {
+            eph: *eph,
+            code: vec![],
+            amp: 0.0,
+            delay: 0.0,
+            subframe: None,
+        };
+        let elev = ch.get_elevation(cfg, &rx);
+        if elev.to_degrees() < elev_mask_deg {
+            continue;
+        }
+        sim_sats.push(SimSat {
+            eph: *eph,
+            cn0: cn0 - CN0_ELEV_LOSS * (1.0 - elev.sin()),
+        });
+    }
+    sim_sats
+}
+
+// GPS L1 C/A signal of a set of SVs, as received at the positions of the
+// trajectory, in thermal noise. Ionosphere and troposphere are left out.
+pub struct Simulator {
+    cfg: SimConfig,
+    channels: Vec<SimChannel>,
+}
+
+impl Simulator {
+    pub fn new(cfg: SimConfig, sats: Vec<SimSat>) -> Result<Self, Box<dyn Error>> {
+        let noise_rms = cfg.get_noise_rms();
+        let rx = cfg.get_rx_position(0.0);
+        let mut channels = vec![];
+        for sat in sats {
+            let prn = Code::get_code_prn(sat.eph.sv);
+            let code = Code::gen_code("L1CA", prn).ok_or(format!("no L1CA code for {prn}"))?;
+            // C/N0 = A^2 / N0, with N0 = 2 * noise_rms^2 / fs. Real samples
+            // only keep half of the power of the signal.
+            let mut amp = (2.0 * noise_rms.powi(2) * 10f64.powf(sat.cn0 / 10.0) / cfg.fs).sqrt();
+            if cfg.file_type.is_real() {
+                amp *= 2f64.sqrt();
+            }
+            let mut ch = SimChannel {
+                eph: sat.eph,
+                code,
+                amp,
+                delay: 0.0,
+                subframe: None,
+            };
+            ch.delay = ch.get_delay(&cfg, 0.0, &rx);
+            let doppler = -Code::get_code_freq("L1CA")
+                * (ch.get_delay(&cfg, 1.0, &cfg.get_rx_position(1.0)) - ch.delay);
+            log::warn!(
+                "sim: {}: elev={:4.1} cn0={:.1} dB-Hz range={:.0} km doppler={:+.0} Hz",
+                sat.eph.sv,
+                ch.get_elevation(&cfg, &rx).to_degrees(),
+                sat.cn0,
+                ch.delay * SPEED_OF_LIGHT / 1000.0,
+                doppler
+            );
+            channels.push(ch);
+        }
+        Ok(Self { cfg, channels })
+    }
+
+    fn write_sigmf_meta(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let datatype = get_sigmf_datatype(&self.cfg.file_type)
+            .ok_or(format!("{}: no SigMF datatype", self.cfg.file_type))?;
+        let start = NaiveDate::from_ymd_opt(1980, 1, 6)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            + Duration::seconds(get_gps_secs(self.cfg.week, self.cfg.sow))
+            - Duration::seconds(self.cfg.iono_utc.utc[4] as i64);
+        let meta = SigMFMeta {
+            global: SigMFGlobal {
+                datatype: datatype.to_string(),
+                sample_rate: Some(self.cfg.fs),
+                version: Some("1.0.0".to_string()),
+                description: Some("gnss-rcv simulation".to_string()),
+                ..Default::default()
+            },
+            captures: vec![SigMFCapture {
+                sample_start: 0,
+                frequency: Some(Code::get_code_freq("L1CA") - self.cfg.fi),
+                // UTC: GPS time minus the leap seconds
+                datetime: Some(start.and_utc().to_rfc3339_opts(SecondsFormat::Millis, true)),
+                ..Default::default()
+            }],
+            annotations: vec![],
+            ..Default::default()
+        };
+        meta.save(&get_meta_path(path))
+    }
+
+    // writes the samples to path, in the format of the configuration, along
+    // with SigMF metadata for a .sigmf-data file
+    pub fn write(&mut self, path: &Path, is_sigmf: bool) -> Result<(), Box<dyn Error>> {
+        if is_sigmf {
+            self.write_sigmf_meta(path)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut rng = StdRng::seed_from_u64(self.cfg.seed);
+        let noise = Normal::new(0.0, self.cfg.get_noise_rms())?;
+        let num_samples = (self.cfg.duration_sec * self.cfg.fs) as usize;
+        let fs = self.cfg.fs;
+        let mut next_report_sec = REPORT_SEC;
+
+        println!(
+            "simulation: {} SVs {:.1} secs -- {} {}",
+            self.channels.len(),
+            self.cfg.duration_sec,
+            self.cfg.file_type,
+            path.display().to_string().green()
+        );
+        let mut off = 0;
+        while off < num_samples {
+            let n = BLOCK_LEN.min(num_samples - off);
+            let (t0, t1) = (off as f64 / fs, (off + n) as f64 / fs);
+            let cfg = &self.cfg;
+            let mut v: Vec<Complex64> = self
+                .channels
+                .par_iter_mut()
+                .map(|ch| ch.gen_block(cfg, t0, t1, n))
+                .reduce(
+                    || vec![Complex64::default(); n],
+                    |mut acc, v| {
+                        acc.iter_mut().zip(v).for_each(|(a, s)| *a += s);
+                        acc
+                    },
+                );
+            for s in v.iter_mut() {
+                s.re += noise.sample(&mut rng);
+                s.im = if cfg.file_type.is_real() {
+                    0.0
+                } else {
+                    s.im + noise.sample(&mut rng)
+                };
+            }
+            writer.write_all(&IQRecording::encode(&v, &cfg.file_type)?)?;
+            off += n;
+            if t1 >= next_report_sec {
+                log::warn!("sim: {t1:.0} secs");
+                next_report_sec += REPORT_SEC;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::Channel;
+    use crate::rinex::load_rinex_nav;
+    use crate::state::GnssState;
+    use std::sync::{Arc, Mutex};
+
+    const RINEX_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/rinex/example.99n");
+
+    #[test]
+    fn acquisition() {
+        let fs = 2_046_000.0;
+        let (week, sow) = (1025, 409904);
+        let file_type = IQFileType::TypePairInt16;
+        let ephs = load_rinex_nav(Path::new(RINEX_FILE)).unwrap();
+        let cfg = SimConfig {
+            fs,
+            fi: 0.0,
+            file_type: file_type.clone(),
+            week,
+            sow,
+            duration_sec: 0.012,
+            trajectory: vec![(0.0, parse_position("46.95,7.45,500").unwrap())],
+            seed: 1,
+            almanac: get_almanac(&ephs, week, sow),
+            iono_utc: IonoUtc::default(),
+        };
+        let sats = ephs.iter().map(|&eph| SimSat { eph, cn0: 48.0 }).collect();
+        let mut sim = Simulator::new(cfg, sats).unwrap();
+        let path = std::env::temp_dir().join(format!("gnss-rcv-sim-{}.bin", std::process::id()));
+        sim.write(&path, false).unwrap();
+        let mut rec = IQRecording::new(&path, fs, &file_type).unwrap();
+        let iq_vec = rec.read_iq_data(0, 12 * 2046).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(iq_vec.len(), 12 * 2046);
+
+        let cfg = &sim.cfg;
+        let rx = cfg.get_rx_position(0.0);
+        for sim_ch in &sim.channels {
+            let sv = sim_ch.eph.sv;
+            let state = Arc::new(Mutex::new(GnssState::new()));
+            let mut ch = Channel::new("L1CA", sv, fs, 0.0, 0.0, state.clone()).unwrap();
+            for k in 1..=10 {
+                let ts_sec = (k + 1) as f64 * 1e-3;
+                ch.process_samples(&iq_vec[(k - 1) * 2046..(k + 1) * 2046], ts_sec);
+            }
+            assert!(ch.is_state_tracking(), "{sv}: no lock");
+
+            // the code starts delay after each ms of the receiver
+            let delay = sim_ch.get_delay(cfg, 0.01, &rx);
+            let code_off = delay.rem_euclid(CODE_PERIOD);
+            let mut err_chips = (ch.get_code_off_sec() - code_off) / CODE_PERIOD * 1023.0;
+            err_chips -= (err_chips / 1023.0).round() * 1023.0;
+            assert!(
+                err_chips.abs() <= 1.0,
+                "{sv}: code phase error {err_chips:.2} chips"
+            );
+
+            let doppler = -Code::get_code_freq("L1CA")
+                * (sim_ch.get_delay(cfg, 0.011, &rx) - sim_ch.get_delay(cfg, 0.01, &rx))
+                / 1e-3;
+            let doppler_acq = state.lock().unwrap().channels[&ch.id].doppler_hz;
+            // within one 320 Hz doppler bin
+            assert!(
+                (doppler_acq - doppler).abs() <= 320.0,
+                "{sv}: doppler {doppler_acq:.0} vs {doppler:.0} Hz"
+            );
+        }
+    }
+}
@@ -4,40 +4,14 @@ use gnss_rtk::prelude::{
     AprioriPosition, Candidate, Carrier, Config, Duration, Epoch, InterpolationResult,
     IonosphereBias, Method, Observation, Solver, TroposphereBias, Vector3,
 };
-use map_3d::{Ellipsoid, ecef2geodetic};
+use map_3d::{Ellipsoid, ecef2geodetic, geodetic2ecef};
 use once_cell::sync::Lazy;
 use std::sync::{Arc, Mutex};
 
-use crate::{
-    constants::{EARTH_MU_GPS, EARTH_ROTATION_RATE, SPEED_OF_LIGHT},
-    ephemeris::Ephemeris,
-    state::GnssState,
-};
+use crate::{constants::SPEED_OF_LIGHT, ephemeris::Ephemeris, state::GnssState};
 
 const PI: f64 = std::f64::consts::PI;
 
-fn get_eccentric_anomaly(eph: &Ephemeris, t_k: f64) -> f64 {
-    // computed mean motion
-    let n0 = (EARTH_MU_GPS / eph.a.powi(3)).sqrt();
-    // corrected mean motion
-    let n = n0 + eph.deln;
-    // mean anomaly
-    let mk = eph.m0 + n * t_k;
-
-    let mut e = mk;
-    let mut e_k = 0.0;
-    let mut n_iter = 0;
-
-    while (e - e_k).abs() > 1e-14 && n_iter < 30 {
-        e_k = e;
-        e = e + (mk - e + eph.ecc * e.sin()) / (1.0 - eph.ecc * e.cos());
-        n_iter += 1;
-    }
-    assert!(n_iter < 20);
-
-    e
-}
-
 fn compute_sv_position_ecef(eph: &Ephemeris, t: Epoch) -> (f64, f64, f64) {
     let mut dte = (t - eph.toe_gpst).to_seconds();
 
@@ -51,28 +25,7 @@ fn compute_sv_position_ecef(eph: &Ephemeris, t: Epoch) -> (f64, f64, f64) {
         dte += 604800.0;
     }
 
-    let ecc_anomaly = get_eccentric_anomaly(eph, dte);
-    let v_k =
-        ((1.0 - eph.ecc.powi(2)).sqrt() * ecc_anomaly.sin()).atan2(ecc_anomaly.cos() - eph.ecc);
-
-    let phi_k = v_k + eph.omg;
-    let duk = eph.cus * (2.0 * phi_k).sin() + eph.cuc * (2.0 * phi_k).cos();
-    let drk = eph.crs * (2.0 * phi_k).sin() + eph.crc * (2.0 * phi_k).cos();
-    let dik = eph.cis * (2.0 * phi_k).sin() + eph.cic * (2.0 * phi_k).cos();
-
-    let uk = phi_k + duk;
-    let rk = eph.a * (1.0 - eph.ecc * ecc_anomaly.cos()) + drk;
-    let ik = eph.i0 + eph.i_dot * dte + dik;
-
-    let orb_plane_x = rk * uk.cos();
-    let orb_plane_y = rk * uk.sin();
-
-    let omega =
-        eph.omg0 + (eph.omg_dot - EARTH_ROTATION_RATE) * dte - EARTH_ROTATION_RATE * eph.toe as f64;
-
-    let ecef_x = orb_plane_x * omega.cos() - orb_plane_y * ik.cos() * omega.sin();
-    let ecef_y = orb_plane_x * omega.sin() + orb_plane_y * ik.cos() * omega.cos();
-    let ecef_z = orb_plane_y * ik.sin();
+    let (ecef_x, ecef_y, ecef_z) = eph.get_sv_position_ecef(dte);
 
     log::warn!(
         "{}: position: x={:8.1} y={:8.1} z={:8.1} h={:.1}",
@@ -93,6 +46,15 @@ fn compute_sv_position_ecef(eph: &Ephemeris, t: Epoch) -> (f64, f64, f64) {
     (ecef_x, ecef_y, ecef_z)
 }
 
+// azimuth and elevation (rad) of a line of sight in ECEF, from lat/lon (deg)
+fn get_azimuth_elevation(lat: f64, lon: f64, los: &[f64; 3]) -> (f64, f64) {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    let e = -lon.sin() * los[0] + lon.cos() * los[1];
+    let n = -lat.sin() * lon.cos() * los[0] - lat.sin() * lon.sin() * los[1] + lat.cos() * los[2];
+    let u = lat.cos() * lon.cos() * los[0] + lat.cos() * lon.sin() * los[1] + lat.sin() * los[2];
+    (e.atan2(n), u.atan2((e * e + n * n).sqrt()))
+}
+
 fn get_tropo_iono_bias() -> (TroposphereBias, IonosphereBias) {
     let iono_bias = IonosphereBias {
         kb_model: None,
@@ -136,6 +98,42 @@ impl PositionSolver {
         Self { solver, pub_state }
     }
 
+    // SBAS correction of the pseudorange of eph's SV: fast and long term
+    // corrections, and ionospheric delay once the receiver position is known.
+    // The long term corrections of the satellite position and clock are
+    // folded into the pseudorange.
+    fn get_sbas_corr(&self, eph: &Ephemeris, ts_sec: f64, now_gpst: Epoch) -> f64 {
+        let st = self.pub_state.lock().unwrap();
+        let Some((sbas, prc)) = st.get_sbas_prc(eph.sv, ts_sec) else {
+            return 0.0;
+        };
+        let mut corr = prc;
+        let has_position = st.latitude != 0.0 || st.longitude != 0.0 || st.height != 0.0;
+        let (lat, lon, h) = (st.latitude, st.longitude, st.height * 1000.0);
+        let rx = geodetic2ecef(lat.to_radians(), lon.to_radians(), h, Ellipsoid::WGS84);
+        let sv = compute_sv_position_ecef(eph, now_gpst);
+        let los = [sv.0 - rx.0, sv.1 - rx.1, sv.2 - rx.2];
+        let range = (los[0].powi(2) + los[1].powi(2) + los[2].powi(2)).sqrt();
+
+        let tod = (eph.tow as f64 + ts_sec - eph.ts_sec).rem_euclid(86400.0);
+        if let Some(lcorr) = sbas.get_long_corr(eph.sv).filter(|l| l.iode == eph.iode)
+            && let Some((dpos, dclk)) = lcorr.get_corr(ts_sec, tod)
+        {
+            corr += dclk * SPEED_OF_LIGHT;
+            if has_position {
+                corr -= (0..3).map(|i| los[i] * dpos[i]).sum::<f64>() / range;
+            }
+        }
+        if has_position {
+            let (az, el) = get_azimuth_elevation(lat, lon, &los);
+            if let Some(delay) = sbas.get_iono_delay(lat, lon, az, el, ts_sec) {
+                corr -= delay;
+            }
+        }
+        log::debug!("{} - sbas prc={prc:.3}m corr={corr:.3}m", eph.sv);
+        corr
+    }
+
     pub fn compute_position(&mut self, ts_sec: f64, ephs: &Vec<Ephemeris>) {
         {
             let mut glob_ephs = SOLVER_EPHEMERIS.lock().unwrap();
@@ -170,7 +168,8 @@ impl PositionSolver {
         for eph in ephs {
             let e_gpst = eph.tow_gpst + Duration::from_seconds(ts_sec - eph.ts_sec);
             let pseudo_range_sec = (e_gpst - min_gpst).to_seconds() + eph.code_off_sec;
-            let pseudo_range = pseudo_range_sec * SPEED_OF_LIGHT;
+            let mut pseudo_range = pseudo_range_sec * SPEED_OF_LIGHT;
+            pseudo_range += self.get_sbas_corr(eph, ts_sec, now_gpst);
             let dt = (now_gpst - eph.tow_gpst).to_seconds();
             let clock_corr = eph.f0 + eph.f1 * dt + eph.f2 * dt.powi(2);
             assert!(dt >= 0.0);
@@ -1,4 +1,4 @@
-use crate::{almanac::Almanac, channel::State};
+use crate::{almanac::Almanac, almanac::IonoUtc, channel::State, sbas::Sbas};
 use gnss_rs::sv::SV;
 use gnss_rtk::prelude::Epoch;
 use std::collections::HashMap;
@@ -7,6 +7,9 @@ pub struct UpdateFunc {
     pub func: Box<dyn Fn() + Send + Sync>,
 }
 
+// a satellite may be tracked on several signals, e.g. L1CA and L5Q
+pub type ChannelId = (SV, String);
+
 pub struct ChannelState {
     pub state: State,
     pub cn0: f64,
@@ -28,16 +31,30 @@ impl Default for ChannelState {
     }
 }
 
+// a channel state change worth recording, e.g. LOCK or LOST
+pub struct ChannelEvent {
+    pub ts_sec: f64,
+    pub id: ChannelId,
+    pub label: String,
+    pub cn0: f64,
+}
+
 pub struct GnssState {
     pub tow_gpst: Epoch,
     pub almanac: Vec<Almanac>,
+    pub iono_utc: IonoUtc,
     pub utc_adj: bool,
     pub ion_adj: bool,
     pub latitude: f64,
     pub longitude: f64,
     pub height: f64,
 
-    pub channels: HashMap<SV, ChannelState>,
+    pub channels: HashMap<ChannelId, ChannelState>,
+    pub sbas: HashMap<u8, Sbas>, // corrections, by GEO PRN
+    pub events: Vec<ChannelEvent>,
+    pub record_events: bool,     // events are only kept for SigMF annotations
+    pub num_lost_samples: usize, // dropped by live sources
+    pub num_gaps: usize,
     pub update_func: UpdateFunc,
 }
 
@@ -46,12 +63,18 @@ impl GnssState {
         Self {
             tow_gpst: Epoch::default(),
             almanac: vec![Almanac::default(); 32],
+            iono_utc: IonoUtc::default(),
             utc_adj: false,
             ion_adj: false,
             latitude: 0.0,
             longitude: 0.0,
             height: 0.0,
-            channels: HashMap::<SV, ChannelState>::new(),
+            channels: HashMap::<ChannelId, ChannelState>::new(),
+            sbas: HashMap::<u8, Sbas>::new(),
+            events: vec![],
+            record_events: false,
+            num_lost_samples: 0,
+            num_gaps: 0,
             update_func: UpdateFunc {
                 func: Box::new(|| {}),
             },
@@ -60,4 +83,15 @@ impl GnssState {
     pub fn set_update_func(&mut self, func: Box<dyn Fn() + Send + Sync>) {
         self.update_func.func = func;
     }
+
+    // SBAS fast correction for sv at receiver time ts_sec, from the most
+    // recent message of any GEO, along with the corrections of that GEO
+    pub fn get_sbas_prc(&self, sv: SV, ts_sec: f64) -> Option<(&Sbas, f64)> {
+        self.sbas
+            .values()
+            .filter_map(|sbas| sbas.get_fast_corr(sv).map(|fcorr| (sbas, fcorr)))
+            .filter_map(|(sbas, fcorr)| Some((sbas, fcorr.ts_sec, fcorr.get_prc(ts_sec)?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(sbas, _, prc)| (sbas, prc))
+    }
 }
@@ -0,0 +1,111 @@
+use colored::Colorize;
+use rustfft::num_complex::Complex64;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::receiver::IQReader;
+use crate::recording::{IQFileType, IQRecording, ReadStats};
+
+const STDIN_PATH: &str = "-";
+const MAX_READ_SAMPLES: usize = 1 << 20; // bound on a single read, e.g. when skipping
+
+// true for "-" (stdin) and anything that isn't a regular file, e.g. a FIFO
+pub fn is_stream(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH || path.metadata().is_ok_and(|m| !m.is_file())
+}
+
+// Raw IQ samples read from stdin or a named pipe, e.g.
+//   rtl_sdr -f 1575420000 -s 2046000 - | gnss-rcv -f - -t rtlsdr-file
+// A stream only goes forward: the samples before the last ones read are gone.
+pub struct IQStream {
+    reader: Box<dyn Read + Send>,
+    file_type: IQFileType,
+    buf: Vec<u8>,
+    pending: VecDeque<Complex64>, // samples decoded but not handed out yet
+    off_samples: usize,           // stream offset of the first pending sample
+    stats: ReadStats,
+}
+
+impl IQStream {
+    pub fn new(path: &Path, file_type: &IQFileType) -> Result<Self, Box<dyn Error>> {
+        let reader: Box<dyn Read + Send> = if path.as_os_str() == STDIN_PATH {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(path)?)
+        };
+        println!(
+            "stream: {} -- {file_type}",
+            path.display().to_string().green()
+        );
+        Ok(Self {
+            reader,
+            file_type: file_type.clone(),
+            buf: vec![],
+            pending: VecDeque::new(),
+            off_samples: 0,
+            stats: ReadStats::default(),
+        })
+    }
+
+    // reads and decodes at least num_samples, blocking until they arrive
+    fn fill(&mut self, num_samples: usize) -> Result<(), Box<dyn Error>> {
+        let ts = Instant::now();
+        let bits = IQRecording::get_sample_size_bits(&self.file_type);
+        self.buf.resize((num_samples * bits).div_ceil(8), 0);
+
+        let res = self.reader.read_exact(&mut self.buf);
+        self.stats.num_reads += 1;
+        self.stats.elapsed += ts.elapsed();
+        match res {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err("end of stream".into());
+            }
+            Err(e) => return Err(e.into()),
+            Ok(()) => {}
+        }
+        self.stats.num_bytes += self.buf.len() as u64;
+        self.pending
+            .extend(IQRecording::decode(&self.buf, &self.file_type));
+        Ok(())
+    }
+}
+
+impl IQReader for IQStream {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        if off_samples < self.off_samples {
+            return Err(format!(
+                "stream: offset {off_samples} already consumed (at {})",
+                self.off_samples
+            )
+            .into());
+        }
+
+        let mut skip = off_samples - self.off_samples;
+        loop {
+            let n = usize::min(skip, self.pending.len());
+            self.pending.drain(..n);
+            self.off_samples += n;
+            skip -= n;
+            if skip == 0 && self.pending.len() >= num_samples {
+                break;
+            }
+            let missing = skip + num_samples - self.pending.len();
+            self.fill(usize::min(missing, MAX_READ_SAMPLES))?;
+        }
+
+        self.off_samples += num_samples;
+        Ok(self.pending.drain(..num_samples).collect())
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        Some(self.stats.clone())
+    }
+}
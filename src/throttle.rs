@@ -0,0 +1,143 @@
+use colored::Colorize;
+use rustfft::num_complex::Complex64;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::queue::QUEUE_SEC;
+use crate::receiver::IQReader;
+use crate::recording::ReadStats;
+
+const REPORT_SEC: f64 = 10.0; // of samples, between two reports
+
+// Releases the samples of a recording at the pace they'd come from a device:
+// fs times speed, e.g. 0.5 or 10. The samples are handed out once the wall
+// clock reaches them. When processing doesn't keep up, they are late instead:
+// beyond QUEUE_SEC of lag, a live source would start dropping samples.
+pub struct IQThrottle {
+    iq_feed: Box<dyn IQReader>,
+    fs: f64,
+    speed: f64,
+    ts: Option<Instant>, // first read
+    start_off: usize,
+    end_off: usize, // after the last samples read
+    slept: Duration,
+    lag_sec: f64,
+    max_lag_sec: f64,
+    next_report_sec: f64,
+}
+
+impl IQThrottle {
+    pub fn new(iq_feed: Box<dyn IQReader>, fs: f64, speed: f64) -> Self {
+        log::warn!(
+            "real-time playback: {speed}x -- {:.1} KHz",
+            fs * speed / 1000.0
+        );
+        Self {
+            iq_feed,
+            fs,
+            speed,
+            ts: None,
+            start_off: 0,
+            end_off: 0,
+            slept: Duration::ZERO,
+            lag_sec: 0.0,
+            max_lag_sec: 0.0,
+            next_report_sec: REPORT_SEC,
+        }
+    }
+
+    // samples released so far, in seconds of the recording
+    fn get_stream_sec(&self) -> f64 {
+        (self.end_off - self.start_off) as f64 / self.fs
+    }
+
+    // Time spent processing, relative to the real-time budget: above 100%,
+    // the receiver can't keep up at this speed.
+    fn get_load(&self) -> f64 {
+        let elapsed = self.ts.map_or(Duration::ZERO, |ts| ts.elapsed());
+        let budget = self.get_stream_sec() / self.speed;
+        if budget == 0.0 {
+            return 0.0;
+        }
+        elapsed.saturating_sub(self.slept).as_secs_f64() / budget
+    }
+
+    fn report(&self) {
+        let msg = format!(
+            "real-time {}x: t={:.0}s load={:.0}% lag={:.2}s max_lag={:.2}s",
+            self.speed,
+            self.get_stream_sec(),
+            100.0 * self.get_load(),
+            self.lag_sec,
+            self.max_lag_sec
+        );
+        if self.lag_sec > QUEUE_SEC {
+            log::warn!("{}", msg.red());
+        } else {
+            log::warn!("{msg}");
+        }
+    }
+}
+
+impl Drop for IQThrottle {
+    fn drop(&mut self) {
+        self.report();
+        if self.max_lag_sec > QUEUE_SEC {
+            log::warn!(
+                "{}",
+                format!(
+                    "processing doesn't keep up at {}x: a live source would have dropped samples",
+                    self.speed
+                )
+                .red()
+            );
+        } else {
+            log::warn!("processing keeps up at {}x", self.speed);
+        }
+    }
+}
+
+impl IQReader for IQThrottle {
+    fn get_iq_data(
+        &mut self,
+        off_samples: usize,
+        num_samples: usize,
+    ) -> Result<Vec<Complex64>, Box<dyn std::error::Error>> {
+        let ts = *self.ts.get_or_insert_with(|| {
+            self.start_off = off_samples;
+            self.end_off = off_samples;
+            Instant::now()
+        });
+        let v = self.iq_feed.get_iq_data(off_samples, num_samples)?;
+        self.end_off = usize::max(self.end_off, off_samples + v.len());
+
+        let due = self.get_stream_sec() / self.speed;
+        let elapsed = ts.elapsed().as_secs_f64();
+        if due > elapsed {
+            let ts_sleep = Instant::now();
+            thread::sleep(Duration::from_secs_f64(due - elapsed));
+            self.slept += ts_sleep.elapsed();
+            self.lag_sec = 0.0;
+        } else {
+            self.lag_sec = elapsed - due;
+            self.max_lag_sec = self.max_lag_sec.max(self.lag_sec);
+        }
+        if self.get_stream_sec() >= self.next_report_sec {
+            self.report();
+            self.next_report_sec += REPORT_SEC;
+        }
+        Ok(v)
+    }
+
+    fn get_stats(&self) -> Option<ReadStats> {
+        self.iq_feed.get_stats()
+    }
+
+    fn get_gain_db(&self) -> Option<f64> {
+        self.iq_feed.get_gain_db()
+    }
+
+    fn take_lost_samples(&mut self) -> usize {
+        self.iq_feed.take_lost_samples()
+    }
+}
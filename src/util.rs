@@ -173,3 +173,46 @@ pub fn setbitu(buf: &mut [u8], pos: usize, len: usize, data: u32) {
         mask >>= 1;
     }
 }
+
+// field split in two parts, e.g. across two words of an LNAV subframe
+pub fn setbitu2(buf: &mut [u8], p1: usize, l1: usize, p2: usize, l2: usize, data: u32) {
+    assert!(l1 + l2 <= 32);
+    setbitu(buf, p1, l1, data >> l2);
+    setbitu(buf, p2, l2, data & ((1 << l2) - 1));
+}
+
+// CRC-24Q (Qualcomm), computed over a sequence of bits (one bit per u8)
+pub fn crc24q_bits(bits: &[u8]) -> u32 {
+    const POLY: u32 = 0x1864CFB;
+    let mut crc = 0u32;
+    for &b in bits {
+        let top = ((crc >> 23) & 1) as u8 ^ b;
+        crc = (crc << 1) & 0xFFFFFF;
+        if top == 1 {
+            crc ^= POLY & 0xFFFFFF;
+        }
+    }
+    crc
+}
+
+pub fn bits_to_bytes(bits: &[u8]) -> Vec<u8> {
+    let mut buf = vec![0u8; bits.len().div_ceil(8)];
+    for (i, b) in bits.iter().enumerate() {
+        setbitu(&mut buf, i, 1, *b as u32);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc24q() {
+        let bits: Vec<u8> = b"123456789"
+            .iter()
+            .flat_map(|c| (0..8).rev().map(move |i| (c >> i) & 1))
+            .collect();
+        assert_eq!(crc24q_bits(&bits), 0xcde703);
+    }
+}